@@ -0,0 +1,423 @@
+//! Host tool for bringing up ADS1298 boards through Linux spidev, or against the simulator
+//!
+//! ```text
+//! ads1298-cli --spi /dev/spidev0.0 --drdy-line 25 dump
+//! ads1298-cli --spi /dev/spidev0.0 --drdy-line 25 write CONFIG1 0x86
+//! ads1298-cli --simulate self-test
+//! ads1298-cli --simulate stream --seconds 5 --format bdf --output ecg.bdf
+//! ```
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use ads1298_rs::driver::clock::ClockSource;
+use ads1298_rs::driver::frame::{CHANNELS, FRAME_SIZE};
+use ads1298_rs::driver::initialization::{Default8Lead1x500, Initializer};
+use ads1298_rs::driver::profile::Profile;
+use ads1298_rs::driver::registers::map::{REGISTER_COUNT, REGISTER_NAMES};
+use ads1298_rs::driver::self_test::TEST_SIGNAL_TOLERANCE;
+use ads1298_rs::driver::ADS1298;
+use ads1298_rs::recording::capture::{CaptureHeader, CaptureWriter};
+use ads1298_rs::recording::csv::CsvWriter;
+use ads1298_rs::recording::edf::{EdfFormat, EdfWriter};
+use ads1298_rs::recording::{LeadMapping, RecordingInfo, StartTime};
+use ads1298_rs::simulator::Simulator;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use embedded_hal::digital::{Error as _, InputPin};
+use embedded_hal::spi::SpiDevice;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Registers `write` refuses
+const READ_ONLY: [&str; 3] = ["ID", "LOFF_STATP", "LOFF_STATN"];
+
+/// Longest wait for `DRDY#` while streaming
+const DRDY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Inspect, test and stream an ADS1298 through Linux spidev
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(flatten)]
+    device: DeviceArgs,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Args)]
+struct DeviceArgs {
+    /// Use a simulated ADS1298 instead of hardware, reset for every command
+    #[arg(long, conflicts_with_all = ["spi", "spi_hz", "drdy_chip", "drdy_line", "clock_hz"])]
+    simulate: bool,
+    /// spidev device, in SPI mode 1
+    #[arg(long, default_value = "/dev/spidev0.0")]
+    spi: PathBuf,
+    /// SCLK frequency, in Hz
+    #[arg(long, default_value_t = 4_000_000)]
+    spi_hz: u32,
+    /// GPIO character device of `DRDY#`
+    #[arg(long, default_value = "/dev/gpiochip0")]
+    drdy_chip: PathBuf,
+    /// GPIO line of `DRDY#`
+    #[arg(long, required_unless_present = "simulate")]
+    drdy_line: Option<u32>,
+    /// fCLK of an external clock on `CLK`, in Hz, the internal oscillator when absent
+    #[arg(long)]
+    clock_hz: Option<u32>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Read and decode every register
+    Dump {
+        #[arg(long, value_enum, default_value_t = DumpFormat::Text)]
+        format: DumpFormat,
+    },
+    /// Write one register and show what changed
+    Write {
+        /// Name such as `CONFIG1`, or address such as `0x01`
+        #[arg(value_parser = parse_register)]
+        register: u8,
+        /// Such as `0x86`, `0b10000110` or `134`
+        #[arg(value_parser = parse_byte)]
+        value: u8,
+    },
+    /// Check `ID`, the internal test signal and the input-short noise
+    SelfTest {
+        #[command(flatten)]
+        setup: Setup,
+        /// Input-short samples per channel
+        #[arg(long, default_value_t = 250)]
+        samples: usize,
+    },
+    /// Stream frames to stdout or to a recording
+    Stream(StreamArgs),
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DumpFormat {
+    Text,
+    Json,
+    Toml,
+}
+
+/// Configuration before `self-test` and `stream`
+#[derive(Args)]
+struct Setup {
+    /// Initialize from a TOML or JSON `Profile` instead of the 8-lead 500 SPS default
+    #[arg(long, conflicts_with = "no_init")]
+    profile: Option<PathBuf>,
+    /// Keep the current registers, only leaving `RDATAC` mode
+    #[arg(long)]
+    no_init: bool,
+}
+
+#[derive(Args)]
+struct StreamArgs {
+    #[command(flatten)]
+    setup: Setup,
+    /// Number of frames
+    #[arg(long, conflicts_with = "seconds")]
+    frames: Option<u64>,
+    /// Duration at the configured data rate
+    #[arg(long, default_value_t = 10.0)]
+    seconds: f64,
+    #[arg(long, value_enum, default_value_t = StreamFormat::Csv)]
+    format: StreamFormat,
+    /// File to write, required except for CSV which defaults to stdout
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+    /// Labels of the channels
+    #[arg(long, value_enum, default_value_t = Leads::Numbered)]
+    leads: Leads,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum StreamFormat {
+    Csv,
+    Bdf,
+    Edf,
+    /// Raw frames with the registers, see `recording::capture`
+    Capture,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Leads {
+    /// `CH1` ~ `CH8`
+    Numbered,
+    /// Wiring of the ADS1298ECGFE-PDK
+    #[value(name = "12-lead")]
+    TwelveLead,
+}
+
+enum Sink {
+    Csv(CsvWriter<Box<dyn Write>>),
+    Edf(EdfWriter<BufWriter<File>>),
+    Capture(CaptureWriter<BufWriter<File>>),
+}
+
+fn main() -> ExitCode {
+    match open_and_run(Cli::parse()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Returns `false` when a check failed
+fn open_and_run(cli: Cli) -> Result<bool> {
+    if cli.device.simulate {
+        let simulator = Simulator::new();
+        let mut driver = ADS1298::new(simulator.spi());
+        return run(cli.command, &mut driver, &mut simulator.drdy());
+    }
+    let clock = match cli.device.clock_hz {
+        Some(hz) => ClockSource::External(hz),
+        None => ClockSource::Internal,
+    };
+    hardware(cli, clock)
+}
+
+#[cfg(target_os = "linux")]
+fn hardware(cli: Cli, clock: ClockSource) -> Result<bool> {
+    use linux_embedded_hal::gpio_cdev::{Chip, LineRequestFlags};
+    use linux_embedded_hal::spidev::{SpiModeFlags, SpidevOptions};
+    use linux_embedded_hal::{CdevPin, SpidevDevice};
+
+    let device = &cli.device;
+    let open_failed = |e: &dyn Error| format!("failed to open {}: {e}", device.spi.display());
+    // Chip select is handled by the kernel
+    let mut spi = SpidevDevice::open(&device.spi).map_err(|e| open_failed(&e))?;
+    spi.configure(
+        &SpidevOptions::new()
+            .bits_per_word(8)
+            .max_speed_hz(device.spi_hz)
+            .mode(SpiModeFlags::SPI_MODE_1)
+            .build(),
+    )
+    .map_err(|e| open_failed(&e))?;
+    let line = device.drdy_line.expect("required without --simulate");
+    let mut drdy = Chip::new(&device.drdy_chip)
+        .and_then(|mut chip| chip.get_line(line))
+        .and_then(|line| line.request(LineRequestFlags::INPUT, 0, env!("CARGO_BIN_NAME")))
+        .and_then(CdevPin::new)
+        .map_err(|e| {
+            let chip = device.drdy_chip.display();
+            format!("failed to request line {line} of {chip}: {e}")
+        })?;
+    run(cli.command, &mut ADS1298::with_clock(spi, clock), &mut drdy)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn hardware(_cli: Cli, _clock: ClockSource) -> Result<bool> {
+    Err("hardware access needs Linux spidev, use --simulate".into())
+}
+
+fn run<SPI, DRDY>(command: Command, driver: &mut ADS1298<SPI>, drdy: &mut DRDY) -> Result<bool>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+    DRDY: InputPin,
+{
+    match command {
+        Command::Dump { format } => {
+            let map = driver.read_register_map()?;
+            match format {
+                DumpFormat::Text => print!("{map}"),
+                DumpFormat::Json => println!("{}", serde_json::to_string_pretty(&map)?),
+                DumpFormat::Toml => print!("{}", toml::to_string(&map)?),
+            }
+            Ok(true)
+        }
+        Command::Write { register, value } => {
+            let name = REGISTER_NAMES[usize::from(register)];
+            if READ_ONLY.contains(&name) {
+                return Err(format!("{name} is read-only").into());
+            }
+            let before = driver.read_register_map()?;
+            driver.write_register(register, value)?;
+            let changes = before.diff(&driver.read_register_map()?);
+            if changes.is_empty() {
+                println!("{name} {register:02X}h unchanged");
+            }
+            for change in changes {
+                println!("{change}");
+            }
+            Ok(true)
+        }
+        Command::SelfTest { setup, samples } => {
+            setup.apply(driver)?;
+            let report = driver.self_test(drdy, samples)?;
+            let verdict = |ok| if ok { "ok" } else { "FAIL" };
+            println!("ID   {:02X}h {}", report.id.0, verdict(report.id_ok));
+            for (channel, i) in (1..).zip(0..CHANNELS) {
+                let Some(uv) = report.test_signal_uv[i] else {
+                    println!("CH{channel}  powered down");
+                    continue;
+                };
+                println!(
+                    "CH{channel}  test signal {uv:8.1} µV {:<4}  noise {:6.2} µVrms  offset {:8.1} µV",
+                    verdict(report.channel_ok(channel)),
+                    report.noise.rms_uv[i],
+                    report.noise.offset_uv[i],
+                );
+            }
+            println!(
+                "Test signal expected {:.1} µV ± {:.0} %",
+                report.expected_uv,
+                TEST_SIGNAL_TOLERANCE * 100.0
+            );
+            println!("{}", if report.is_ok() { "PASS" } else { "FAIL" });
+            Ok(report.is_ok())
+        }
+        Command::Stream(args) => stream(args, driver, drdy),
+    }
+}
+
+impl Setup {
+    fn apply<SPI>(&self, driver: &mut ADS1298<SPI>) -> Result<()>
+    where
+        SPI: SpiDevice,
+        SPI::Error: 'static,
+    {
+        match &self.profile {
+            _ if self.no_init => driver.operator.stop_stream()?,
+            Some(path) => driver.init(&Profile::load(path)?)?,
+            None => driver.init(Default8Lead1x500)?,
+        }
+        Ok(())
+    }
+}
+
+fn stream<SPI, DRDY>(args: StreamArgs, driver: &mut ADS1298<SPI>, drdy: &mut DRDY) -> Result<bool>
+where
+    SPI: SpiDevice,
+    SPI::Error: 'static,
+    DRDY: InputPin,
+{
+    args.setup.apply(driver)?;
+    let map = driver.read_register_map()?;
+    let leads = match args.leads {
+        Leads::Numbered => LeadMapping::numbered(),
+        Leads::TwelveLead => LeadMapping::twelve_lead(),
+    };
+    let info = RecordingInfo::from_register_map(&map, driver.clock(), &leads, now())?;
+    let frames = args
+        .frames
+        .unwrap_or_else(|| (args.seconds * f64::from(info.data_rate_hz)).round() as u64);
+
+    let mut sink = match (args.format, &args.output) {
+        (StreamFormat::Csv, output) => {
+            let output: Box<dyn Write> = match output {
+                Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+                None => Box::new(io::stdout().lock()),
+            };
+            Sink::Csv(CsvWriter::new(output, &info)?)
+        }
+        (StreamFormat::Bdf, Some(path)) => {
+            Sink::Edf(EdfWriter::create(path, EdfFormat::Bdf, &info)?)
+        }
+        (StreamFormat::Edf, Some(path)) => {
+            Sink::Edf(EdfWriter::create(path, EdfFormat::Edf, &info)?)
+        }
+        (StreamFormat::Capture, Some(path)) => {
+            Sink::Capture(CaptureWriter::create(path, &CaptureHeader::read(driver)?)?)
+        }
+        (_, None) => return Err("--output is required for bdf, edf and capture".into()),
+    };
+
+    driver.operator.start()?;
+    let mut reader = driver.stream_reader(drdy)?;
+    let mut raw = [0; FRAME_SIZE];
+    for _ in 0..frames {
+        // Gives up instead of waiting forever in `read_into`
+        wait_data_ready(&mut reader.drdy)?;
+        let mut frame = reader.read_into(&mut raw)?.to_frame();
+        frame.apply_offsets(&reader.driver.offsets());
+        match &mut sink {
+            Sink::Csv(writer) => writer.write_frame(&frame)?,
+            Sink::Edf(writer) => writer.write_frame(&frame)?,
+            Sink::Capture(writer) => writer.write_frame(&raw)?,
+        }
+    }
+    match sink {
+        Sink::Csv(writer) => drop(writer.finish()?),
+        Sink::Edf(writer) => drop(writer.finish()?),
+        Sink::Capture(writer) => drop(writer.finish()?),
+    }
+    Ok(true)
+}
+
+/// Busy wait until `DRDY#` becomes `low`, giving up after `DRDY_TIMEOUT`
+fn wait_data_ready<DRDY: InputPin>(drdy: &mut DRDY) -> Result<()> {
+    let start = Instant::now();
+    while drdy
+        .is_high()
+        .map_err(|e| format!("failed to sample DRDY: {}", e.kind()))?
+    {
+        if start.elapsed() > DRDY_TIMEOUT {
+            return Err("DRDY stayed high, is the device converting?".into());
+        }
+    }
+    Ok(())
+}
+
+/// Register name, case insensitive, or address
+fn parse_register(s: &str) -> std::result::Result<u8, String> {
+    if let Some(address) = REGISTER_NAMES
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(s))
+    {
+        return Ok(address as u8);
+    }
+    match parse_byte(s) {
+        Ok(address) if usize::from(address) < REGISTER_COUNT => Ok(address),
+        _ => Err(format!("no register {s}")),
+    }
+}
+
+/// Hexadecimal with `0x`, binary with `0b`, or decimal
+fn parse_byte(s: &str) -> std::result::Result<u8, String> {
+    let parsed = if let Some(hex) = s.strip_prefix("0x").or(s.strip_prefix("0X")) {
+        u8::from_str_radix(hex, 16)
+    } else if let Some(binary) = s.strip_prefix("0b").or(s.strip_prefix("0B")) {
+        u8::from_str_radix(binary, 2)
+    } else {
+        s.parse()
+    };
+    parsed.map_err(|e| format!("{s}: {e}"))
+}
+
+/// Current time in UTC, there is no time zone database to get the local one
+fn now() -> StartTime {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+    // Civil date of days since 1970-01-01, from "chrono-Compatible Low-Level Date Algorithms"
+    // by H. Hinnant, with eras of 400 years starting on March 1st
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    StartTime {
+        year: year as u16,
+        month: month as u8,
+        day: day as u8,
+        hour: (time / 3600) as u8,
+        minute: (time / 60 % 60) as u8,
+        second: (time % 60) as u8,
+    }
+}
@@ -0,0 +1,202 @@
+//! Decimation of high-rate frames, such as 32 kSPS for pace detection down to 500 SPS
+//!
+//! The rate is divided in stages of at most 8, each a windowed-sinc low-pass evaluated only
+//! for the samples kept, the polyphase form of a decimating FIR. The status words of the frames
+//! between two outputs are ORed together, so a lead-off reported by any of them is kept.
+
+use core::fmt;
+
+use crate::driver::frame::{Frame, CHANNELS, CODE_MAX, CODE_MIN};
+use crate::driver::registers::data::{DataStatus1, DataStatus2, DataStatus3};
+use crate::filters::fir::{convolve, Fir};
+
+/// Taps of the low-pass of every stage
+pub const STAGE_TAPS: usize = 48;
+
+/// Stages at most, `4⁴` divides 32 kSPS down to 125 SPS
+pub const MAX_STAGES: usize = 4;
+
+/// Largest factor of a stage
+const MAX_STAGE_FACTOR: usize = 8;
+
+/// Cutoff of the last stage relative to the output rate, the band above is attenuated
+/// before it aliases
+const PASSBAND: f32 = 0.4;
+
+/// Cutoff of the other stages relative to their output rate
+const INTERMEDIATE_PASSBAND: f32 = 0.45;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DecimationError {
+    /// The input rate is not an integer multiple of the output rate, or the factor does not
+    /// split into `MAX_STAGES` stages of at most 8, in SPS
+    UnsupportedRatio { input_hz: f32, output_hz: f32 },
+}
+
+impl fmt::Display for DecimationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecimationError::UnsupportedRatio {
+                input_hz,
+                output_hz,
+            } => write!(f, "cannot decimate {input_hz} SPS to {output_hz} SPS"),
+        }
+    }
+}
+
+impl core::error::Error for DecimationError {}
+
+/// One decimating low-pass over every channel
+#[derive(Clone, Copy, Debug)]
+struct Stage {
+    factor: usize,
+    coefficients: [f32; STAGE_TAPS],
+    /// Past inputs of each channel, `delay[_][position]` is the newest
+    delay: [[f32; STAGE_TAPS]; CHANNELS],
+    position: usize,
+    /// Inputs since the last output
+    phase: usize,
+}
+
+impl Stage {
+    fn new(factor: usize, input_hz: f32, cutoff_hz: f32) -> Self {
+        let fir = Fir::<STAGE_TAPS>::low_pass(input_hz, cutoff_hz)
+            .expect("cutoff is below the Nyquist frequency of the stage");
+        Stage {
+            factor,
+            coefficients: *fir.coefficients(),
+            delay: [[0.0; STAGE_TAPS]; CHANNELS],
+            position: 0,
+            phase: 0,
+        }
+    }
+
+    fn push(&mut self, x: &[f32; CHANNELS]) -> Option<[f32; CHANNELS]> {
+        self.position = (self.position + 1) % STAGE_TAPS;
+        for (delay, x) in self.delay.iter_mut().zip(x) {
+            delay[self.position] = *x;
+        }
+        self.phase += 1;
+        if self.phase < self.factor {
+            return None;
+        }
+        self.phase = 0;
+        Some(core::array::from_fn(|channel| {
+            let delay = &self.delay[channel];
+            convolve(&self.coefficients, delay, self.position, 0.0, |c, x| c * x)
+        }))
+    }
+
+    fn reset(&mut self) {
+        self.delay = [[0.0; STAGE_TAPS]; CHANNELS];
+        self.phase = 0;
+    }
+}
+
+/// Decimates frames by an integer factor
+///
+/// The delay is about `(STAGE_TAPS - 1) / 2` input samples of each stage.
+#[derive(Clone, Debug)]
+pub struct Decimator {
+    stages: [Option<Stage>; MAX_STAGES],
+    input_hz: f32,
+    output_hz: f32,
+    /// Status words ORed since the last output
+    status: [u8; 3],
+}
+
+impl Decimator {
+    /// `input_hz` and `output_hz` are data rates, see `DataRate::hz`
+    pub fn new(input_hz: f32, output_hz: f32) -> Result<Self, DecimationError> {
+        let unsupported = DecimationError::UnsupportedRatio {
+            input_hz,
+            output_hz,
+        };
+        let ratio = input_hz / output_hz;
+        if !(1.0..=u32::MAX as f32).contains(&ratio) || ratio.fract() != 0.0 {
+            return Err(unsupported);
+        }
+        let factors = split(ratio as usize).ok_or(unsupported)?;
+
+        let mut stages = [None; MAX_STAGES];
+        let mut rate = input_hz;
+        let last = factors.iter().rposition(|f| *f > 1);
+        for (i, factor) in factors.into_iter().enumerate().filter(|(_, f)| *f > 1) {
+            let out = rate / factor as f32;
+            let passband = if Some(i) == last {
+                PASSBAND
+            } else {
+                INTERMEDIATE_PASSBAND
+            };
+            stages[i] = Some(Stage::new(factor, rate, passband * out));
+            rate = out;
+        }
+        Ok(Decimator {
+            stages,
+            input_hz,
+            output_hz,
+            status: [0; 3],
+        })
+    }
+
+    pub fn input_hz(&self) -> f32 {
+        self.input_hz
+    }
+
+    pub fn output_hz(&self) -> f32 {
+        self.output_hz
+    }
+
+    /// Input frames per output frame
+    pub fn factor(&self) -> usize {
+        self.stages.iter().flatten().map(|s| s.factor).product()
+    }
+
+    /// Process the next frame, returns a frame once `factor` frames have been processed
+    ///
+    /// Codes are rounded and saturated at the 24-bit range.
+    pub fn process(&mut self, frame: &Frame) -> Option<Frame> {
+        let status = &frame.status;
+        self.status[0] |= status.ds1.0;
+        self.status[1] |= status.ds2.0;
+        self.status[2] |= status.ds3.0;
+
+        let mut x = frame.channels.map(|code| code as f32);
+        for stage in self.stages.iter_mut().flatten() {
+            x = stage.push(&x)?;
+        }
+
+        let [ds1, ds2, ds3] = core::mem::take(&mut self.status);
+        let mut out = *frame;
+        out.status.ds1 = DataStatus1(ds1);
+        out.status.ds2 = DataStatus2(ds2);
+        out.status.ds3 = DataStatus3(ds3);
+        out.channels = x.map(|y| (y.round() as i32).clamp(CODE_MIN, CODE_MAX));
+        Some(out)
+    }
+
+    /// Clear the state, as if only zeros had been processed
+    pub fn reset(&mut self) {
+        self.stages.iter_mut().flatten().for_each(Stage::reset);
+        self.status = [0; 3];
+    }
+}
+
+/// Split `factor` into stages of 4, then 2, then one of at most 8
+///
+/// A remaining 8 is kept in one stage, so 512 still fits in `MAX_STAGES`.
+fn split(mut factor: usize) -> Option<[usize; MAX_STAGES]> {
+    let mut factors = [1; MAX_STAGES];
+    for stage in factors.iter_mut() {
+        *stage = match factor {
+            1 => break,
+            f if f % 4 == 0 && f != 8 => 4,
+            f if f % 2 == 0 && f != 8 => 2,
+            f if f <= MAX_STAGE_FACTOR => f,
+            _ => return None,
+        };
+        factor /= *stage;
+    }
+    (factor == 1).then_some(factors)
+}
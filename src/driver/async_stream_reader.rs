@@ -0,0 +1,115 @@
+//! Streaming over `embedded-hal-async`, for executors such as Embassy
+//!
+//! `AsyncStreamReader` borrows the driver like `StreamReader`, so `SPI` implements both the
+//! blocking and the async `SpiDevice`. The ADS1298 is configured through the blocking one, for
+//! instance with `ADS1298::init`, and frames are read through the async one.
+
+use core::time::Duration;
+
+use embedded_hal::digital::Error as _;
+use embedded_hal::spi::{ErrorType, SpiDevice};
+use embedded_hal_async::digital::Wait;
+use futures_util::{future, stream, Stream, StreamExt};
+
+use super::frame::{Frame, FRAME_SIZE};
+use super::stream_reader::samples_in;
+use super::{StreamError, ADS1298};
+
+type Error<SPI> = StreamError<<SPI as ErrorType>::Error>;
+
+/// Reads a frame with `RDATA` each time `DRDY` becomes `low`
+pub struct AsyncStreamReader<'a, SPI: SpiDevice, DRDY> {
+    pub driver: &'a mut ADS1298<SPI>,
+    drdy: DRDY,
+}
+
+impl<SPI, DRDY> AsyncStreamReader<'_, SPI, DRDY>
+where
+    SPI: SpiDevice + embedded_hal_async::spi::SpiDevice,
+    DRDY: Wait,
+{
+    pub fn into_inner(self) -> DRDY {
+        self.drdy
+    }
+
+    /// Wait for `DRDY` and read the next frame
+    ///
+    /// Offsets set by `ADS1298::calibrate_offsets` are subtracted.
+    pub async fn read_frame(&mut self) -> Result<Frame, Error<SPI>> {
+        self.drdy
+            .wait_for_low()
+            .await
+            .map_err(|e| StreamError::DataReadyError(e.kind()))?;
+        let mut buffer = [0; FRAME_SIZE];
+        self.driver
+            .operator
+            .read_data_into_async(&mut buffer)
+            .await
+            .map_err(|e| {
+                error!("Streaming from ADS1298 aborted");
+                StreamError::StreamingAbort(e)
+            })?;
+        let mut frame = Frame::from_bytes(&buffer);
+        frame.apply_offsets(&self.driver.offsets());
+        Ok(frame)
+    }
+
+    /// Output data rate of the current `CONFIG1`, in SPS, read over the blocking `SpiDevice`
+    pub fn data_rate_hz(&mut self) -> Result<f32, Error<SPI>> {
+        self.driver
+            .data_rate_hz()
+            .map_err(StreamError::ReadConfigError)?
+            .ok_or(StreamError::ReservedDataRate)
+    }
+}
+
+impl<'a, SPI, DRDY> AsyncStreamReader<'a, SPI, DRDY>
+where
+    SPI: SpiDevice + embedded_hal_async::spi::SpiDevice + 'a,
+    DRDY: Wait + 'a,
+{
+    /// Yield `read_frame` forever, errors included
+    pub fn into_stream(self) -> impl Stream<Item = Result<Frame, Error<SPI>>> + 'a {
+        stream::unfold(self, |mut reader| async move {
+            let frame = reader.read_frame().await;
+            Some((frame, reader))
+        })
+    }
+
+    /// Yield `n` frames
+    pub fn samples(self, n: usize) -> impl Stream<Item = Result<Frame, Error<SPI>>> + 'a {
+        self.into_stream().take(n)
+    }
+
+    /// Yield the frames converted in `duration` at the data rate of the current `CONFIG1`
+    pub fn for_duration(
+        mut self,
+        duration: Duration,
+    ) -> Result<impl Stream<Item = Result<Frame, Error<SPI>>> + 'a, Error<SPI>> {
+        let hz = self.data_rate_hz()?;
+        Ok(self.samples(samples_in(duration, hz)))
+    }
+
+    /// Yield frames until one reports an electrode off, which is not yielded
+    ///
+    /// Errors are yielded and do not end the stream.
+    pub fn until_lead_off(self) -> impl Stream<Item = Result<Frame, Error<SPI>>> + 'a {
+        self.into_stream().take_while(|frame| {
+            let lead_off = matches!(frame, Ok(frame) if frame.status.is_lead_off());
+            future::ready(!lead_off)
+        })
+    }
+}
+
+impl<SPI> ADS1298<SPI>
+where
+    SPI: SpiDevice + embedded_hal_async::spi::SpiDevice,
+{
+    /// `drdy` is the `DRDY` pin
+    pub fn async_stream_reader<DRDY: Wait>(
+        &mut self,
+        drdy: DRDY,
+    ) -> AsyncStreamReader<'_, SPI, DRDY> {
+        AsyncStreamReader { driver: self, drdy }
+    }
+}
@@ -0,0 +1,129 @@
+use embedded_hal::digital::InputPin;
+use embedded_hal::spi::SpiDevice;
+
+use super::frame::{code_to_volts, CHANNELS};
+use super::measurement::MeasureError;
+use super::registers::access::ReadFromRegister;
+use super::registers::data::ChSetReg;
+use super::registers::CONFIG3;
+use super::ADS1298;
+
+/// Result of an input-short measurement, `[0]` is CH1
+///
+/// Voltages are input referred, using the gain of each channel.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NoiseMeasurement {
+    /// Number of samples per channel
+    pub samples: usize,
+    /// Mean code of each channel
+    pub offset: [i32; CHANNELS],
+    /// Mean of each channel, in µV
+    pub offset_uv: [f32; CHANNELS],
+    /// Standard deviation of each channel, in µV
+    pub rms_uv: [f32; CHANNELS],
+    /// Peak-to-peak of each channel, in µV
+    pub peak_to_peak_uv: [f32; CHANNELS],
+}
+
+impl<SPI: SpiDevice> ADS1298<SPI> {
+    /// Short the inputs of every channel and measure DC offset and noise
+    ///
+    /// The gains are kept, all `CHnSET` are restored afterwards. Conversion is restarted if it
+    /// was running, and stays stopped otherwise.
+    /// The device must be in `SDATAC` mode, `drdy` is the `DRDY#` pin.
+    pub fn measure_input_short<DRDY: InputPin>(
+        &mut self,
+        drdy: &mut DRDY,
+        samples: usize,
+    ) -> Result<NoiseMeasurement, MeasureError<SPI::Error>> {
+        let started = self.operator.is_started();
+        let measurement = self.capture_input_short(drdy, samples);
+        self.restore_conversion(started)?;
+        measurement
+    }
+
+    /// `measure_input_short` leaving conversion running
+    pub(crate) fn capture_input_short<DRDY: InputPin>(
+        &mut self,
+        drdy: &mut DRDY,
+        samples: usize,
+    ) -> Result<NoiseMeasurement, MeasureError<SPI::Error>> {
+        let settings = self.read_channel_settings()?;
+        let mut gains = [0u8; CHANNELS];
+        for (channel, (gain, setting)) in (1..).zip(gains.iter_mut().zip(&settings)) {
+            *gain = setting
+                .pga_gain()
+                .ok_or(MeasureError::InvalidGain { channel })?;
+        }
+        let vref = self.read(CONFIG3)?.vref();
+
+        for (channel, setting) in (1..).zip(settings) {
+            let mut shorted = setting;
+            shorted.set_mux(ChSetReg::MUX_INPUT_SHORT);
+            self.write_channel_setting(channel, shorted)?;
+        }
+
+        let mut sum = [0i64; CHANNELS];
+        let mut sum_sq = [0f64; CHANNELS];
+        let mut min = [i32::MAX; CHANNELS];
+        let mut max = [i32::MIN; CHANNELS];
+        let captured = self.capture(drdy, samples, |frame| {
+            for (i, &code) in frame.channels.iter().enumerate() {
+                sum[i] += i64::from(code);
+                sum_sq[i] += f64::from(code) * f64::from(code);
+                min[i] = min[i].min(code);
+                max[i] = max[i].max(code);
+            }
+        });
+
+        for (channel, setting) in (1..).zip(settings) {
+            self.write_channel_setting(channel, setting)?;
+        }
+        captured?;
+
+        let n = samples as f64;
+        let mut measurement = NoiseMeasurement {
+            samples,
+            offset: [0; CHANNELS],
+            offset_uv: [0.0; CHANNELS],
+            rms_uv: [0.0; CHANNELS],
+            peak_to_peak_uv: [0.0; CHANNELS],
+        };
+        for i in 0..CHANNELS {
+            let mean = sum[i] as f64 / n;
+            let variance = (sum_sq[i] / n - mean * mean).max(0.0);
+            let lsb_uv = code_to_volts(1, vref, gains[i]) * 1e6;
+            measurement.offset[i] = mean.round() as i32;
+            measurement.offset_uv[i] = mean as f32 * lsb_uv;
+            measurement.rms_uv[i] = variance.sqrt() as f32 * lsb_uv;
+            measurement.peak_to_peak_uv[i] = (max[i] - min[i]) as f32 * lsb_uv;
+        }
+        debug!("Input short measurement of ADS1298: {:?}", measurement);
+        Ok(measurement)
+    }
+
+    /// Measure the input-short offsets and subtract them from the following frames
+    pub fn calibrate_offsets<DRDY: InputPin>(
+        &mut self,
+        drdy: &mut DRDY,
+        samples: usize,
+    ) -> Result<NoiseMeasurement, MeasureError<SPI::Error>> {
+        let measurement = self.measure_input_short(drdy, samples)?;
+        self.offsets = measurement.offset;
+        Ok(measurement)
+    }
+
+    /// Offsets subtracted from every frame read by `StreamReader`, `[0]` is CH1
+    pub fn offsets(&self) -> [i32; CHANNELS] {
+        self.offsets
+    }
+
+    pub fn set_offsets(&mut self, offsets: [i32; CHANNELS]) {
+        self.offsets = offsets;
+    }
+
+    pub fn clear_offsets(&mut self) {
+        self.offsets = [0; CHANNELS];
+    }
+}
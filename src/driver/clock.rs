@@ -0,0 +1,59 @@
+use super::registers::data::{Config1Reg, Config2Reg};
+
+/// Frequency of the internal oscillator, in Hz
+pub const INTERNAL_CLOCK_HZ: u32 = 2_048_000;
+
+/// Master clock of the ADS1298, selected by the `CLKSEL` pin
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ClockSource {
+    /// Internal 2.048 MHz oscillator, `CLKSEL` = `high`
+    #[default]
+    Internal,
+    /// External clock on the `CLK` pin, in Hz, `CLKSEL` = `low`
+    External(u32),
+}
+
+impl ClockSource {
+    /// fCLK, in Hz
+    pub fn frequency_hz(&self) -> u32 {
+        match *self {
+            ClockSource::Internal => INTERNAL_CLOCK_HZ,
+            ClockSource::External(hz) => hz,
+        }
+    }
+
+    /// tCLK rounded up, in ns
+    pub fn tclk_ns(&self) -> u32 {
+        1_000_000_000u32.div_ceil(self.frequency_hz().max(1))
+    }
+
+    /// fMOD, fCLK/4 in HR mode and fCLK/8 in LP mode, in Hz
+    pub fn modulator_hz(&self, config1: Config1Reg) -> f32 {
+        let divider = if config1.hr() { 4.0 } else { 8.0 };
+        self.frequency_hz() as f32 / divider
+    }
+
+    /// Output data rate selected by `CONFIG1::hr` and `CONFIG1::dr`, in SPS
+    ///
+    /// Returns `None` for the reserved `dr` = `111`.
+    pub fn data_rate_hz(&self, config1: Config1Reg) -> Option<f32> {
+        match config1.dr() {
+            dr @ 0b000..=0b110 => Some(self.modulator_hz(config1) / f32::from(16u16 << dr)),
+            _ => None,
+        }
+    }
+
+    /// Frequency of the internal test signal selected by `CONFIG2::test_freq`, in Hz
+    ///
+    /// Returns `Some(0.0)` for DC and `None` for the unused `10`.
+    pub fn test_signal_hz(&self, config2: Config2Reg) -> Option<f32> {
+        let f_clk = self.frequency_hz() as f32;
+        match config2.test_freq() {
+            0b00 => Some(f_clk / (1u32 << 21) as f32),
+            0b01 => Some(f_clk / (1u32 << 20) as f32),
+            0b11 => Some(0.0),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use core::time::Duration;
+
+use embedded_hal::spi::SpiDevice;
+
+use super::clock::ClockSource;
+use super::registers::access::{ReadFromRegister, WriteToRegister};
+use super::registers::data::Config1Reg;
+use super::registers::CONFIG1;
+use super::{ConfigError, ADS1298};
+
+/// Largest `CONFIG1::dr` in use, `111` is reserved
+const DR_MAX: u8 = 0b110;
+
+/// Relative tolerance when matching a requested data rate
+const DATA_RATE_TOLERANCE: f32 = 1e-3;
+
+/// `CONFIG1::hr`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum PowerMode {
+    /// HR 模式，fMOD = fCLK/4
+    HighResolution,
+    /// LP 模式，fMOD = fCLK/8
+    LowPower,
+}
+
+impl PowerMode {
+    pub(crate) fn hr(self) -> bool {
+        self == PowerMode::HighResolution
+    }
+}
+
+/// Output data rate and the `CONFIG1` bits selecting it
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DataRate {
+    /// In SPS
+    pub hz: f32,
+    pub power_mode: PowerMode,
+    /// `CONFIG1::dr`
+    pub dr: u8,
+}
+
+impl DataRate {
+    /// Time between two samples, for timestamping
+    pub fn sample_period(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / f64::from(self.hz))
+    }
+}
+
+impl<SPI: SpiDevice> ADS1298<SPI> {
+    /// Current output data rate, read from `CONFIG1`
+    pub fn data_rate(&mut self) -> Result<DataRate, ConfigError<SPI::Error>> {
+        self.paused(false, |driver| {
+            let config1: Config1Reg = driver.read(CONFIG1)?;
+            driver.decode_data_rate(config1)
+        })
+    }
+
+    /// Time between two samples at the current output data rate
+    pub fn sample_period(&mut self) -> Result<Duration, ConfigError<SPI::Error>> {
+        Ok(self.data_rate()?.sample_period())
+    }
+
+    /// Set the output data rate, in SPS
+    ///
+    /// The current power mode is kept if it supports `hz`, otherwise the other one is used.
+    /// Conversion and `RDATAC` mode are stopped while writing `CONFIG1` and restored afterwards.
+    pub fn set_data_rate(&mut self, hz: u32) -> Result<DataRate, ConfigError<SPI::Error>> {
+        self.paused(true, |driver| {
+            let config1: Config1Reg = driver.read(CONFIG1)?;
+            let current = config1.hr();
+            let x = config1_for_data_rate(driver.clock, config1, hz, &[current, !current])
+                .ok_or(ConfigError::UnsupportedDataRate(hz))?;
+            driver.write(CONFIG1, x)?;
+            let rate = driver.decode_data_rate(x)?;
+            debug!("Data rate of ADS1298 set to {:?}", rate);
+            Ok(rate)
+        })
+    }
+
+    /// Switch between HR and LP mode, keeping `CONFIG1::dr`
+    ///
+    /// The output data rate of LP mode is half of HR mode.
+    /// Conversion and `RDATAC` mode are stopped while writing `CONFIG1` and restored afterwards.
+    pub fn set_power_mode(
+        &mut self,
+        power_mode: PowerMode,
+    ) -> Result<DataRate, ConfigError<SPI::Error>> {
+        self.paused(true, |driver| {
+            let mut x: Config1Reg = driver.read(CONFIG1)?;
+            x.set_hr(power_mode.hr());
+            driver.write(CONFIG1, x)?;
+            driver.decode_data_rate(x)
+        })
+    }
+
+    fn decode_data_rate(&self, config1: Config1Reg) -> Result<DataRate, ConfigError<SPI::Error>> {
+        let hz = self
+            .clock
+            .data_rate_hz(config1)
+            .ok_or(ConfigError::ReservedDataRate)?;
+        Ok(DataRate {
+            hz,
+            power_mode: if config1.hr() {
+                PowerMode::HighResolution
+            } else {
+                PowerMode::LowPower
+            },
+            dr: config1.dr(),
+        })
+    }
+
+    /// Run `f` with `RDATAC` mode, and conversion if `stop_conversion`, stopped
+    ///
+    /// The stopped modes are restored afterwards, even if `f` fails.
+    pub(crate) fn paused<T>(
+        &mut self,
+        stop_conversion: bool,
+        f: impl FnOnce(&mut Self) -> Result<T, ConfigError<SPI::Error>>,
+    ) -> Result<T, ConfigError<SPI::Error>> {
+        let continuous_read = self.operator.is_continuous_read();
+        let started = stop_conversion && self.operator.is_started();
+        if continuous_read {
+            self.operator.stop_stream()?;
+        }
+        if started {
+            self.operator.stop()?;
+        }
+        let result = f(self);
+        if started {
+            self.operator.start()?;
+        }
+        if continuous_read {
+            self.operator.start_stream()?;
+        }
+        result
+    }
+}
+
+/// `config1` with `hr` and `dr` giving `hz`, trying `CONFIG1::hr` in the order of `modes`
+pub(crate) fn config1_for_data_rate(
+    clock: ClockSource,
+    config1: Config1Reg,
+    hz: u32,
+    modes: &[bool],
+) -> Option<Config1Reg> {
+    modes
+        .iter()
+        .flat_map(|&hr| (0..=DR_MAX).map(move |dr| (hr, dr)))
+        .map(|(hr, dr)| {
+            let mut x = config1;
+            x.set_hr(hr);
+            x.set_dr(dr);
+            x
+        })
+        .find(|x| {
+            clock
+                .data_rate_hz(*x)
+                .is_some_and(|rate| (rate - hz as f32).abs() <= hz as f32 * DATA_RATE_TOLERANCE)
+        })
+}
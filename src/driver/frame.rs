@@ -0,0 +1,207 @@
+use byteorder::{BigEndian, ByteOrder};
+use ux::u24;
+
+use super::registers::{
+    data::{DataStatus, DataStatus1, DataStatus2, DataStatus3},
+    DataRegister,
+};
+
+/// Number of ECG channels of the ADS1298
+pub const CHANNELS: usize = 8;
+
+/// Bytes returned by a single `RDATA` read back: 24-bit status word and 8 × 24-bit channels
+pub const FRAME_SIZE: usize = 27;
+
+/// Largest positive code of a channel, `7FFFFFh`
+pub const CODE_MAX: i32 = 0x7f_ffff;
+
+/// Most negative code of a channel, `800000h`
+pub const CODE_MIN: i32 = -0x80_0000;
+
+/// A decoded conversion result
+///
+/// Channel codes are sign extended from the 24-bit two's complement data, `channels[0]` is CH1.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Frame {
+    pub status: DataStatus,
+    pub channels: [i32; CHANNELS],
+}
+
+impl Frame {
+    /// Decode the bytes returned by `RDATA` / `RDATAC`
+    pub fn from_bytes(raw: &[u8; FRAME_SIZE]) -> Frame {
+        FrameRef::new(raw).to_frame()
+    }
+
+    /// Subtract a per-channel offset, saturating at the 24-bit code range
+    pub fn apply_offsets(&mut self, offsets: &[i32; CHANNELS]) {
+        for (code, offset) in self.channels.iter_mut().zip(offsets) {
+            *code = code.saturating_sub(*offset).clamp(CODE_MIN, CODE_MAX);
+        }
+    }
+
+    /// Convert to the registers layout returned by `StreamReader::read`
+    pub fn to_registers(&self) -> Vec<DataRegister> {
+        let c = self.channels.map(code_to_u24);
+        vec![
+            DataRegister::DATA_STATUS_1(self.status.ds1),
+            DataRegister::DATA_STATUS_2(self.status.ds2),
+            DataRegister::DATA_STATUS_3(self.status.ds3),
+            DataRegister::DATA_CH1(c[0]),
+            DataRegister::DATA_CH2(c[1]),
+            DataRegister::DATA_CH3(c[2]),
+            DataRegister::DATA_CH4(c[3]),
+            DataRegister::DATA_CH5(c[4]),
+            DataRegister::DATA_CH6(c[5]),
+            DataRegister::DATA_CH7(c[6]),
+            DataRegister::DATA_CH8(c[7]),
+        ]
+    }
+}
+
+/// A conversion result decoded lazily from borrowed bytes
+///
+/// Offsets set by `ADS1298::calibrate_offsets` are not applied.
+#[derive(Clone, Copy, Debug)]
+pub struct FrameRef<'a> {
+    raw: &'a [u8; FRAME_SIZE],
+}
+
+impl<'a> FrameRef<'a> {
+    /// Borrow the bytes returned by `RDATA` / `RDATAC`
+    pub fn new(raw: &'a [u8; FRAME_SIZE]) -> Self {
+        FrameRef { raw }
+    }
+
+    pub fn raw(&self) -> &'a [u8; FRAME_SIZE] {
+        self.raw
+    }
+
+    pub fn status(&self) -> DataStatus {
+        DataStatus {
+            ds1: DataStatus1(self.raw[0]),
+            ds2: DataStatus2(self.raw[1]),
+            ds3: DataStatus3(self.raw[2]),
+        }
+    }
+
+    /// Code of the channel at `index`, `0` is CH1
+    ///
+    /// Panics if `index` >= `CHANNELS`.
+    pub fn channel(&self, index: usize) -> i32 {
+        assert!(index < CHANNELS, "channel index out of range: {index}");
+        let start = 3 + 3 * index;
+        code_from_u24(u24::new(BigEndian::read_u24(&self.raw[start..start + 3])))
+    }
+
+    /// Codes of CH1 ~ CH8
+    pub fn channels(&self) -> impl Iterator<Item = i32> + 'a {
+        let frame = *self;
+        (0..CHANNELS).map(move |i| frame.channel(i))
+    }
+
+    pub fn to_frame(&self) -> Frame {
+        let mut channels = [0i32; CHANNELS];
+        for (code, x) in channels.iter_mut().zip(self.channels()) {
+            *code = x;
+        }
+        Frame {
+            status: self.status(),
+            channels,
+        }
+    }
+}
+
+/// A fixed ring of frame buffers, handed one after another to reads or DMA transfers
+///
+/// Once full, each new frame overwrites the oldest one.
+pub struct FrameBuffers<const N: usize> {
+    buffers: [[u8; FRAME_SIZE]; N],
+    /// Index of the buffer filled next
+    next: usize,
+    /// Number of filled buffers, up to `N`
+    filled: usize,
+}
+
+impl<const N: usize> FrameBuffers<N> {
+    pub const fn new() -> Self {
+        assert!(N > 0, "FrameBuffers needs at least one buffer");
+        FrameBuffers {
+            buffers: [[0; FRAME_SIZE]; N],
+            next: 0,
+            filled: 0,
+        }
+    }
+
+    /// Buffer to fill next, e.g. the target of a DMA transfer
+    ///
+    /// It holds the oldest frame when the ring is full, call `commit` once it is filled.
+    pub fn next_buffer(&mut self) -> &mut [u8; FRAME_SIZE] {
+        &mut self.buffers[self.next]
+    }
+
+    /// Mark the buffer returned by `next_buffer` as filled
+    pub fn commit(&mut self) -> FrameRef<'_> {
+        let index = self.next;
+        self.next = (self.next + 1) % N;
+        self.filled = (self.filled + 1).min(N);
+        FrameRef::new(&self.buffers[index])
+    }
+
+    /// Fill the next buffer with `read` and commit it if `read` succeeds
+    pub fn fill<E>(
+        &mut self,
+        read: impl FnOnce(&mut [u8; FRAME_SIZE]) -> Result<(), E>,
+    ) -> Result<FrameRef<'_>, E> {
+        read(self.next_buffer())?;
+        Ok(self.commit())
+    }
+
+    /// The frame committed last
+    pub fn latest(&self) -> Option<FrameRef<'_>> {
+        (self.filled > 0).then(|| FrameRef::new(&self.buffers[(self.next + N - 1) % N]))
+    }
+
+    /// Filled frames, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = FrameRef<'_>> {
+        let oldest = (self.next + N - self.filled) % N;
+        (0..self.filled).map(move |i| FrameRef::new(&self.buffers[(oldest + i) % N]))
+    }
+
+    pub fn len(&self) -> usize {
+        self.filled
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filled == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.next = 0;
+        self.filled = 0;
+    }
+}
+
+impl<const N: usize> Default for FrameBuffers<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sign extend a 24-bit two's complement channel code
+pub fn code_from_u24(data: u24) -> i32 {
+    (u32::from(data) << 8) as i32 >> 8
+}
+
+/// Encode a channel code as 24-bit two's complement, saturating at the code range
+pub fn code_to_u24(code: i32) -> u24 {
+    u24::new(code.clamp(CODE_MIN, CODE_MAX) as u32 & 0xff_ffff)
+}
+
+/// Input referred voltage of a channel code
+///
+/// 1 LSB = VREF / (2^23 − 1) / gain
+pub fn code_to_volts(code: i32, vref: f32, gain: u8) -> f32 {
+    code as f32 * vref / CODE_MAX as f32 / f32::from(gain)
+}
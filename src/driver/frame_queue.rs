@@ -0,0 +1,236 @@
+//! Single-producer single-consumer frame queue for interrupt-driven acquisition
+//!
+//! The producer half reads frames in the `DRDY` interrupt, the consumer half decodes them in a
+//! lower-priority task. Only atomic loads and stores are used, so it also works on cores without
+//! compare-and-swap, such as Cortex-M0.
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use embedded_hal::spi::SpiDevice;
+
+use super::frame::{Frame, FrameRef, FRAME_SIZE};
+use super::operator::Operator;
+use super::registers::access::ReadError;
+
+/// A queue of `N` raw frames, `N` is a power of two
+///
+/// `head` and `tail` wrap around `usize::MAX`, which keeps `% N` continuous only for powers of
+/// two.
+///
+/// ```ignore
+/// static mut QUEUE: FrameQueue<64> = FrameQueue::new();
+/// let (producer, consumer) = unsafe { (*core::ptr::addr_of_mut!(QUEUE)).split() };
+/// ```
+pub struct FrameQueue<const N: usize> {
+    buffers: [UnsafeCell<[u8; FRAME_SIZE]>; N],
+    /// Frames dequeued, only written by the consumer
+    head: AtomicUsize,
+    /// Frames enqueued, only written by the producer
+    tail: AtomicUsize,
+    /// Frames dropped because the queue was full, only written by the producer
+    overflows: AtomicU32,
+}
+
+// The producer only writes the slot at `tail` before publishing it, the consumer only reads
+// the slot at `head` before releasing it.
+unsafe impl<const N: usize> Sync for FrameQueue<N> {}
+
+impl<const N: usize> FrameQueue<N> {
+    pub const fn new() -> Self {
+        const {
+            assert!(
+                N.is_power_of_two(),
+                "FrameQueue needs a power of two buffers"
+            )
+        };
+        FrameQueue {
+            buffers: [const { UnsafeCell::new([0; FRAME_SIZE]) }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            overflows: AtomicU32::new(0),
+        }
+    }
+
+    /// Split into the producer and the consumer halves
+    pub fn split(&mut self) -> (Producer<'_, N>, Consumer<'_, N>) {
+        let queue = &*self;
+        (
+            Producer { queue },
+            Consumer {
+                queue,
+                overflows_seen: queue.overflows.load(Ordering::Relaxed),
+            },
+        )
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        let head = self.head.load(Ordering::Acquire);
+        tail.wrapping_sub(head)
+    }
+}
+
+impl<const N: usize> Default for FrameQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Producer half, to be owned by the `DRDY` interrupt handler
+pub struct Producer<'a, const N: usize> {
+    queue: &'a FrameQueue<N>,
+}
+
+// The producer half only touches the queue through the protocol above.
+unsafe impl<const N: usize> Send for Producer<'_, N> {}
+
+impl<const N: usize> Producer<'_, N> {
+    /// Fill the next free buffer with `read` and publish it if `read` succeeds
+    ///
+    /// Returns `Ok(false)` without calling `read` when the queue is full, the frame is counted
+    /// as an overflow.
+    pub fn enqueue_with<E>(
+        &mut self,
+        read: impl FnOnce(&mut [u8; FRAME_SIZE]) -> Result<(), E>,
+    ) -> Result<bool, E> {
+        let queue = self.queue;
+        let tail = queue.tail.load(Ordering::Relaxed);
+        let head = queue.head.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) >= N {
+            let overflows = queue.overflows.load(Ordering::Relaxed);
+            queue
+                .overflows
+                .store(overflows.wrapping_add(1), Ordering::Relaxed);
+            return Ok(false);
+        }
+        // The consumer does not read this slot until `tail` is published
+        let buffer = unsafe { &mut *queue.buffers[tail % N].get() };
+        read(buffer)?;
+        queue.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Ok(true)
+    }
+
+    /// Read a frame with `RDATA` into the queue
+    ///
+    /// Returns `Ok(false)` when the queue is full, the frame is left unread.
+    pub fn read_from<SPI: SpiDevice>(
+        &mut self,
+        operator: &mut Operator<SPI>,
+    ) -> Result<bool, ReadError<SPI::Error>> {
+        self.enqueue_with(|buffer| operator.read_data_into(buffer))
+    }
+
+    /// Read a frame in `RDATAC` mode into the queue
+    ///
+    /// Returns `Ok(false)` when the queue is full, the frame is left unread.
+    pub fn read_continuous_from<SPI: SpiDevice>(
+        &mut self,
+        operator: &mut Operator<SPI>,
+    ) -> Result<bool, ReadError<SPI::Error>> {
+        self.enqueue_with(|buffer| operator.read_continuous_data_into(buffer))
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.queue.len() >= N
+    }
+}
+
+/// Consumer half, to be owned by the processing task
+pub struct Consumer<'a, const N: usize> {
+    queue: &'a FrameQueue<N>,
+    /// Value of `FrameQueue::overflows` at the last `take_overflows`
+    overflows_seen: u32,
+}
+
+// The consumer half only touches the queue through the protocol above.
+unsafe impl<const N: usize> Send for Consumer<'_, N> {}
+
+impl<const N: usize> Consumer<'_, N> {
+    /// Decode the oldest frame in place with `f` and release its buffer
+    pub fn dequeue_with<R>(&mut self, f: impl FnOnce(FrameRef<'_>) -> R) -> Option<R> {
+        let queue = self.queue;
+        let head = queue.head.load(Ordering::Relaxed);
+        let tail = queue.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        // The producer does not write this slot until `head` is released
+        let buffer = unsafe { &*queue.buffers[head % N].get() };
+        let r = f(FrameRef::new(buffer));
+        queue.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(r)
+    }
+
+    /// Decode and release the oldest frame
+    ///
+    /// Offsets set by `ADS1298::calibrate_offsets` are not applied.
+    pub fn dequeue(&mut self) -> Option<Frame> {
+        self.dequeue_with(|frame| frame.to_frame())
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Frames dropped since the queue was created
+    pub fn overflows(&self) -> u32 {
+        self.queue.overflows.load(Ordering::Relaxed)
+    }
+
+    /// Frames dropped since the last call
+    pub fn take_overflows(&mut self) -> u32 {
+        let overflows = self.overflows();
+        let new = overflows.wrapping_sub(self.overflows_seen);
+        self.overflows_seen = overflows;
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indices_wrap_around_usize_max() {
+        let start = usize::MAX - 5;
+        let mut queue = FrameQueue::<4> {
+            head: AtomicUsize::new(start),
+            tail: AtomicUsize::new(start),
+            ..FrameQueue::new()
+        };
+        let (mut producer, mut consumer) = queue.split();
+        let mut next = 0u8;
+        let mut expected = 0u8;
+        for _ in 0..5 {
+            while !producer.is_full() {
+                let pushed = producer.enqueue_with(|buffer| {
+                    buffer[0] = next;
+                    next += 1;
+                    Ok::<_, ()>(())
+                });
+                assert_eq!(pushed, Ok(true));
+            }
+            assert_eq!(consumer.len(), 4);
+            for _ in 0..3 {
+                assert_eq!(
+                    consumer.dequeue_with(|frame| frame.raw()[0]),
+                    Some(expected)
+                );
+                expected += 1;
+            }
+        }
+        assert_eq!(
+            queue.tail.load(Ordering::Relaxed),
+            start.wrapping_add(4 + 4 * 3)
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use core::fmt;
+
+use embedded_hal::digital::{self, ErrorType, InputPin, OutputPin};
+use embedded_hal::spi::SpiDevice;
+
+use super::registers::access::{ReadError, ReadFromRegister, WriteError, WriteToRegister};
+use super::registers::data::GpioReg;
+use super::registers::{CONFIG4, GPIO};
+use super::ADS1298;
+
+/// Number of GPIO pins of the ADS1298
+pub const GPIO_PINS: u8 = 4;
+
+/// Lowest `CONFIG4::resp_freq` which outputs square waves on GPIO3 and GPIO4
+const RESP_FREQ_SQUARE_WAVE: u8 = 0b010;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GpioError<SpiError> {
+    ReadError(ReadError<SpiError>),
+    WriteError(WriteError<SpiError>),
+    /// Pin number is not in `1..=4`
+    InvalidPin(u8),
+    /// GPIO3 or GPIO4 outputs the respiration square wave, see `Config4Reg::resp_freq`
+    ClaimedByRespiration(u8),
+}
+
+impl<SpiError> From<ReadError<SpiError>> for GpioError<SpiError> {
+    fn from(e: ReadError<SpiError>) -> Self {
+        GpioError::ReadError(e)
+    }
+}
+
+impl<SpiError> From<WriteError<SpiError>> for GpioError<SpiError> {
+    fn from(e: WriteError<SpiError>) -> Self {
+        GpioError::WriteError(e)
+    }
+}
+
+impl<SpiError: fmt::Debug> fmt::Display for GpioError<SpiError> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GpioError::ReadError(e) => e.fmt(f),
+            GpioError::WriteError(e) => e.fmt(f),
+            GpioError::InvalidPin(pin) => write!(f, "invalid GPIO pin {pin}"),
+            GpioError::ClaimedByRespiration(pin) => {
+                write!(f, "GPIO{pin} outputs the respiration square wave")
+            }
+        }
+    }
+}
+
+impl<SpiError: fmt::Debug + 'static> core::error::Error for GpioError<SpiError> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            GpioError::ReadError(e) => Some(e),
+            GpioError::WriteError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<SpiError: fmt::Debug> digital::Error for GpioError<SpiError> {
+    fn kind(&self) -> digital::ErrorKind {
+        digital::ErrorKind::Other
+    }
+}
+
+/// One of GPIO1 ~ GPIO4, backed by the `GPIO` register
+///
+/// Every access is a read-modify-write of the `GPIO` register, so the device must be in
+/// `SDATAC` mode. Writes to GPIO3 and GPIO4 fail once `CONFIG4::resp_freq` claims them.
+pub struct Gpio<'a, SPI: SpiDevice> {
+    driver: &'a mut ADS1298<SPI>,
+    pin: u8,
+}
+
+impl<SPI: SpiDevice> ADS1298<SPI> {
+    /// Borrow GPIO `pin` in `1..=4`
+    ///
+    /// GPIO3 and GPIO4 are refused while `CONFIG4::resp_freq` outputs square waves on them.
+    pub fn gpio(&mut self, pin: u8) -> Result<Gpio<'_, SPI>, GpioError<SPI::Error>> {
+        if pin == 0 || pin > GPIO_PINS {
+            return Err(GpioError::InvalidPin(pin));
+        }
+        check_respiration(self, pin)?;
+        Ok(Gpio { driver: self, pin })
+    }
+}
+
+/// Refuse GPIO3 and GPIO4 while `CONFIG4::resp_freq` outputs square waves on them
+fn check_respiration<SPI: SpiDevice>(
+    driver: &mut ADS1298<SPI>,
+    pin: u8,
+) -> Result<(), GpioError<SPI::Error>> {
+    if pin >= 3 && driver.read(CONFIG4)?.resp_freq() >= RESP_FREQ_SQUARE_WAVE {
+        return Err(GpioError::ClaimedByRespiration(pin));
+    }
+    Ok(())
+}
+
+impl<SPI: SpiDevice> Gpio<'_, SPI> {
+    pub fn pin(&self) -> u8 {
+        self.pin
+    }
+
+    fn mask(&self) -> u8 {
+        1 << (self.pin - 1)
+    }
+
+    /// `CONFIG4` is checked again, it may have been changed since the pin was borrowed
+    fn modify(&mut self, f: impl FnOnce(&mut GpioReg, u8)) -> Result<(), GpioError<SPI::Error>> {
+        check_respiration(self.driver, self.pin)?;
+        let mut x: GpioReg = self.driver.read(GPIO)?;
+        f(&mut x, self.mask());
+        self.driver.write(GPIO, x)?;
+        Ok(())
+    }
+
+    /// Configure the pin as output, `GPIOC` = `0`
+    pub fn set_as_output(&mut self) -> Result<(), GpioError<SPI::Error>> {
+        self.modify(|x, mask| x.set_gpio_c(x.gpio_c() & !mask))
+    }
+
+    /// Configure the pin as input, `GPIOC` = `1`
+    pub fn set_as_input(&mut self) -> Result<(), GpioError<SPI::Error>> {
+        self.modify(|x, mask| x.set_gpio_c(x.gpio_c() | mask))
+    }
+
+    pub fn is_output(&mut self) -> Result<bool, GpioError<SPI::Error>> {
+        let x: GpioReg = self.driver.read(GPIO)?;
+        Ok(x.gpio_c() & self.mask() == 0)
+    }
+}
+
+impl<SPI: SpiDevice> ErrorType for Gpio<'_, SPI> {
+    type Error = GpioError<SPI::Error>;
+}
+
+impl<SPI: SpiDevice> InputPin for Gpio<'_, SPI> {
+    /// Returns the state of the external pin, whether it is an input or an output
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        let x: GpioReg = self.driver.read(GPIO)?;
+        Ok(x.gpio_d() & self.mask() != 0)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.is_high()?)
+    }
+}
+
+impl<SPI: SpiDevice> OutputPin for Gpio<'_, SPI> {
+    /// Has no effect until the pin is configured as output
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.modify(|x, mask| x.set_gpio_d(x.gpio_d() & !mask))
+    }
+
+    /// Has no effect until the pin is configured as output
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.modify(|x, mask| x.set_gpio_d(x.gpio_d() | mask))
+    }
+}
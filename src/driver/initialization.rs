@@ -1,4 +1,8 @@
+use core::fmt;
+
 use crate::driver::registers::access::{ReadError, WriteError};
+use crate::driver::registers::addressable::Address;
+use crate::driver::registers::map::REGISTER_NAMES;
 
 pub trait Initializer<Application> {
     type SpiError;
@@ -7,14 +11,116 @@ pub trait Initializer<Application> {
 
 pub struct Default8Lead1x500;
 
+/// Opcode step of `Initializer::init`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InitStep {
+    /// `RESET`
+    Reset,
+    /// `SDATAC`
+    StopContinuousRead,
+    /// `START`
+    StartConversion,
+}
+
+impl InitStep {
+    fn opcode(self) -> &'static str {
+        match self {
+            InitStep::Reset => "RESET",
+            InitStep::StopContinuousRead => "SDATAC",
+            InitStep::StartConversion => "START",
+        }
+    }
+}
+
+impl fmt::Display for InitStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.opcode())
+    }
+}
+
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum InitializeError<SpiError> {
+    /// Sending an opcode failed
+    CommandError {
+        step: InitStep,
+        source: WriteError<SpiError>,
+    },
+    /// Reading the register at `address` failed
+    ReadError {
+        source: ReadError<SpiError>,
+        address: Address,
+    },
+    /// Writing `data` to the register at `address` failed
     WriteError {
         source: WriteError<SpiError>,
-        address: u8,
+        address: Address,
         data: u8,
     },
-    ResetError(WriteError<SpiError>, Option<String>),
-    ReadError(ReadError<SpiError>, Option<String>),
-    InitError(Option<String>),
+    /// `ID` read back after all retries, check the SPI configuration and connection
+    InvalidId(u8),
+    #[cfg(feature = "serde")]
+    ProfileError(super::profile::ProfileError),
+}
+
+impl<SpiError> InitializeError<SpiError> {
+    /// Map the failure of the opcode of `step`
+    pub(crate) fn command(step: InitStep) -> impl FnOnce(WriteError<SpiError>) -> Self {
+        move |source| {
+            error!("Failed to send {} to ADS1298", step.opcode());
+            InitializeError::CommandError { step, source }
+        }
+    }
+}
+
+impl<SpiError: fmt::Debug> fmt::Display for InitializeError<SpiError> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InitializeError::CommandError { step, source } => {
+                write!(f, "failed to send {step}: {source}")
+            }
+            InitializeError::ReadError { source, address } => {
+                let name = register_name(*address);
+                write!(f, "failed to read {name} ({address:02X}h): {source}")
+            }
+            InitializeError::WriteError {
+                source,
+                address,
+                data,
+            } => {
+                let name = register_name(*address);
+                write!(
+                    f,
+                    "failed to write {data:02X}h to {name} ({address:02X}h): {source}"
+                )
+            }
+            InitializeError::InvalidId(id) => write!(
+                f,
+                "incorrect ID register {id:02X}h, check the SPI configuration and connection"
+            ),
+            #[cfg(feature = "serde")]
+            InitializeError::ProfileError(e) => write!(f, "invalid profile: {e}"),
+        }
+    }
+}
+
+impl<SpiError: fmt::Debug + 'static> core::error::Error for InitializeError<SpiError> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            InitializeError::CommandError { source, .. } => Some(source),
+            InitializeError::ReadError { source, .. } => Some(source),
+            InitializeError::WriteError { source, .. } => Some(source),
+            InitializeError::InvalidId(_) => None,
+            #[cfg(feature = "serde")]
+            InitializeError::ProfileError(e) => Some(e),
+        }
+    }
+}
+
+fn register_name(address: Address) -> &'static str {
+    REGISTER_NAMES
+        .get(usize::from(address))
+        .copied()
+        .unwrap_or("unknown register")
 }
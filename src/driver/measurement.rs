@@ -0,0 +1,159 @@
+use core::fmt;
+
+use embedded_hal::digital::{self, Error as _, InputPin};
+use embedded_hal::spi::SpiDevice;
+
+use super::frame::{Frame, CHANNELS};
+use super::registers::access::{ReadError, ReadFromRegister, WriteError, WriteToRegister};
+use super::registers::addressable::{Address, Addressable};
+use super::registers::data::ChSetReg;
+use super::registers::CH1SET;
+use super::ADS1298;
+
+/// Errors of the routines which reconfigure channels and capture samples
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MeasureError<SpiError> {
+    ReadError(ReadError<SpiError>),
+    WriteError(WriteError<SpiError>),
+    /// Failed to sample the `DRDY` pin
+    DataReadyError(digital::ErrorKind),
+    /// Channel number is not in `1..=8`
+    InvalidChannel(u8),
+    /// `CHnSET` of the channel holds the reserved gain `111`
+    InvalidGain { channel: u8 },
+    /// Requested zero samples
+    NoSamples,
+    /// `CONFIG1::dr` holds the reserved `111`
+    ReservedDataRate,
+}
+
+impl<SpiError> From<ReadError<SpiError>> for MeasureError<SpiError> {
+    fn from(e: ReadError<SpiError>) -> Self {
+        MeasureError::ReadError(e)
+    }
+}
+
+impl<SpiError> From<WriteError<SpiError>> for MeasureError<SpiError> {
+    fn from(e: WriteError<SpiError>) -> Self {
+        MeasureError::WriteError(e)
+    }
+}
+
+impl<SpiError: fmt::Debug> fmt::Display for MeasureError<SpiError> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeasureError::ReadError(e) => e.fmt(f),
+            MeasureError::WriteError(e) => e.fmt(f),
+            MeasureError::DataReadyError(kind) => write!(f, "failed to sample DRDY: {kind}"),
+            MeasureError::InvalidChannel(channel) => write!(f, "invalid channel {channel}"),
+            MeasureError::InvalidGain { channel } => {
+                write!(f, "reserved PGA gain on channel {channel}")
+            }
+            MeasureError::NoSamples => f.write_str("zero samples requested"),
+            MeasureError::ReservedDataRate => f.write_str("CONFIG1 holds the reserved data rate"),
+        }
+    }
+}
+
+impl<SpiError: fmt::Debug + 'static> core::error::Error for MeasureError<SpiError> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            MeasureError::ReadError(e) => Some(e),
+            MeasureError::WriteError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Address of `CHnSET`, `channel` is in `1..=8`
+pub(crate) fn channel_setting_address<SpiError>(
+    channel: u8,
+) -> Result<Address, MeasureError<SpiError>> {
+    if channel == 0 || channel as usize > CHANNELS {
+        return Err(MeasureError::InvalidChannel(channel));
+    }
+    Ok(channel_address(usize::from(channel - 1)))
+}
+
+/// Address of `CHnSET` of channel `i + 1`, `i` is in `0..8`
+pub(crate) fn channel_address(i: usize) -> Address {
+    CH1SET.get_address() + i as Address
+}
+
+/// Busy wait until `DRDY` becomes `low`
+pub(crate) fn wait_data_ready<DRDY: InputPin>(drdy: &mut DRDY) -> Result<(), digital::ErrorKind> {
+    while drdy.is_high().map_err(|e| e.kind())? {}
+    Ok(())
+}
+
+impl<SPI: SpiDevice> ADS1298<SPI> {
+    /// Read `CHnSET` of channel `1..=8`
+    pub fn read_channel_setting(
+        &mut self,
+        channel: u8,
+    ) -> Result<ChSetReg, MeasureError<SPI::Error>> {
+        let address = channel_setting_address(channel)?;
+        Ok(ChSetReg(self.operator.read(address)?))
+    }
+
+    /// Write `CHnSET` of channel `1..=8`
+    pub fn write_channel_setting(
+        &mut self,
+        channel: u8,
+        data: ChSetReg,
+    ) -> Result<(), MeasureError<SPI::Error>> {
+        let address = channel_setting_address(channel)?;
+        self.operator.write(address, data.0)?;
+        Ok(())
+    }
+
+    /// Read all `CHnSET`, `[0]` is CH1
+    pub(crate) fn read_channel_settings(
+        &mut self,
+    ) -> Result<[ChSetReg; CHANNELS], MeasureError<SPI::Error>> {
+        let mut settings = [ChSetReg(0); CHANNELS];
+        for (channel, setting) in (1..).zip(settings.iter_mut()) {
+            *setting = self.read_channel_setting(channel)?;
+        }
+        Ok(settings)
+    }
+
+    /// Restart conversion, then read `samples` frames, each one after `DRDY` becomes `low`
+    ///
+    /// Frames are passed to `f` without offset correction. Conversion keeps running afterwards,
+    /// see `restore_conversion`.
+    pub(crate) fn capture<DRDY: InputPin>(
+        &mut self,
+        drdy: &mut DRDY,
+        samples: usize,
+        mut f: impl FnMut(&Frame),
+    ) -> Result<(), MeasureError<SPI::Error>> {
+        if samples == 0 {
+            return Err(MeasureError::NoSamples);
+        }
+        self.operator.start()?;
+        for _ in 0..samples {
+            wait_data_ready(drdy).map_err(MeasureError::DataReadyError)?;
+            let raw = self.operator.read_single_data()?;
+            f(&Frame::from_bytes(&raw));
+        }
+        Ok(())
+    }
+
+    /// Restart conversion if it was `started` before `capture`, or stop it otherwise
+    ///
+    /// Restarting lets the digital filter settle with the restored channel settings.
+    pub(crate) fn restore_conversion(
+        &mut self,
+        started: bool,
+    ) -> Result<(), WriteError<SPI::Error>> {
+        if started {
+            self.operator.start()
+        } else if self.operator.is_started() {
+            self.operator.stop()
+        } else {
+            Ok(())
+        }
+    }
+}
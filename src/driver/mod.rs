@@ -1,3 +1,6 @@
+use core::fmt;
+
+use embedded_hal::digital::{self, InputPin};
 use embedded_hal::spi::SpiDevice;
 use registers::access::WriteError;
 use registers::data::{
@@ -11,39 +14,220 @@ use registers::{
     WCT1, WCT2,
 };
 
-use crate::driver::initialization::{Default8Lead1x500, InitializeError, Initializer};
+use crate::driver::initialization::{Default8Lead1x500, InitStep, InitializeError, Initializer};
 use crate::driver::registers::access::{ReadError, ReadFromRegister, WriteToRegister};
-use crate::driver::registers::addressable::Addressable;
+use crate::driver::registers::addressable::{Address, Addressable};
 
+use self::clock::ClockSource;
+use self::frame::CHANNELS;
 use self::operator::Operator;
 
 use self::stream_reader::StreamReader;
 
+#[cfg(feature = "async")]
+pub mod async_stream_reader;
+pub mod calibration;
+pub mod clock;
+pub mod data_rate;
+pub mod frame;
+pub mod frame_queue;
+pub mod gpio;
 pub mod initialization;
+pub mod measurement;
 pub mod operator;
+pub mod power;
+#[cfg(feature = "serde")]
+pub mod profile;
 pub mod registers;
+pub mod self_test;
+pub mod single_shot;
 pub mod stream_reader;
+pub mod supply;
+pub mod temperature;
+pub mod timestamp;
 
 pub struct ADS1298<SPI: SpiDevice> {
     pub operator: Operator<SPI>,
+    clock: ClockSource,
+    offsets: [i32; CHANNELS],
+    /// `CHnSET` of channels before `power_down_channels`
+    powered_down: [Option<ChSetReg>; CHANNELS],
 }
 
 impl<SPI: SpiDevice> ADS1298<SPI> {
+    /// Use the internal 2.048 MHz oscillator
     pub fn new(spi: SPI) -> ADS1298<SPI> {
+        Self::with_clock(spi, ClockSource::Internal)
+    }
+
+    /// `clock` must match the `CLKSEL` pin and the clock on `CLK`
+    pub fn with_clock(spi: SPI, clock: ClockSource) -> ADS1298<SPI> {
         ADS1298 {
-            operator: Operator::new(spi),
+            operator: Operator::with_clock(spi, clock),
+            clock,
+            offsets: [0; CHANNELS],
+            powered_down: [None; CHANNELS],
         }
     }
 
-    pub fn stream_reader(&mut self) -> Result<StreamReader<SPI>, StreamError<SPI::Error>> {
-        StreamReader::new(self)
+    pub fn clock(&self) -> ClockSource {
+        self.clock
+    }
+
+    /// Output data rate of the current `CONFIG1`, in SPS
+    ///
+    /// Returns `None` for the reserved `dr` = `111`.
+    pub fn data_rate_hz(&mut self) -> Result<Option<f32>, ReadError<SPI::Error>> {
+        let config1 = self.read(CONFIG1)?;
+        Ok(self.clock.data_rate_hz(config1))
+    }
+
+    /// Frequency of the internal test signal of the current `CONFIG2`, in Hz
+    ///
+    /// Returns `Some(0.0)` for DC and `None` for the unused `10`.
+    pub fn test_signal_hz(&mut self) -> Result<Option<f32>, ReadError<SPI::Error>> {
+        let config2 = self.read(CONFIG2)?;
+        Ok(self.clock.test_signal_hz(config2))
+    }
+
+    /// `drdy` is the `DRDY` pin, waited on before every frame
+    pub fn stream_reader<DRDY: InputPin>(
+        &mut self,
+        drdy: DRDY,
+    ) -> Result<StreamReader<'_, SPI, DRDY>, StreamError<SPI::Error>> {
+        StreamReader::new(self, drdy)
+    }
+
+    /// `RESET`, `SDATAC`, and check the `ID` register, retrying up to 10 times
+    fn reset_and_check_id(&mut self) -> Result<(), InitializeError<SPI::Error>> {
+        let mut id = 0;
+        for _ in 0..10 {
+            // 重置芯片
+            self.operator
+                .reset()
+                .map_err(InitializeError::command(InitStep::Reset))?;
+            // 停止数据连续发送
+            self.operator
+                .stop_stream()
+                .map_err(InitializeError::command(InitStep::StopContinuousRead))?;
+            // 测试读取 ID 寄存器
+            let id_reg = self.read(ID).map_err(|source| InitializeError::ReadError {
+                source,
+                address: ID.get_address(),
+            })?;
+            if id_reg.rev_4() {
+                debug!("ID register of ADS1298: {:#x}", id_reg.0);
+                return Ok(());
+            }
+            id = id_reg.0;
+            warn!("Incorrect ID register {:#x} of ADS1298, retrying", id);
+        }
+        error!(
+            "Incorrect ID register {:#x} of ADS1298 after all retries",
+            id
+        );
+        Err(InitializeError::InvalidId(id))
+    }
+
+    /// Write a register during init, failures report the register and the data
+    fn init_write(
+        &mut self,
+        register: impl Addressable,
+        data: u8,
+    ) -> Result<(), InitializeError<SPI::Error>> {
+        let address = register.get_address();
+        self.operator.write(address, data).map_err(|source| {
+            error!(
+                "Failed to write {:#x} to the address {:#x} of ADS1298",
+                data, address
+            );
+            InitializeError::WriteError {
+                source,
+                address,
+                data,
+            }
+        })
     }
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum StreamError<SpiError> {
     ReadConfigError(ReadError<SpiError>),
     StreamingAbort(ReadError<SpiError>),
+    /// Failed to wait for the `DRDY` pin
+    DataReadyError(digital::ErrorKind),
+    /// `CONFIG1::dr` holds the reserved `111`
+    ReservedDataRate,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError<SpiError> {
+    ReadError(ReadError<SpiError>),
+    WriteError(WriteError<SpiError>),
+    /// No combination of `CONFIG1::hr` and `CONFIG1::dr` gives the requested data rate, in SPS
+    UnsupportedDataRate(u32),
+    /// `CONFIG1::dr` holds the reserved `111`
+    ReservedDataRate,
+    /// No writable register at the address, see `write_register`
+    NotWritable(Address),
+}
+
+impl<SpiError> From<ReadError<SpiError>> for ConfigError<SpiError> {
+    fn from(e: ReadError<SpiError>) -> Self {
+        ConfigError::ReadError(e)
+    }
+}
+
+impl<SpiError> From<WriteError<SpiError>> for ConfigError<SpiError> {
+    fn from(e: WriteError<SpiError>) -> Self {
+        ConfigError::WriteError(e)
+    }
+}
+
+impl<SpiError: fmt::Debug> fmt::Display for StreamError<SpiError> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamError::ReadConfigError(e) => write!(f, "failed to read configuration: {e}"),
+            StreamError::StreamingAbort(e) => write!(f, "streaming aborted: {e}"),
+            StreamError::DataReadyError(kind) => write!(f, "failed to wait for DRDY: {kind}"),
+            StreamError::ReservedDataRate => f.write_str("CONFIG1 holds the reserved data rate"),
+        }
+    }
+}
+
+impl<SpiError: fmt::Debug + 'static> core::error::Error for StreamError<SpiError> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            StreamError::ReadConfigError(e) | StreamError::StreamingAbort(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<SpiError: fmt::Debug> fmt::Display for ConfigError<SpiError> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::ReadError(e) => e.fmt(f),
+            ConfigError::WriteError(e) => e.fmt(f),
+            ConfigError::UnsupportedDataRate(hz) => write!(f, "unsupported data rate {hz} SPS"),
+            ConfigError::ReservedDataRate => f.write_str("CONFIG1 holds the reserved data rate"),
+            ConfigError::NotWritable(address) => {
+                write!(f, "no writable register at {address:02X}h")
+            }
+        }
+    }
+}
+
+impl<SpiError: fmt::Debug + 'static> core::error::Error for ConfigError<SpiError> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            ConfigError::ReadError(e) => Some(e),
+            ConfigError::WriteError(e) => Some(e),
+            _ => None,
+        }
+    }
 }
 
 impl<SPI: SpiDevice> Initializer<Default8Lead1x500> for ADS1298<SPI> {
@@ -61,68 +245,33 @@ impl<SPI: SpiDevice> Initializer<Default8Lead1x500> for ADS1298<SPI> {
         &mut self,
         _application: Default8Lead1x500,
     ) -> Result<(), InitializeError<Self::SpiError>> {
-        let mut retries = 10;
-        let mut inited = false;
-
-        while !inited {
-            if retries <= 0 {
-                return Err(InitializeError::InitError(Some(format!(
-                    "Incorrect ID register, check your SPI config and connection"
-                ))));
-            }
-            // 重置芯片
-            self.operator.reset().map_err(|e| {
-                InitializeError::ResetError(e, Some(format!("Failed to reset chip")))
-            })?;
-            // 停止数据连续发送
-            self.operator.stop_stream().map_err(|e| {
-                InitializeError::ResetError(e, Some(format!("Failed to disable converting mode")))
-            })?;
-            // 测试读取 ID 寄存器
-            let id_reg = self.read(ID).map_err(|e| {
-                InitializeError::ReadError(e, Some(format!("Failed to read ID register")))
-            })?;
-            if id_reg.rev_4() == true {
-                inited = true;
-            } else {
-                retries -= 1;
-            }
-        }
+        self.reset_and_check_id()?;
         // 高分辨率模式, 输出数据速率 500SPS
-        self.write(CONFIG1, {
+        self.init_write(CONFIG1, {
             let mut x = Config1Reg(0);
             x.set_hr(true);
             x.set_dr(0b110);
-            x
-        })
-        .map_err(|e| InitializeError::ResetError(e, Some(format!("Failed to set sample rate"))))?;
+            x.0
+        })?;
         // 不更改配置寄存器2
-        self.write(CONFIG2, {
-            let x = Config2Reg(0);
-            x
-        })
-        .map_err(|e| InitializeError::ResetError(e, Some(format!("Failed to set CONFIG2"))))?;
+        self.init_write(CONFIG2, 0)?;
         // 使用内部基准
-        self.write(CONFIG3, {
+        self.init_write(CONFIG3, {
             let mut x = Config3Reg(0);
             x.set_rev_6(true);
             x.set_pd_refbuf(true);
             x.set_pd_rld(true);
             x.set_rldref_int(true);
             x.set_rld_meas(true);
-            x
-        })
-        .map_err(|e| {
-            InitializeError::ResetError(e, Some(format!("Failed to switch to internal reference")))
+            x.0
         })?;
         // WCT 连接到 RLD
-        self.write(CONFIG4, {
+        self.init_write(CONFIG4, {
             let mut x = Config4Reg(0);
             x.set_wct_to_rld(true);
             x.set_pd_loff_comp(true);
-            x
-        })
-        .map_err(|e| InitializeError::ResetError(e, Some(format!("Failed to set CONFIG4"))))?;
+            x.0
+        })?;
         // 调节 1,4,5,6,7,8 通道增益为 2
         let data = {
             let mut x = ChSetReg(0);
@@ -130,24 +279,12 @@ impl<SPI: SpiDevice> Initializer<Default8Lead1x500> for ADS1298<SPI> {
             x.set_gain(0b010);
             x
         };
-        self.write(CH1SET, data).map_err(|e| {
-            InitializeError::ResetError(e, Some(format!("Failed to set gain for CH1")))
-        })?;
-        self.write(CH4SET, data).map_err(|e| {
-            InitializeError::ResetError(e, Some(format!("Failed to set gain for CH4")))
-        })?;
-        self.write(CH5SET, data).map_err(|e| {
-            InitializeError::ResetError(e, Some(format!("Failed to set gain for CH5")))
-        })?;
-        self.write(CH6SET, data).map_err(|e| {
-            InitializeError::ResetError(e, Some(format!("Failed to set gain for CH6")))
-        })?;
-        self.write(CH7SET, data).map_err(|e| {
-            InitializeError::ResetError(e, Some(format!("Failed to set gain for CH7")))
-        })?;
-        self.write(CH8SET, data).map_err(|e| {
-            InitializeError::ResetError(e, Some(format!("Failed to set gain for CH8")))
-        })?;
+        self.init_write(CH1SET, data.0)?;
+        self.init_write(CH4SET, data.0)?;
+        self.init_write(CH5SET, data.0)?;
+        self.init_write(CH6SET, data.0)?;
+        self.init_write(CH7SET, data.0)?;
+        self.init_write(CH8SET, data.0)?;
 
         // 调节 2,3 通道增益为 2
         let data = {
@@ -156,24 +293,19 @@ impl<SPI: SpiDevice> Initializer<Default8Lead1x500> for ADS1298<SPI> {
             x.set_gain(0b010);
             x
         };
-        self.write(CH2SET, data).map_err(|e| {
-            InitializeError::ResetError(e, Some(format!("Failed to set gain for CH2")))
-        })?;
-        self.write(CH3SET, data).map_err(|e| {
-            InitializeError::ResetError(e, Some(format!("Failed to set gain for CH3")))
-        })?;
+        self.init_write(CH2SET, data.0)?;
+        self.init_write(CH3SET, data.0)?;
 
         // 启用导联脱落检测
-        self.write(LOFF, {
+        self.init_write(LOFF, {
             let mut x = LOffReg(0);
             x.set_flead_off(0b11);
             x.set_vlead_off_en(true);
-            x
-        })
-        .map_err(|e| InitializeError::ResetError(e, Some(format!("Failed to enable LOff"))))?;
+            x.0
+        })?;
 
         // 启用正信号导联脱落检测
-        self.write(LOFF_SENSP, {
+        self.init_write(LOFF_SENSP, {
             let mut x = LOffSensPReg(0);
             x.set_loff1p(true);
             x.set_loff2p(true);
@@ -183,12 +315,11 @@ impl<SPI: SpiDevice> Initializer<Default8Lead1x500> for ADS1298<SPI> {
             x.set_loff6p(true);
             x.set_loff7p(true);
             x.set_loff8p(true);
-            x
-        })
-        .map_err(|e| InitializeError::ResetError(e, Some(format!("Failed to enable LOffSensP"))))?;
+            x.0
+        })?;
 
         // 启用负信号导联脱落检测
-        self.write(LOFF_SENSN, {
+        self.init_write(LOFF_SENSN, {
             let mut x = LOffSensNReg(0);
             x.set_loff1n(true);
             x.set_loff2n(true);
@@ -198,53 +329,48 @@ impl<SPI: SpiDevice> Initializer<Default8Lead1x500> for ADS1298<SPI> {
             x.set_loff6n(true);
             x.set_loff7n(true);
             x.set_loff8n(true);
-            x
-        })
-        .map_err(|e| InitializeError::ResetError(e, Some(format!("Failed to enable LOffSensN"))))?;
+            x.0
+        })?;
 
         // 右腿驱动正信号
-        self.write(RLD_SENSP, {
+        self.init_write(RLD_SENSP, {
             let mut x = RldSensPReg(0);
             x.set_rld2p(true); // IN2P -> RA
             x.set_rld3p(true); // IN3P -> LA
-            x
-        })
-        .map_err(|e| InitializeError::ResetError(e, Some(format!("Failed to enable RldSensP"))))?;
+            x.0
+        })?;
 
         // 右腿驱动负信号
-        self.write(RLD_SENSN, {
+        self.init_write(RLD_SENSN, {
             let mut x = RldSensNReg(0);
             x.set_rld2n(true); // IN2N -> LA
             x.set_rld3n(true); // IN3N -> LL
-            x
-        })
-        .map_err(|e| InitializeError::ResetError(e, Some(format!("Failed to enable RldSensN"))))?;
+            x.0
+        })?;
 
         // WCT RA -> 通道 4 负输入
-        self.write(WCT1, {
+        self.init_write(WCT1, {
             let mut x = Wct1Reg(0);
             // x.set_wcta_channel(0b111);
             x.set_pd_wtca(false);
-            x
-        })
-        .map_err(|e| InitializeError::ResetError(e, Some(format!("Failed to setup WCT1"))))?;
+            x.0
+        })?;
 
         // WCT LA -> 通道 3 负输入
         // WCT LL -> 通道 2 正输入
-        self.write(WCT2, {
+        self.init_write(WCT2, {
             let mut x = Wct2Reg(0);
             // x.set_wctb_channel(0b101);
             // x.set_wctc_channel(0b010);
             x.set_pd_wctb(false);
             x.set_pd_wctc(false);
-            x
-        })
-        .map_err(|e| InitializeError::ResetError(e, Some(format!("Failed to setup WCT2"))))?;
+            x.0
+        })?;
 
         // 启动转换
-        self.operator.start().map_err(|e| {
-            InitializeError::ResetError(e, Some(format!("Failed to enable converting mode")))
-        })?;
+        self.operator
+            .start()
+            .map_err(InitializeError::command(InitStep::StartConversion))?;
 
         Ok(())
     }
@@ -3,11 +3,10 @@ use ux::u5;
 
 use crate::driver::registers::access::{ReadError, ReadFromRegister, WriteError};
 
+use super::clock::ClockSource;
+use super::frame::FRAME_SIZE;
 use super::registers::{access::WriteToRegister, addressable::Address};
 
-/// 500 ns for transfer a single bit
-static TCLK_2_048M: u32 = 500;
-
 /// Table 15. 操作码命令定义
 #[derive(Clone, Copy)]
 pub enum OpCode {
@@ -33,34 +32,112 @@ pub enum OpCode {
     WReg { start: u5, n: u5 },
 }
 
-impl Into<Vec<u8>> for OpCode {
-    fn into(self) -> Vec<u8> {
+impl OpCode {
+    /// 操作码的第一个字节，`RREG` 和 `WREG` 不含寄存器地址
+    pub const fn byte(self) -> u8 {
         match self {
-            OpCode::WakeUp => vec![0b0000_0010],
-            OpCode::StandBy => vec![0b0000_0100],
-            OpCode::Reset => vec![0b0000_0110],
-            OpCode::Start => vec![0b0000_1000],
-            OpCode::Stop => vec![0b0000_1010],
-            OpCode::RDataC => vec![0b0001_0000],
-            OpCode::SDataC => vec![0b0001_0001],
-            OpCode::RData => vec![0b0001_0010],
+            OpCode::WakeUp => 0b0000_0010,
+            OpCode::StandBy => 0b0000_0100,
+            OpCode::Reset => 0b0000_0110,
+            OpCode::Start => 0b0000_1000,
+            OpCode::Stop => 0b0000_1010,
+            OpCode::RDataC => 0b0001_0000,
+            OpCode::SDataC => 0b0001_0001,
+            OpCode::RData => 0b0001_0010,
+            OpCode::RReg { .. } => 0b0010_0000,
+            OpCode::WReg { .. } => 0b0100_0000,
+        }
+    }
+}
+
+impl From<OpCode> for Vec<u8> {
+    fn from(opcode: OpCode) -> Vec<u8> {
+        match opcode {
+            OpCode::RReg { start, n } | OpCode::WReg { start, n } => {
+                vec![opcode.byte() | u8::from(start), u8::from(n)]
+            }
+            _ => vec![opcode.byte()],
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for OpCode {
+    fn format(&self, f: defmt::Formatter) {
+        match *self {
+            OpCode::WakeUp => defmt::write!(f, "WAKEUP"),
+            OpCode::StandBy => defmt::write!(f, "STANDBY"),
+            OpCode::Reset => defmt::write!(f, "RESET"),
+            OpCode::Start => defmt::write!(f, "START"),
+            OpCode::Stop => defmt::write!(f, "STOP"),
+            OpCode::RDataC => defmt::write!(f, "RDATAC"),
+            OpCode::SDataC => defmt::write!(f, "SDATAC"),
+            OpCode::RData => defmt::write!(f, "RDATA"),
             OpCode::RReg { start, n } => {
-                vec![0b0010_0000 | u8::from(start), u8::from(n)]
+                defmt::write!(f, "RREG {=u8:#x} {=u8}", u8::from(start), u8::from(n))
             }
             OpCode::WReg { start, n } => {
-                vec![0b0100_0000 | u8::from(start), u8::from(n)]
+                defmt::write!(f, "WREG {=u8:#x} {=u8}", u8::from(start), u8::from(n))
             }
         }
     }
 }
 
+/// `RDATA` without allocating
+const RDATA: [u8; 1] = [OpCode::RData.byte()];
+
 pub struct Operator<SPI: SpiDevice> {
     spi: SPI,
+    /// tCLK of the master clock, in ns
+    tclk_ns: u32,
+    /// `RDATAC` mode, the default after power-up and `RESET`
+    continuous_read: bool,
+    /// `START` issued and not stopped by `STOP`
+    started: bool,
+    /// `STANDBY` issued and not woken up by `WAKEUP`
+    standby: bool,
 }
 
 impl<SPI: SpiDevice> Operator<SPI> {
+    /// Use the internal 2.048 MHz oscillator
     pub fn new(spi: SPI) -> Operator<SPI> {
-        Operator { spi }
+        Self::with_clock(spi, ClockSource::Internal)
+    }
+
+    /// `clock` determines the tCLK waits after opcodes
+    pub fn with_clock(spi: SPI, clock: ClockSource) -> Operator<SPI> {
+        Operator {
+            spi,
+            tclk_ns: clock.tclk_ns(),
+            continuous_read: true,
+            started: false,
+            standby: false,
+        }
+    }
+
+    /// Whether the device is in `RDATAC` mode, as tracked from the opcodes sent
+    pub fn is_continuous_read(&self) -> bool {
+        self.continuous_read
+    }
+
+    /// Whether `START` was sent and not followed by `STOP`
+    ///
+    /// Conversions started by the `START` pin are not tracked.
+    pub fn is_started(&self) -> bool {
+        self.started
+    }
+
+    /// Whether `STANDBY` was sent and not followed by `WAKEUP`
+    pub fn is_standby(&self) -> bool {
+        self.standby
+    }
+
+    pub fn spi(&self) -> &SPI {
+        &self.spi
+    }
+
+    pub fn spi_mut(&mut self) -> &mut SPI {
+        &mut self.spi
     }
 }
 
@@ -75,7 +152,7 @@ impl<SPI: SpiDevice> WriteToRegister<Address, u8, SPI::Error> for Operator<SPI>
         self.spi
             .transaction(&mut [Operation::Write(&buffer)])
             .map_err(WriteError::SpiTransferError)?;
-        log::debug!("Write {data:#04x} to the address {address:#04x} of ADS1298",);
+        debug!("Write {:#x} to the address {:#x} of ADS1298", data, address);
         Ok(())
     }
 }
@@ -95,18 +172,61 @@ impl<SPI: SpiDevice> ReadFromRegister<Address, u8, SPI::Error> for Operator<SPI>
     }
 }
 
+impl<SPI: SpiDevice> Operator<SPI> {
+    /// 从地址 `start` 开始连续读取 `buffer.len()` 个寄存器
+    pub fn read_registers(
+        &mut self,
+        start: Address,
+        buffer: &mut [u8],
+    ) -> Result<(), ReadError<SPI::Error>> {
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        let command: Vec<_> = OpCode::RReg {
+            start: u5::new(start),
+            n: u5::new(buffer.len() as u8 - 1),
+        }
+        .into();
+        self.spi
+            .transaction(&mut [Operation::Write(&command), Operation::Read(buffer)])
+            .map_err(ReadError::SpiTransferError)
+    }
+
+    /// 从地址 `start` 开始连续写入 `data.len()` 个寄存器
+    pub fn write_registers(
+        &mut self,
+        start: Address,
+        data: &[u8],
+    ) -> Result<(), WriteError<SPI::Error>> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let mut buffer: Vec<_> = OpCode::WReg {
+            start: u5::new(start),
+            n: u5::new(data.len() as u8 - 1),
+        }
+        .into();
+        buffer.extend_from_slice(data);
+        self.spi
+            .transaction(&mut [Operation::Write(&buffer)])
+            .map_err(WriteError::SpiTransferError)?;
+        debug!("Write {:?} from the address {:#x} of ADS1298", data, start);
+        Ok(())
+    }
+}
+
 /// todo
 impl<SPI: SpiDevice> Operator<SPI> {
     pub fn stream<'a>(
         &mut self,
         address: Address,
-        mut buffer: &'a mut [u8],
+        buffer: &'a mut [u8],
     ) -> Result<&'a mut [u8], ReadError<SPI::Error>> {
         let command = address | (1u8 << 7);
         buffer[0] = command;
 
         self.spi
-            .transaction(&mut [Operation::TransferInPlace(&mut buffer)])
+            .transaction(&mut [Operation::TransferInPlace(buffer)])
             .map_err(ReadError::SpiTransferError)?;
 
         Ok(&mut buffer[1..])
@@ -117,89 +237,145 @@ impl<SPI: SpiDevice> Operator<SPI> {
     /// 读取一次数据
     ///
     /// buffer size need to be 27 bytes
-    pub fn read_single_data<'a>(
+    pub fn read_single_data(&mut self) -> Result<[u8; FRAME_SIZE], ReadError<SPI::Error>> {
+        let mut r = [0u8; FRAME_SIZE];
+        self.read_data_into(&mut r)?;
+        Ok(r)
+    }
+
+    /// 通过 `RDATA` 命令读取一次数据到 `buffer`，不分配内存
+    pub fn read_data_into(
         &mut self,
-        // mut buffer: &'a mut [u8],
-    ) -> Result<[u8; 27], ReadError<SPI::Error>> {
-        let command: Vec<_> = OpCode::RData.into();
-        let mut r = [0u8; 27];
+        buffer: &mut [u8; FRAME_SIZE],
+    ) -> Result<(), ReadError<SPI::Error>> {
         self.spi
-            .transaction(&mut [Operation::Write(&command), Operation::Read(&mut r)])
-            .map_err(ReadError::SpiTransferError)?;
-        Ok(r)
+            .transaction(&mut [Operation::Write(&RDATA), Operation::Read(buffer)])
+            .map_err(ReadError::SpiTransferError)
+    }
+
+    /// 在 `RDATAC` 模式下读取一次数据到 `buffer`，不发送命令
+    pub fn read_continuous_data_into(
+        &mut self,
+        buffer: &mut [u8; FRAME_SIZE],
+    ) -> Result<(), ReadError<SPI::Error>> {
+        self.spi
+            .transaction(&mut [Operation::Read(buffer)])
+            .map_err(ReadError::SpiTransferError)
     }
 
     /// 退出待机模式
     ///
     /// 需要 `4` 个 tCLK 周期
-    pub fn wake_up<'a>(&mut self) -> Result<(), WriteError<SPI::Error>> {
+    pub fn wake_up(&mut self) -> Result<(), WriteError<SPI::Error>> {
         let command: Vec<_> = OpCode::WakeUp.into();
         self.spi
             .transaction(&mut [
                 Operation::Write(&command),
-                Operation::DelayNs(TCLK_2_048M * 4),
+                Operation::DelayNs(self.tclk_ns * 4),
             ])
             .map_err(WriteError::SpiTransferError)?;
+        self.standby = false;
         Ok(())
     }
 
     /// 进入待机模式
-    pub fn stand_by<'a>(&mut self) -> Result<(), WriteError<SPI::Error>> {
+    pub fn stand_by(&mut self) -> Result<(), WriteError<SPI::Error>> {
         let command: Vec<_> = OpCode::StandBy.into();
         self.spi
             .transaction(&mut [Operation::Write(&command)])
             .map_err(WriteError::SpiTransferError)?;
+        self.standby = true;
         Ok(())
     }
 
     /// 复位器件
     ///
     /// 需要 `18` 个 tCLK 周期
-    pub fn reset<'a>(&mut self) -> Result<(), WriteError<SPI::Error>> {
+    pub fn reset(&mut self) -> Result<(), WriteError<SPI::Error>> {
         let command: Vec<_> = OpCode::Reset.into();
         self.spi
             .transaction(&mut [
                 Operation::Write(&command),
-                Operation::DelayNs(TCLK_2_048M * 18),
+                Operation::DelayNs(self.tclk_ns * 18),
             ])
             .map_err(WriteError::SpiTransferError)?;
+        self.continuous_read = true;
+        self.started = false;
+        self.standby = false;
         Ok(())
     }
 
     /// 启动/重新启动（同步）转换
     ///
     /// 需要 `4` 个 tCLK 周期，才可发送 `stop` 操作码
-    pub fn start<'a>(&mut self) -> Result<(), WriteError<SPI::Error>> {
+    pub fn start(&mut self) -> Result<(), WriteError<SPI::Error>> {
         let command: Vec<_> = OpCode::Start.into();
         self.spi
             .transaction(&mut [
                 Operation::Write(&command),
-                Operation::DelayNs(TCLK_2_048M * 4),
+                Operation::DelayNs(self.tclk_ns * 4),
             ])
             .map_err(WriteError::SpiTransferError)?;
+        self.started = true;
         Ok(())
     }
 
     /// 停止转换
-    pub fn stop<'a>(&mut self) -> Result<(), WriteError<SPI::Error>> {
+    pub fn stop(&mut self) -> Result<(), WriteError<SPI::Error>> {
         let command: Vec<_> = OpCode::Stop.into();
         self.spi
             .transaction(&mut [Operation::Write(&command)])
             .map_err(WriteError::SpiTransferError)?;
+        self.started = false;
         Ok(())
     }
 
+    /// The single-shot conversion started by `START` finished, the device is idle again
+    pub(crate) fn single_shot_done(&mut self) {
+        self.started = false;
+    }
+
     /// 停止连续读取数据模式
     ///
     /// 需要 `4` 个 tCLK 周期
-    pub fn stop_stream<'a>(&mut self) -> Result<(), WriteError<SPI::Error>> {
+    pub fn stop_stream(&mut self) -> Result<(), WriteError<SPI::Error>> {
         let command: Vec<_> = OpCode::SDataC.into();
         self.spi
             .transaction(&mut [
                 Operation::Write(&command),
-                Operation::DelayNs(TCLK_2_048M * 4),
+                Operation::DelayNs(self.tclk_ns * 4),
             ])
             .map_err(WriteError::SpiTransferError)?;
+        self.continuous_read = false;
+        Ok(())
+    }
+
+    /// 启用连续读取数据模式
+    pub fn start_stream(&mut self) -> Result<(), WriteError<SPI::Error>> {
+        let command: Vec<_> = OpCode::RDataC.into();
+        self.spi
+            .transaction(&mut [Operation::Write(&command)])
+            .map_err(WriteError::SpiTransferError)?;
+        self.continuous_read = true;
         Ok(())
     }
 }
+
+#[cfg(feature = "async")]
+impl<SPI> Operator<SPI>
+where
+    SPI: SpiDevice + embedded_hal_async::spi::SpiDevice,
+{
+    /// 通过 `RDATA` 命令异步读取一次数据到 `buffer`，不分配内存
+    pub async fn read_data_into_async(
+        &mut self,
+        buffer: &mut [u8; FRAME_SIZE],
+    ) -> Result<(), ReadError<<SPI as embedded_hal::spi::ErrorType>::Error>> {
+        embedded_hal_async::spi::SpiDevice::transaction(
+            &mut self.spi,
+            &mut [Operation::Write(&RDATA), Operation::Read(buffer)],
+        )
+        .await
+        .map_err(ReadError::SpiTransferError)
+    }
+}
@@ -0,0 +1,170 @@
+use embedded_hal::spi::SpiDevice;
+
+use super::frame::CHANNELS;
+use super::measurement::channel_address;
+use super::registers::access::{ReadFromRegister, WriteToRegister};
+use super::registers::data::{ChSetReg, Config1Reg, Config3Reg, Config4Reg, Wct1Reg, Wct2Reg};
+use super::registers::{CONFIG1, CONFIG3, CONFIG4, WCT1, WCT2};
+use super::{ConfigError, ADS1298};
+
+/// Rough typical supply currents at AVDD = 3 V and DVDD = 1.8 V, in mA
+///
+/// For power budgeting only, the datasheet gives the actual limits.
+mod typical {
+    /// AVDD per channel in HR mode
+    pub const CHANNEL_HR: f32 = 0.33;
+    /// AVDD per channel in LP mode
+    pub const CHANNEL_LP: f32 = 0.21;
+    /// AVDD of the internal reference buffer
+    pub const REFERENCE: f32 = 0.22;
+    /// AVDD of the RLD amplifier
+    pub const RLD: f32 = 0.05;
+    /// AVDD of each WCT amplifier
+    pub const WCT: f32 = 0.06;
+    /// AVDD of the lead-off comparators
+    pub const LEAD_OFF: f32 = 0.03;
+    /// DVDD in HR mode
+    pub const DIGITAL_HR: f32 = 0.35;
+    /// DVDD in LP mode
+    pub const DIGITAL_LP: f32 = 0.2;
+    /// AVDD in standby
+    pub const STANDBY_ANALOG: f32 = 0.16;
+    /// DVDD in standby
+    pub const STANDBY_DIGITAL: f32 = 0.06;
+}
+
+/// Estimated supply currents of a configuration, in mA
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CurrentEstimate {
+    pub avdd_ma: f32,
+    pub dvdd_ma: f32,
+}
+
+impl CurrentEstimate {
+    /// Power dissipation at the given supplies, in mW
+    pub fn power_mw(&self, avdd: f32, dvdd: f32) -> f32 {
+        self.avdd_ma * avdd + self.dvdd_ma * dvdd
+    }
+}
+
+impl<SPI: SpiDevice> ADS1298<SPI> {
+    /// Enter standby, conversion and `RDATAC` mode are kept for `wake`
+    ///
+    /// No command but `WAKEUP` may be sent in standby.
+    pub fn enter_standby(&mut self) -> Result<(), ConfigError<SPI::Error>> {
+        if !self.operator.is_standby() {
+            self.operator.stand_by()?;
+        }
+        Ok(())
+    }
+
+    /// Leave standby and re-synchronize conversion with `START` if it was started
+    pub fn wake(&mut self) -> Result<(), ConfigError<SPI::Error>> {
+        if self.operator.is_standby() {
+            self.operator.wake_up()?;
+            if self.operator.is_started() {
+                self.operator.start()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Power down channels whose bit in `channels` is set, bit 0 is CH1
+    ///
+    /// As TI recommends, the inputs of powered down channels are shorted, `MUX` = `001`.
+    /// Their previous `CHnSET` are kept for `power_up_channels`.
+    pub fn power_down_channels(&mut self, channels: u8) -> Result<(), ConfigError<SPI::Error>> {
+        self.paused(false, |driver| {
+            for i in (0..CHANNELS).filter(|i| channels & (1 << i) != 0) {
+                let address = channel_address(i);
+                let setting = ChSetReg(driver.operator.read(address)?);
+                if setting.pd() {
+                    continue;
+                }
+                let mut x = setting;
+                x.set_pd(true);
+                x.set_mux(ChSetReg::MUX_INPUT_SHORT);
+                driver.operator.write(address, x.0)?;
+                driver.powered_down[i] = Some(setting);
+            }
+            Ok(())
+        })
+    }
+
+    /// Power up channels whose bit in `channels` is set, bit 0 is CH1
+    ///
+    /// Channels powered down by `power_down_channels` get their previous `CHnSET` back,
+    /// other channels keep their `MUX`.
+    pub fn power_up_channels(&mut self, channels: u8) -> Result<(), ConfigError<SPI::Error>> {
+        self.paused(false, |driver| {
+            for i in (0..CHANNELS).filter(|i| channels & (1 << i) != 0) {
+                let address = channel_address(i);
+                let x = match driver.powered_down[i].take() {
+                    Some(setting) => setting,
+                    None => {
+                        let mut x = ChSetReg(driver.operator.read(address)?);
+                        x.set_pd(false);
+                        x
+                    }
+                };
+                driver.operator.write(address, x.0)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Power down every channel not in `used`, power up every channel in it, bit 0 is CH1
+    pub fn set_used_channels(&mut self, used: u8) -> Result<(), ConfigError<SPI::Error>> {
+        self.power_down_channels(!used)?;
+        self.power_up_channels(used)
+    }
+
+    /// Estimate the supply currents of the current configuration
+    pub fn estimate_current(&mut self) -> Result<CurrentEstimate, ConfigError<SPI::Error>> {
+        if self.operator.is_standby() {
+            return Ok(CurrentEstimate {
+                avdd_ma: typical::STANDBY_ANALOG,
+                dvdd_ma: typical::STANDBY_DIGITAL,
+            });
+        }
+        self.paused(false, |driver| {
+            let config1: Config1Reg = driver.read(CONFIG1)?;
+            let config3: Config3Reg = driver.read(CONFIG3)?;
+            let config4: Config4Reg = driver.read(CONFIG4)?;
+            let wct1: Wct1Reg = driver.read(WCT1)?;
+            let wct2: Wct2Reg = driver.read(WCT2)?;
+            let mut channels = 0;
+            for i in 0..CHANNELS {
+                if !ChSetReg(driver.operator.read(channel_address(i))?).pd() {
+                    channels += 1;
+                }
+            }
+
+            let (channel, digital) = if config1.hr() {
+                (typical::CHANNEL_HR, typical::DIGITAL_HR)
+            } else {
+                (typical::CHANNEL_LP, typical::DIGITAL_LP)
+            };
+            let mut avdd_ma = channel * channels as f32;
+            // PD_* 位为 1 表示上电
+            let blocks = [
+                (config3.pd_refbuf(), typical::REFERENCE),
+                (config3.pd_rld(), typical::RLD),
+                (config4.pd_loff_comp(), typical::LEAD_OFF),
+                (wct1.pd_wtca(), typical::WCT),
+                (wct2.pd_wctb(), typical::WCT),
+                (wct2.pd_wctc(), typical::WCT),
+            ];
+            for (powered, current) in blocks {
+                if powered {
+                    avdd_ma += current;
+                }
+            }
+            Ok(CurrentEstimate {
+                avdd_ma,
+                dvdd_ma: digital,
+            })
+        })
+    }
+}
@@ -0,0 +1,638 @@
+//! Device configuration profiles, deserialized from TOML or JSON
+//!
+//! A profile describes the electrode wiring of a product: the channels with their inputs and
+//! gains, WCT, RLD and lead-off. It is validated against the register constraints, then
+//! applied with `Initializer<&Profile>`.
+//!
+//! ```toml
+//! name = "3-lead"
+//! data_rate = 500
+//!
+//! [[channels]]
+//! channel = 2
+//! gain = 6
+//! rld = { positive = true, negative = true }
+//! lead_off = { positive = true, negative = true }
+//!
+//! [rld]
+//! enabled = true
+//!
+//! [lead_off]
+//! current_na = 6
+//! ```
+
+use core::fmt;
+
+use embedded_hal::spi::SpiDevice;
+use serde::{Deserialize, Serialize};
+
+use super::clock::ClockSource;
+use super::data_rate::{config1_for_data_rate, PowerMode};
+use super::frame::CHANNELS;
+use super::initialization::{InitStep, InitializeError, Initializer};
+use super::registers::addressable::Address;
+use super::registers::data::{ChSetReg, Config1Reg};
+use super::registers::map::{RegisterMap, REGISTER_COUNT};
+use super::ADS1298;
+
+/// PGA gains of `CHnSET::gain` `000` ~ `110`
+const GAINS: [u8; 7] = [6, 1, 2, 3, 4, 8, 12];
+
+/// Positive side thresholds of `LOFF::comp_th` `000` ~ `111`, in %
+const LEAD_OFF_THRESHOLDS: [f32; 8] = [95.0, 92.5, 90.0, 87.5, 85.0, 80.0, 75.0, 70.0];
+
+/// Currents of `LOFF::ilead_off` `00` ~ `11`, in nA
+const LEAD_OFF_CURRENTS: [u8; 4] = [6, 12, 18, 24];
+
+/// Registers skipped when writing a profile, they are read-only
+const READ_ONLY: [Address; 3] = [0x00, 0x12, 0x13];
+
+/// Configuration of a whole device
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[serde(default)]
+    pub name: String,
+    /// Output data rate, in SPS
+    pub data_rate: u32,
+    /// HR mode is preferred if not given
+    #[serde(default)]
+    pub power_mode: Option<PowerMode>,
+    /// VREFP = 4 V, only with a 5 V analog supply
+    #[serde(default)]
+    pub vref_4v: bool,
+    /// Power down the internal reference buffer and use an external reference
+    #[serde(default)]
+    pub external_reference: bool,
+    /// Channels not listed are powered down with their inputs shorted
+    pub channels: Vec<ChannelProfile>,
+    /// Required by channels with `input = "test-signal"`
+    #[serde(default)]
+    pub test_signal: Option<TestSignal>,
+    #[serde(default)]
+    pub wct: WctProfile,
+    #[serde(default)]
+    pub rld: RldProfile,
+    /// Required by channels with `lead_off` electrodes
+    #[serde(default)]
+    pub lead_off: Option<LeadOffProfile>,
+}
+
+/// `CHnSET` and the per-channel bits of the RLD and lead-off registers
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChannelProfile {
+    /// 1 ~ 8
+    pub channel: u8,
+    /// `false` powers the channel down
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub input: Input,
+    /// PGA gain, one of 1, 2, 3, 4, 6, 8 and 12
+    pub gain: u8,
+    /// Electrodes summed into RLD, `RLD_SENSP` and `RLD_SENSN`
+    #[serde(default)]
+    pub rld: Electrodes,
+    /// Electrodes with lead-off detection, `LOFF_SENSP` and `LOFF_SENSN`
+    #[serde(default)]
+    pub lead_off: Electrodes,
+    /// Flip the lead-off current direction, `LOFF_FLIP`
+    #[serde(default)]
+    pub lead_off_flip: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// `CHnSET::mux`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Input {
+    #[default]
+    Normal,
+    Shorted,
+    RldMeasure,
+    Mvdd,
+    Temperature,
+    TestSignal,
+    RldDrp,
+    RldDrn,
+}
+
+impl Input {
+    fn mux(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Positive and negative electrode of a channel
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Electrodes {
+    #[serde(default)]
+    pub positive: bool,
+    #[serde(default)]
+    pub negative: bool,
+}
+
+impl Electrodes {
+    fn any(&self) -> bool {
+        self.positive || self.negative
+    }
+}
+
+/// Internal test signal, `CONFIG2`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestSignal {
+    /// 2 × –(VREFP – VREFN)/2400 V instead of 1 ×
+    #[serde(default)]
+    pub double_amplitude: bool,
+    pub frequency: TestFrequency,
+}
+
+/// `CONFIG2::test_freq`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TestFrequency {
+    /// fCLK/2^21
+    Slow,
+    /// fCLK/2^20
+    Fast,
+    Dc,
+}
+
+/// WCT amplifiers and augmented leads, `WCT1`, `WCT2` and `CONFIG4::wct_to_rld`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WctProfile {
+    /// Input of WCTA, usually RA, as `IN1P` ~ `IN4N`, powered down if not given
+    #[serde(default)]
+    pub a: Option<String>,
+    /// Input of WCTB, usually LA
+    #[serde(default)]
+    pub b: Option<String>,
+    /// Input of WCTC, usually LL
+    #[serde(default)]
+    pub c: Option<String>,
+    #[serde(default)]
+    pub to_rld: bool,
+    /// (WCTA + WCTB)/2 to the negative input of CH6
+    #[serde(default)]
+    pub avf_ch6: bool,
+    /// (WCTA + WCTC)/2 to the negative input of CH5
+    #[serde(default)]
+    pub avl_ch5: bool,
+    /// (WCTB + WCTC)/2 to the negative input of CH7
+    #[serde(default)]
+    pub avr_ch7: bool,
+    /// (WCTB + WCTC)/2 to the negative input of CH4
+    #[serde(default)]
+    pub avr_ch4: bool,
+}
+
+/// RLD amplifier, `CONFIG3`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RldProfile {
+    /// Power up the RLD buffer
+    #[serde(default)]
+    pub enabled: bool,
+    /// RLDREF = (AVDD – AVSS)/2, otherwise fed externally
+    #[serde(default = "enabled")]
+    pub internal_reference: bool,
+    /// Route RLD_IN to channels with `input = "rld-measure"`
+    #[serde(default)]
+    pub measure: bool,
+    /// RLD lead-off sensing
+    #[serde(default)]
+    pub lead_off_sense: bool,
+}
+
+impl Default for RldProfile {
+    fn default() -> Self {
+        RldProfile {
+            enabled: false,
+            internal_reference: true,
+            measure: false,
+            lead_off_sense: false,
+        }
+    }
+}
+
+/// Lead-off detection, `LOFF` and `CONFIG4::pd_loff_comp`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LeadOffProfile {
+    #[serde(default)]
+    pub mode: LeadOffMode,
+    #[serde(default)]
+    pub frequency: LeadOffFrequency,
+    /// Current of the current source mode, one of 6, 12, 18 and 24 nA
+    #[serde(default = "default_lead_off_current")]
+    pub current_na: u8,
+    /// Positive side comparator threshold, one of 95, 92.5, 90, 87.5, 85, 80, 75 and 70 %
+    #[serde(default = "default_lead_off_threshold")]
+    pub threshold_percent: f32,
+}
+
+fn default_lead_off_current() -> u8 {
+    LEAD_OFF_CURRENTS[0]
+}
+
+fn default_lead_off_threshold() -> f32 {
+    LEAD_OFF_THRESHOLDS[0]
+}
+
+/// `LOFF::vlead_off_en`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LeadOffMode {
+    #[default]
+    CurrentSource,
+    PullResistor,
+}
+
+/// `LOFF::flead_off`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LeadOffFrequency {
+    #[default]
+    Dc,
+    /// fDR/4
+    Ac,
+}
+
+#[derive(Debug)]
+pub enum ProfileError {
+    /// Channel not in 1 ~ 8
+    InvalidChannel(u8),
+    DuplicateChannel(u8),
+    InvalidGain {
+        channel: u8,
+        gain: u8,
+    },
+    /// No `CONFIG1` gives the data rate in the power mode, in SPS
+    UnsupportedDataRate(u32),
+    /// WCT input not in `IN1P` ~ `IN4N`
+    InvalidWctInput(String),
+    /// Augmented lead using a powered down WCT amplifier
+    WctAmplifierPoweredDown(char),
+    InvalidLeadOffCurrent(u8),
+    InvalidLeadOffThreshold(f32),
+    /// Channel with `lead_off` electrodes but no `lead_off` section
+    LeadOffNotConfigured(u8),
+    /// Channel with `input = "test-signal"` but no `test_signal` section
+    TestSignalNotConfigured(u8),
+    /// Channel with `input = "rld-measure"` but `rld.measure` not set
+    RldMeasureNotEnabled(u8),
+    /// Channel with `rld` electrodes but RLD not enabled
+    RldNotEnabled(u8),
+    /// Powered down channel with `rld` or `lead_off` electrodes
+    ChannelPoweredDown(u8),
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+    #[cfg(feature = "std")]
+    Toml(toml::de::Error),
+    #[cfg(feature = "std")]
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileError::InvalidChannel(channel) => write!(f, "invalid channel {channel}"),
+            ProfileError::DuplicateChannel(channel) => write!(f, "channel {channel} listed twice"),
+            ProfileError::InvalidGain { channel, gain } => {
+                write!(f, "invalid gain {gain} of channel {channel}")
+            }
+            ProfileError::UnsupportedDataRate(hz) => write!(f, "unsupported data rate {hz} SPS"),
+            ProfileError::InvalidWctInput(input) => write!(f, "invalid WCT input {input:?}"),
+            ProfileError::WctAmplifierPoweredDown(amplifier) => {
+                write!(f, "augmented lead uses powered down WCT{amplifier}")
+            }
+            ProfileError::InvalidLeadOffCurrent(na) => {
+                write!(f, "invalid lead-off current {na} nA")
+            }
+            ProfileError::InvalidLeadOffThreshold(percent) => {
+                write!(f, "invalid lead-off threshold {percent}%")
+            }
+            ProfileError::LeadOffNotConfigured(channel) => {
+                write!(
+                    f,
+                    "channel {channel} uses lead-off but it is not configured"
+                )
+            }
+            ProfileError::TestSignalNotConfigured(channel) => {
+                write!(
+                    f,
+                    "channel {channel} uses the test signal but it is not configured"
+                )
+            }
+            ProfileError::RldMeasureNotEnabled(channel) => {
+                write!(
+                    f,
+                    "channel {channel} measures RLD but rld.measure is not set"
+                )
+            }
+            ProfileError::RldNotEnabled(channel) => {
+                write!(f, "channel {channel} drives RLD but RLD is not enabled")
+            }
+            ProfileError::ChannelPoweredDown(channel) => {
+                write!(
+                    f,
+                    "channel {channel} is powered down but used by RLD or lead-off"
+                )
+            }
+            #[cfg(feature = "std")]
+            ProfileError::Io(e) => write!(f, "failed to read profile: {e}"),
+            #[cfg(feature = "std")]
+            ProfileError::Toml(e) => write!(f, "invalid TOML profile: {e}"),
+            #[cfg(feature = "std")]
+            ProfileError::Json(e) => write!(f, "invalid JSON profile: {e}"),
+        }
+    }
+}
+
+impl core::error::Error for ProfileError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            ProfileError::Io(e) => Some(e),
+            #[cfg(feature = "std")]
+            ProfileError::Toml(e) => Some(e),
+            #[cfg(feature = "std")]
+            ProfileError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for ProfileError {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{}", defmt::Display2Format(self))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Profile {
+    pub fn from_toml(s: &str) -> Result<Profile, ProfileError> {
+        let profile: Profile = toml::from_str(s).map_err(ProfileError::Toml)?;
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    pub fn from_json(s: &str) -> Result<Profile, ProfileError> {
+        let profile: Profile = serde_json::from_str(s).map_err(ProfileError::Json)?;
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    /// Load a `.json` file as JSON, any other file as TOML
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Profile, ProfileError> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path).map_err(ProfileError::Io)?;
+        if path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("json"))
+        {
+            Profile::from_json(&s)
+        } else {
+            Profile::from_toml(&s)
+        }
+    }
+}
+
+impl Profile {
+    /// Check the profile against the register constraints
+    ///
+    /// The data rate is checked by `to_register_map`, as it depends on the master clock.
+    pub fn validate(&self) -> Result<(), ProfileError> {
+        let mut listed = [false; CHANNELS];
+        for x in &self.channels {
+            let channel = x.channel;
+            let i = usize::from(channel)
+                .checked_sub(1)
+                .filter(|&i| i < CHANNELS)
+                .ok_or(ProfileError::InvalidChannel(channel))?;
+            if core::mem::replace(&mut listed[i], true) {
+                return Err(ProfileError::DuplicateChannel(channel));
+            }
+            if !GAINS.contains(&x.gain) {
+                return Err(ProfileError::InvalidGain {
+                    channel,
+                    gain: x.gain,
+                });
+            }
+            if !x.enabled && (x.rld.any() || x.lead_off.any()) {
+                return Err(ProfileError::ChannelPoweredDown(channel));
+            }
+            if x.lead_off.any() && self.lead_off.is_none() {
+                return Err(ProfileError::LeadOffNotConfigured(channel));
+            }
+            if x.rld.any() && !self.rld.enabled {
+                return Err(ProfileError::RldNotEnabled(channel));
+            }
+            if x.input == Input::TestSignal && self.test_signal.is_none() {
+                return Err(ProfileError::TestSignalNotConfigured(channel));
+            }
+            if x.input == Input::RldMeasure && !self.rld.measure {
+                return Err(ProfileError::RldMeasureNotEnabled(channel));
+            }
+        }
+
+        let [a, b, c] = [&self.wct.a, &self.wct.b, &self.wct.c].map(|input| {
+            input
+                .as_deref()
+                .map(wct_channel)
+                .transpose()
+                .map(|x| x.is_some())
+        });
+        let (a, b, c) = (a?, b?, c?);
+        let wct = &self.wct;
+        if (wct.avf_ch6 || wct.avl_ch5) && !a {
+            return Err(ProfileError::WctAmplifierPoweredDown('A'));
+        }
+        if (wct.avf_ch6 || wct.avr_ch7 || wct.avr_ch4) && !b {
+            return Err(ProfileError::WctAmplifierPoweredDown('B'));
+        }
+        if (wct.avl_ch5 || wct.avr_ch7 || wct.avr_ch4) && !c {
+            return Err(ProfileError::WctAmplifierPoweredDown('C'));
+        }
+
+        if let Some(lead_off) = &self.lead_off {
+            lead_off_current(lead_off)?;
+            lead_off_threshold(lead_off)?;
+        }
+        Ok(())
+    }
+
+    /// Registers configured by the profile, running at `clock`
+    ///
+    /// Registers and fields not covered by the profile are zero, `ID`, `LOFF_STATP` and
+    /// `LOFF_STATN` included.
+    pub fn to_register_map(&self, clock: ClockSource) -> Result<RegisterMap, ProfileError> {
+        self.validate()?;
+        let mut map = RegisterMap::from_bytes(&[0; REGISTER_COUNT]);
+
+        let modes = match self.power_mode {
+            Some(mode) => vec![mode.hr()],
+            None => vec![true, false],
+        };
+        map.config1 = config1_for_data_rate(clock, Config1Reg(0), self.data_rate, &modes)
+            .ok_or(ProfileError::UnsupportedDataRate(self.data_rate))?;
+
+        if let Some(test) = &self.test_signal {
+            map.config2.set_int_test(true);
+            map.config2.set_test_amp(test.double_amplitude);
+            map.config2.set_test_freq(match test.frequency {
+                TestFrequency::Slow => 0b00,
+                TestFrequency::Fast => 0b01,
+                TestFrequency::Dc => 0b11,
+            });
+        }
+
+        map.config3.set_rev_6(true);
+        map.config3.set_pd_refbuf(!self.external_reference);
+        map.config3.set_vref_4v(self.vref_4v);
+        map.config3.set_rld_meas(self.rld.measure);
+        map.config3.set_rldref_int(self.rld.internal_reference);
+        map.config3.set_pd_rld(self.rld.enabled);
+        map.config3.set_rld_loff_sens(self.rld.lead_off_sense);
+
+        if let Some(lead_off) = &self.lead_off {
+            map.loff.set_comp_th(lead_off_threshold(lead_off)?);
+            map.loff
+                .set_vlead_off_en(lead_off.mode == LeadOffMode::PullResistor);
+            map.loff.set_ilead_off(lead_off_current(lead_off)?);
+            map.loff.set_flead_off(match lead_off.frequency {
+                LeadOffFrequency::Ac => 0b01,
+                LeadOffFrequency::Dc => 0b11,
+            });
+            map.config4.set_pd_loff_comp(true);
+        }
+
+        let mut settings = [ChSetReg(0); CHANNELS].map(|mut x| {
+            // TI 建议断电通道设置为输入短路
+            x.set_pd(true);
+            x.set_mux(Input::Shorted.mux());
+            x
+        });
+        for x in &self.channels {
+            let i = usize::from(x.channel - 1);
+            let bit = 1 << i;
+            let setting = &mut settings[i];
+            setting.set_pd(!x.enabled);
+            setting.set_mux(x.input.mux());
+            // `validate` checked the gain
+            setting.set_gain(GAINS.iter().position(|&g| g == x.gain).unwrap_or(0) as u8);
+            if x.rld.positive {
+                map.rld_sensp.0 |= bit;
+            }
+            if x.rld.negative {
+                map.rld_sensn.0 |= bit;
+            }
+            if x.lead_off.positive {
+                map.loff_sensp.0 |= bit;
+            }
+            if x.lead_off.negative {
+                map.loff_sensn.0 |= bit;
+            }
+            if x.lead_off_flip {
+                map.loff_flip.0 |= bit;
+            }
+        }
+        [
+            map.ch1set, map.ch2set, map.ch3set, map.ch4set, map.ch5set, map.ch6set, map.ch7set,
+            map.ch8set,
+        ] = settings;
+
+        // 所有 GPIO 为输入
+        map.gpio.0 = 0x0f;
+
+        let wct = &self.wct;
+        if let Some(input) = &wct.a {
+            map.wct1.set_pd_wtca(true);
+            map.wct1.set_wcta_channel(wct_channel(input)?);
+        }
+        if let Some(input) = &wct.b {
+            map.wct2.set_pd_wctb(true);
+            map.wct2.set_wctb_channel(wct_channel(input)?);
+        }
+        if let Some(input) = &wct.c {
+            map.wct2.set_pd_wctc(true);
+            map.wct2.set_wctc_channel(wct_channel(input)?);
+        }
+        map.wct1.set_a_vf_ch6(wct.avf_ch6);
+        map.wct1.set_a_vl_ch5(wct.avl_ch5);
+        map.wct1.set_a_vr_ch7(wct.avr_ch7);
+        map.wct1.set_avr_ch4(wct.avr_ch4);
+        map.config4.set_wct_to_rld(wct.to_rld);
+
+        Ok(map)
+    }
+}
+
+/// `WCTx_channel` of `IN1P` ~ `IN4N`
+fn wct_channel(input: &str) -> Result<u8, ProfileError> {
+    let invalid = || ProfileError::InvalidWctInput(input.to_string());
+    let bytes = input.as_bytes();
+    let [b'I' | b'i', b'N' | b'n', channel @ b'1'..=b'4', side] = *bytes else {
+        return Err(invalid());
+    };
+    let negative = match side {
+        b'P' | b'p' => 0,
+        b'N' | b'n' => 1,
+        _ => return Err(invalid()),
+    };
+    Ok(((channel - b'1') << 1) | negative)
+}
+
+fn lead_off_current(lead_off: &LeadOffProfile) -> Result<u8, ProfileError> {
+    LEAD_OFF_CURRENTS
+        .iter()
+        .position(|&na| na == lead_off.current_na)
+        .map(|x| x as u8)
+        .ok_or(ProfileError::InvalidLeadOffCurrent(lead_off.current_na))
+}
+
+fn lead_off_threshold(lead_off: &LeadOffProfile) -> Result<u8, ProfileError> {
+    LEAD_OFF_THRESHOLDS
+        .iter()
+        .position(|&percent| percent == lead_off.threshold_percent)
+        .map(|x| x as u8)
+        .ok_or(ProfileError::InvalidLeadOffThreshold(
+            lead_off.threshold_percent,
+        ))
+}
+
+impl<SPI: SpiDevice> Initializer<&Profile> for ADS1298<SPI> {
+    type SpiError = SPI::Error;
+
+    /// Same preconditions as `Initializer<Default8Lead1x500>`
+    ///
+    /// Conversion is started after writing the registers.
+    fn init(&mut self, profile: &Profile) -> Result<(), InitializeError<Self::SpiError>> {
+        let map = profile
+            .to_register_map(self.clock)
+            .map_err(InitializeError::ProfileError)?;
+        self.reset_and_check_id()?;
+        for (address, data) in map.to_bytes().into_iter().enumerate() {
+            let address = address as Address;
+            if !READ_ONLY.contains(&address) {
+                self.init_write(address, data)?;
+            }
+        }
+        self.operator
+            .start()
+            .map_err(InitializeError::command(InitStep::StartConversion))?;
+        info!(
+            "ADS1298 initialized with profile {:?}",
+            profile.name.as_str()
+        );
+        Ok(())
+    }
+}
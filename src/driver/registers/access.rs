@@ -1,17 +1,41 @@
+use core::fmt;
+
 pub trait WriteToRegister<Register, Data, SpiError> {
     fn write(&mut self, register: Register, data: Data) -> Result<(), WriteError<SpiError>>;
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum WriteError<SpiError> {
     SpiTransferError(SpiError),
 }
 
+impl<SpiError: fmt::Debug> fmt::Display for WriteError<SpiError> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteError::SpiTransferError(e) => write!(f, "SPI write failed: {e:?}"),
+        }
+    }
+}
+
+impl<SpiError: fmt::Debug> core::error::Error for WriteError<SpiError> {}
+
 pub trait ReadFromRegister<Register, Data, SpiError> {
     fn read(&mut self, register: Register) -> Result<Data, ReadError<SpiError>>;
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ReadError<SpiError> {
     SpiTransferError(SpiError),
 }
+
+impl<SpiError: fmt::Debug> fmt::Display for ReadError<SpiError> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::SpiTransferError(e) => write!(f, "SPI read failed: {e:?}"),
+        }
+    }
+}
+
+impl<SpiError: fmt::Debug> core::error::Error for ReadError<SpiError> {}
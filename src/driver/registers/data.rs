@@ -4,17 +4,46 @@ use bitfield::bitfield;
 ///
 /// 状态字 `[0:23]`
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct DataStatus {
     pub ds1: DataStatus1,
     pub ds2: DataStatus2,
     pub ds3: DataStatus3,
 }
 
+impl DataStatus {
+    /// Status word of a conversion, `gpio` is the `GPIO` register, of which `GPIOD[4:1]` is kept
+    pub fn new(loff_statp: u8, loff_statn: u8, gpio: u8) -> Self {
+        DataStatus {
+            ds1: DataStatus1(0xc0 | loff_statp >> 4),
+            ds2: DataStatus2(loff_statp << 4 | loff_statn >> 4),
+            ds3: DataStatus3(loff_statn << 4 | gpio >> 4),
+        }
+    }
+
+    /// `LOFF_STATP` at the time of the conversion, bit 0 is IN1P
+    pub fn loff_statp(&self) -> u8 {
+        self.ds1.0 << 4 | self.ds2.0 >> 4
+    }
+
+    /// `LOFF_STATN` at the time of the conversion, bit 0 is IN1N
+    pub fn loff_statn(&self) -> u8 {
+        self.ds2.0 << 4 | self.ds3.0 >> 4
+    }
+
+    /// Whether any electrode is reported off
+    pub fn is_lead_off(&self) -> bool {
+        self.loff_statp() != 0 || self.loff_statn() != 0
+    }
+}
+
 bitfield! {
     /// 9.4.1.3.1 状态字
     ///
     /// 状态字 `[0:7]`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct DataStatus1(u8);
     impl Debug;
     bool;
@@ -57,6 +86,8 @@ bitfield! {
     ///
     /// 状态字 `[8:15]`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct DataStatus2(u8);
     impl Debug;
     bool;
@@ -115,6 +146,8 @@ bitfield! {
     ///
     /// 状态字 `[16:23]`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct DataStatus3(u8);
     impl Debug;
     bool;
@@ -179,6 +212,8 @@ bitfield! {
 bitfield! {
     /// ID 控制寄存器 地址 = `00h` 复位 = `xxh`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct IdReg(u8);
     impl Debug;
     bool;
@@ -203,6 +238,8 @@ bitfield! {
 bitfield! {
     /// 配置寄存器 1 地址 = `01h` 复位 = `06h`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct Config1Reg(u8);
     impl Debug;
     bool; u8;
@@ -253,6 +290,8 @@ bitfield! {
 bitfield! {
     /// 配置寄存器 2 地址 = `02h` 复位 = `40h`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct Config2Reg(u8);
     impl Debug;
     bool; u8;
@@ -303,6 +342,8 @@ bitfield! {
 bitfield! {
     /// 配置寄存器 3 地址 = `03h` 复位 = `40h`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct Config3Reg(u8);
     impl Debug;
     bool;
@@ -364,6 +405,8 @@ bitfield! {
 bitfield! {
     /// 导联脱落控制寄存器 地址 = `04h` 复位 = `00h`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct LOffReg(u8);
     impl Debug;
     bool; u8;
@@ -421,6 +464,8 @@ bitfield! {
 bitfield! {
     /// 通道设置 地址 = `05h` 复位 = `00h`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct ChSetReg(u8);
     impl Debug;
     bool; u8;
@@ -469,6 +514,8 @@ bitfield! {
 bitfield! {
     /// RLD 正信号导出寄存器 地址 = `0Dh` 复位 = `00h`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct RldSensPReg(u8);
     impl Debug;
     bool; u8;
@@ -517,6 +564,8 @@ bitfield! {
 bitfield! {
     /// RLD 负信号导出寄存器 地址 = `0Eh` 复位 = `00h`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct RldSensNReg(u8);
     impl Debug;
     bool; u8;
@@ -565,6 +614,8 @@ bitfield! {
 bitfield! {
     /// 正信号导联脱落检测寄存器 地址 = `0Fh` 复位 = `00h`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct LOffSensPReg(u8);
     impl Debug;
     bool; u8;
@@ -613,6 +664,8 @@ bitfield! {
 bitfield! {
     /// 负信号导联脱落检测寄存器 地址 = `10h` 复位 = `00h`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct LOffSensNReg(u8);
     impl Debug;
     bool; u8;
@@ -661,6 +714,8 @@ bitfield! {
 bitfield! {
     /// 导联脱落翻转寄存器 地址 = `11h` 复位 = `00h`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct LoffFlipReg(u8);
     impl Debug;
     bool; u8;
@@ -725,6 +780,8 @@ bitfield! {
 bitfield! {
     /// 导联脱落正信号状态寄存器 地址 = `12h` 复位 = `00h`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct LOffStatPReg(u8);
     impl Debug;
     bool; u8;
@@ -789,6 +846,8 @@ bitfield! {
 bitfield! {
     /// 导联脱落负信号状态寄存器 地址 = `13h` 复位 = `00h`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct LOffStatNReg(u8);
     impl Debug;
     bool; u8;
@@ -853,6 +912,8 @@ bitfield! {
 bitfield! {
     /// 通用 I/O 寄存器 地址 = `14h` 复位 = `0Fh`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct GpioReg(u8);
     impl Debug;
     bool; u8;
@@ -880,6 +941,8 @@ bitfield! {
 bitfield! {
     /// 起搏信号检测寄存器 地址 = `15h` 复位 = `00h`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct PaceReg(u8);
     impl Debug;
     bool; u8;
@@ -917,6 +980,8 @@ bitfield! {
 bitfield! {
     /// 呼吸控制寄存器 地址 = `16h` 复位 = `00h`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct RespReg(u8);
     impl Debug;
     bool; u8;
@@ -963,6 +1028,8 @@ bitfield! {
 bitfield! {
     /// 配置寄存器 4 地址 = `17h` 复位 = `00h`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct Config4Reg(u8);
     impl Debug;
     bool; u8;
@@ -1016,6 +1083,8 @@ bitfield! {
 bitfield! {
     /// 威尔逊中心端子和增强导联控制寄存器 地址 = `18h` 复位 = `00h`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct Wct1Reg(u8);
     impl Debug;
     bool; u8;
@@ -1024,7 +1093,7 @@ bitfield! {
     /// - 0 = 禁用
     /// - 1 = 启用
     pub a_vf_ch6, set_a_vf_ch6: 7;
-    /// 使 (WCTA + WCTB)/2 成为通道 5（ADS1296、ADS1296R、ADS1298 和 ADS1298R）的负输入 `[6]` `R/W` 复位 = `0`
+    /// 使 (WCTA + WCTC)/2 成为通道 5（ADS1296、ADS1296R、ADS1298 和 ADS1298R）的负输入 `[6]` `R/W` 复位 = `0`
     ///
     /// - 0 = 禁用
     /// - 1 = 启用
@@ -1062,6 +1131,8 @@ bitfield! {
 bitfield! {
     /// 威尔逊中心端子控制寄存器 地址 = `19h` 复位 = `00h`
     #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct Wct2Reg(u8);
     impl Debug;
     bool; u8;
@@ -1102,3 +1173,41 @@ bitfield! {
     /// - 111 = 通道 4 负输入连接到 WCTC 放大器
     pub wctc_channel, set_wctc_channel: 2, 0;
 }
+
+impl ChSetReg {
+    /// `mux` 输入短路
+    pub const MUX_INPUT_SHORT: u8 = 0b001;
+    /// `mux` MVDD
+    pub const MUX_MVDD: u8 = 0b011;
+    /// `mux` 温度传感器
+    pub const MUX_TEMPERATURE: u8 = 0b100;
+    /// `mux` 测试信号
+    pub const MUX_TEST_SIGNAL: u8 = 0b101;
+    /// `gain` 增益为 1
+    pub const GAIN_1: u8 = 0b001;
+
+    /// PGA 增益倍数，`111` 为保留值
+    pub fn pga_gain(&self) -> Option<u8> {
+        match self.gain() {
+            0b000 => Some(6),
+            0b001 => Some(1),
+            0b010 => Some(2),
+            0b011 => Some(3),
+            0b100 => Some(4),
+            0b101 => Some(8),
+            0b110 => Some(12),
+            _ => None,
+        }
+    }
+}
+
+impl Config3Reg {
+    /// 基准电压 VREFP，单位 V
+    pub fn vref(&self) -> f32 {
+        if self.vref_4v() {
+            4.0
+        } else {
+            2.4
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use core::fmt;
+
+use embedded_hal::spi::SpiDevice;
+
+use super::addressable::Address;
+use super::data::*;
+use crate::driver::{ConfigError, ADS1298};
+
+/// Number of registers, `00h` ~ `19h`
+pub const REGISTER_COUNT: usize = 26;
+
+/// Writable registers before `LOFF_STATP`, `01h` ~ `11h`
+const WRITABLE_LOW: core::ops::RangeInclusive<Address> = 0x01..=0x11;
+
+/// Writable registers after `LOFF_STATN`, `14h` ~ `19h`
+const WRITABLE_HIGH: core::ops::RangeInclusive<Address> = 0x14..=0x19;
+
+macro_rules! register_map {
+    ($($field: ident: $type: ident = $name: ident @ $address: expr,)*) => {
+        /// 9.6.1 寄存器说明，全部 26 个寄存器的快照
+        #[derive(Clone, Copy, Debug)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+        pub struct RegisterMap {
+            $(pub $field: $type,)*
+        }
+
+        /// Names of the registers, indexed by address
+        pub const REGISTER_NAMES: [&str; REGISTER_COUNT] = [$(stringify!($name),)*];
+
+        impl RegisterMap {
+            /// Registers in address order
+            pub fn from_bytes(bytes: &[u8; REGISTER_COUNT]) -> Self {
+                RegisterMap {
+                    $($field: $type(bytes[$address]),)*
+                }
+            }
+
+            /// Registers in address order
+            pub fn to_bytes(&self) -> [u8; REGISTER_COUNT] {
+                let mut bytes = [0; REGISTER_COUNT];
+                $(bytes[$address] = self.$field.0;)*
+                bytes
+            }
+        }
+
+        /// Every field of `value` as the register at `address`
+        fn decode(address: Address, value: u8) -> String {
+            match usize::from(address) {
+                $($address => format!("{:?}", $type(value)),)*
+                _ => format!("{value:#04x}"),
+            }
+        }
+    };
+}
+
+register_map! {
+    id: IdReg = ID @ 0x00,
+    config1: Config1Reg = CONFIG1 @ 0x01,
+    config2: Config2Reg = CONFIG2 @ 0x02,
+    config3: Config3Reg = CONFIG3 @ 0x03,
+    loff: LOffReg = LOFF @ 0x04,
+    ch1set: ChSetReg = CH1SET @ 0x05,
+    ch2set: ChSetReg = CH2SET @ 0x06,
+    ch3set: ChSetReg = CH3SET @ 0x07,
+    ch4set: ChSetReg = CH4SET @ 0x08,
+    ch5set: ChSetReg = CH5SET @ 0x09,
+    ch6set: ChSetReg = CH6SET @ 0x0a,
+    ch7set: ChSetReg = CH7SET @ 0x0b,
+    ch8set: ChSetReg = CH8SET @ 0x0c,
+    rld_sensp: RldSensPReg = RLD_SENSP @ 0x0d,
+    rld_sensn: RldSensNReg = RLD_SENSN @ 0x0e,
+    loff_sensp: LOffSensPReg = LOFF_SENSP @ 0x0f,
+    loff_sensn: LOffSensNReg = LOFF_SENSN @ 0x10,
+    loff_flip: LoffFlipReg = LOFF_FLIP @ 0x11,
+    loff_statp: LOffStatPReg = LOFF_STATP @ 0x12,
+    loff_statn: LOffStatNReg = LOFF_STATN @ 0x13,
+    gpio: GpioReg = GPIO @ 0x14,
+    pace: PaceReg = PACE @ 0x15,
+    resp: RespReg = RESP @ 0x16,
+    config4: Config4Reg = CONFIG4 @ 0x17,
+    wct1: Wct1Reg = WCT1 @ 0x18,
+    wct2: Wct2Reg = WCT2 @ 0x19,
+}
+
+impl RegisterMap {
+    /// Registers whose value differs from `other`, in address order
+    pub fn diff(&self, other: &RegisterMap) -> Vec<RegisterChange> {
+        self.to_bytes()
+            .into_iter()
+            .zip(other.to_bytes())
+            .enumerate()
+            .filter(|(_, (from, to))| from != to)
+            .map(|(address, (from, to))| RegisterChange {
+                address: address as Address,
+                from,
+                to,
+            })
+            .collect()
+    }
+}
+
+impl PartialEq for RegisterMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_bytes() == other.to_bytes()
+    }
+}
+
+impl Eq for RegisterMap {}
+
+/// One line per register, with every field decoded
+impl fmt::Display for RegisterMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (address, value) in self.to_bytes().into_iter().enumerate() {
+            let name = REGISTER_NAMES[address];
+            let decoded = decode(address as Address, value);
+            writeln!(f, "{name:<10} {address:02X}h = {value:02X}h {decoded}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A register differing between two `RegisterMap`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RegisterChange {
+    pub address: Address,
+    pub from: u8,
+    pub to: u8,
+}
+
+impl RegisterChange {
+    pub fn name(&self) -> &'static str {
+        REGISTER_NAMES[usize::from(self.address)]
+    }
+
+    /// Bits differing between `from` and `to`
+    pub fn changed_bits(&self) -> u8 {
+        self.from ^ self.to
+    }
+}
+
+impl fmt::Display for RegisterChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {:02X}h: {:02X}h -> {:02X}h (changed bits {:08b})\n  - {}\n  + {}",
+            self.name(),
+            self.address,
+            self.from,
+            self.to,
+            self.changed_bits(),
+            decode(self.address, self.from),
+            decode(self.address, self.to),
+        )
+    }
+}
+
+impl<SPI: SpiDevice> ADS1298<SPI> {
+    /// Read all registers in one burst
+    ///
+    /// `RDATAC` mode is stopped while reading and restored afterwards.
+    pub fn read_register_map(&mut self) -> Result<RegisterMap, ConfigError<SPI::Error>> {
+        self.paused(false, |driver| {
+            let mut bytes = [0; REGISTER_COUNT];
+            driver.operator.read_registers(0x00, &mut bytes)?;
+            Ok(RegisterMap::from_bytes(&bytes))
+        })
+    }
+
+    /// Write every writable register of `map` in two bursts
+    ///
+    /// `ID`, `LOFF_STATP` and `LOFF_STATN` are read-only and skipped.
+    /// Conversion and `RDATAC` mode are stopped while writing and restored afterwards.
+    pub fn write_register_map(&mut self, map: &RegisterMap) -> Result<(), ConfigError<SPI::Error>> {
+        let bytes = map.to_bytes();
+        self.paused(true, |driver| {
+            for range in [WRITABLE_LOW, WRITABLE_HIGH] {
+                let start = *range.start();
+                let data = &bytes[usize::from(start)..=usize::from(*range.end())];
+                driver.operator.write_registers(start, data)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Write `value` to the register at `address` alone
+    ///
+    /// Conversion and `RDATAC` mode are stopped while writing and restored afterwards. `ID`,
+    /// `LOFF_STATP`, `LOFF_STATN` and addresses past `19h` are rejected.
+    pub fn write_register(
+        &mut self,
+        address: Address,
+        value: u8,
+    ) -> Result<(), ConfigError<SPI::Error>> {
+        if !WRITABLE_LOW.contains(&address) && !WRITABLE_HIGH.contains(&address) {
+            return Err(ConfigError::NotWritable(address));
+        }
+        self.paused(true, |driver| {
+            driver.operator.write_registers(address, &[value])?;
+            Ok(())
+        })
+    }
+}
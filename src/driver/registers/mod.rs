@@ -2,6 +2,7 @@
 pub mod access;
 pub mod addressable;
 pub mod data;
+pub mod map;
 
 use addressable::{Address, Addressable};
 use enum_variant_type::EnumVariantType;
@@ -23,6 +24,25 @@ pub enum DataRegister {
     DATA_CH8(u24),
 }
 
+#[cfg(feature = "defmt")]
+impl defmt::Format for DataRegister {
+    fn format(&self, f: defmt::Formatter) {
+        match self {
+            DataRegister::DATA_STATUS_1(x) => defmt::write!(f, "DATA_STATUS_1({})", x),
+            DataRegister::DATA_STATUS_2(x) => defmt::write!(f, "DATA_STATUS_2({})", x),
+            DataRegister::DATA_STATUS_3(x) => defmt::write!(f, "DATA_STATUS_3({})", x),
+            DataRegister::DATA_CH1(x) => defmt::write!(f, "DATA_CH1({=u32:#x})", u32::from(*x)),
+            DataRegister::DATA_CH2(x) => defmt::write!(f, "DATA_CH2({=u32:#x})", u32::from(*x)),
+            DataRegister::DATA_CH3(x) => defmt::write!(f, "DATA_CH3({=u32:#x})", u32::from(*x)),
+            DataRegister::DATA_CH4(x) => defmt::write!(f, "DATA_CH4({=u32:#x})", u32::from(*x)),
+            DataRegister::DATA_CH5(x) => defmt::write!(f, "DATA_CH5({=u32:#x})", u32::from(*x)),
+            DataRegister::DATA_CH6(x) => defmt::write!(f, "DATA_CH6({=u32:#x})", u32::from(*x)),
+            DataRegister::DATA_CH7(x) => defmt::write!(f, "DATA_CH7({=u32:#x})", u32::from(*x)),
+            DataRegister::DATA_CH8(x) => defmt::write!(f, "DATA_CH8({=u32:#x})", u32::from(*x)),
+        }
+    }
+}
+
 /// 9.6.1 寄存器说明
 #[derive(EnumVariantType)]
 pub enum Register {
@@ -0,0 +1,150 @@
+use embedded_hal::digital::InputPin;
+use embedded_hal::spi::SpiDevice;
+
+use super::calibration::NoiseMeasurement;
+use super::frame::{code_to_volts, CHANNELS};
+use super::measurement::MeasureError;
+use super::registers::access::{ReadFromRegister, WriteToRegister};
+use super::registers::data::{ChSetReg, IdReg};
+use super::registers::{CONFIG1, CONFIG2, CONFIG3, ID};
+use super::ADS1298;
+
+/// `ID` of an ADS1298
+const ID_ADS1298: u8 = 0x92;
+
+/// `ID` of an ADS1298R
+const ID_ADS1298R: u8 = 0xd2;
+
+/// `CONFIG2::test_freq` for fCLK / 2²⁰
+const TEST_FREQ_FAST: u8 = 0b01;
+
+/// Samples captured beyond one period of the test signal
+const TEST_SIGNAL_MARGIN: usize = 4;
+
+/// Relative error allowed on the peak-to-peak of the test signal
+pub const TEST_SIGNAL_TOLERANCE: f32 = 0.1;
+
+/// Result of `ADS1298::self_test`, `[0]` is CH1
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SelfTestReport {
+    pub id: IdReg,
+    /// `ID` is the one of an ADS1298 or ADS1298R
+    pub id_ok: bool,
+    /// Peak-to-peak of the internal test signal on each channel, in µV
+    ///
+    /// `None` for powered down channels.
+    pub test_signal_uv: [Option<f32>; CHANNELS],
+    /// Peak-to-peak of the 1× test signal at the current VREF, in µV
+    pub expected_uv: f32,
+    /// Input-short offsets and noise, for information
+    pub noise: NoiseMeasurement,
+}
+
+impl SelfTestReport {
+    /// The test signal of `channel` in `1..=8` is within `TEST_SIGNAL_TOLERANCE`
+    ///
+    /// Powered down and invalid channels pass.
+    pub fn channel_ok(&self, channel: u8) -> bool {
+        match usize::from(channel).checked_sub(1) {
+            Some(index) if index < CHANNELS => self.test_signal_uv[index].is_none_or(|uv| {
+                (uv - self.expected_uv).abs() <= self.expected_uv * TEST_SIGNAL_TOLERANCE
+            }),
+            _ => true,
+        }
+    }
+
+    /// `ID` and the test signal of every powered up channel are correct
+    pub fn is_ok(&self) -> bool {
+        self.id_ok && (1..=CHANNELS as u8).all(|channel| self.channel_ok(channel))
+    }
+}
+
+impl<SPI: SpiDevice> ADS1298<SPI> {
+    /// Check `ID`, then measure the internal test signal and the input-short noise
+    ///
+    /// Powered up channels are switched to the 1× test signal at fCLK / 2²⁰ with their gains
+    /// kept, and one period is captured. `CONFIG2` and all `CHnSET` are restored afterwards, and
+    /// conversion is restarted if it was running.
+    /// The device must be in `SDATAC` mode, `drdy` is the `DRDY#` pin.
+    pub fn self_test<DRDY: InputPin>(
+        &mut self,
+        drdy: &mut DRDY,
+        noise_samples: usize,
+    ) -> Result<SelfTestReport, MeasureError<SPI::Error>> {
+        let id = self.read(ID)?;
+        let id_ok = matches!(id.0, ID_ADS1298 | ID_ADS1298R);
+
+        let settings = self.read_channel_settings()?;
+        let mut gains = [0u8; CHANNELS];
+        for (channel, (gain, setting)) in (1..).zip(gains.iter_mut().zip(&settings)) {
+            *gain = setting
+                .pga_gain()
+                .ok_or(MeasureError::InvalidGain { channel })?;
+        }
+        let vref = self.read(CONFIG3)?.vref();
+        let config2 = self.read(CONFIG2)?;
+        let test_config2 = {
+            let mut x = config2;
+            x.set_int_test(true);
+            x.set_test_amp(false);
+            x.set_test_freq(TEST_FREQ_FAST);
+            x
+        };
+        let config1 = self.read(CONFIG1)?;
+        let data_rate = self
+            .clock
+            .data_rate_hz(config1)
+            .ok_or(MeasureError::ReservedDataRate)?;
+        let test_hz = self
+            .clock
+            .test_signal_hz(test_config2)
+            .expect("fCLK / 2²⁰ is a valid test frequency");
+        let samples = (data_rate / test_hz).ceil() as usize + TEST_SIGNAL_MARGIN;
+
+        let started = self.operator.is_started();
+        self.write(CONFIG2, test_config2)?;
+        for (channel, setting) in (1..).zip(settings) {
+            if !setting.pd() {
+                let mut test = setting;
+                test.set_mux(ChSetReg::MUX_TEST_SIGNAL);
+                self.write_channel_setting(channel, test)?;
+            }
+        }
+
+        let mut min = [i32::MAX; CHANNELS];
+        let mut max = [i32::MIN; CHANNELS];
+        let captured = self.capture(drdy, samples, |frame| {
+            for (i, &code) in frame.channels.iter().enumerate() {
+                min[i] = min[i].min(code);
+                max[i] = max[i].max(code);
+            }
+        });
+
+        for (channel, setting) in (1..).zip(settings) {
+            self.write_channel_setting(channel, setting)?;
+        }
+        self.write(CONFIG2, config2)?;
+        let noise = captured.and_then(|()| self.capture_input_short(drdy, noise_samples));
+        self.restore_conversion(started)?;
+        let noise = noise?;
+
+        let mut test_signal_uv = [None; CHANNELS];
+        for (i, uv) in test_signal_uv.iter_mut().enumerate() {
+            if !settings[i].pd() {
+                let lsb_uv = code_to_volts(1, vref, gains[i]) * 1e6;
+                *uv = Some((max[i] - min[i]) as f32 * lsb_uv);
+            }
+        }
+
+        let report = SelfTestReport {
+            id,
+            id_ok,
+            test_signal_uv,
+            expected_uv: vref / 2.4 * 2000.0,
+            noise,
+        };
+        debug!("Self-test of ADS1298: {:?}", report);
+        Ok(report)
+    }
+}
@@ -0,0 +1,44 @@
+use embedded_hal::digital::InputPin;
+use embedded_hal::spi::SpiDevice;
+
+use super::frame::Frame;
+use super::measurement::{wait_data_ready, MeasureError};
+use super::registers::access::{ReadFromRegister, WriteToRegister};
+use super::registers::data::Config4Reg;
+use super::registers::CONFIG4;
+use super::ADS1298;
+
+impl<SPI: SpiDevice> ADS1298<SPI> {
+    /// Switch between single-shot and continuous conversion mode, `CONFIG4::single_shot`
+    pub fn set_single_shot(&mut self, enabled: bool) -> Result<(), MeasureError<SPI::Error>> {
+        let mut x: Config4Reg = self.read(CONFIG4)?;
+        if x.single_shot() != enabled {
+            x.set_single_shot(enabled);
+            self.write(CONFIG4, x)?;
+        }
+        Ok(())
+    }
+
+    /// Convert a single frame in single-shot mode
+    ///
+    /// Enables single-shot mode if needed, issues `START`, waits for `DRDY` becoming `low` and
+    /// reads the result with `RDATA`. A frame left unread beforehand is read and dropped first,
+    /// as it would hold `DRDY` low. The device stays idle until the next `START`.
+    /// Offsets set by `ADS1298::calibrate_offsets` are subtracted.
+    /// The device must be in `SDATAC` mode and the `START` pin must be `low`.
+    pub fn convert_once<DRDY: InputPin>(
+        &mut self,
+        drdy: &mut DRDY,
+    ) -> Result<Frame, MeasureError<SPI::Error>> {
+        self.set_single_shot(true)?;
+        self.operator.read_single_data()?;
+        self.operator.start()?;
+        wait_data_ready(drdy).map_err(MeasureError::DataReadyError)?;
+        let raw = self.operator.read_single_data()?;
+        // `paused` must not send `START` again and trigger another conversion
+        self.operator.single_shot_done();
+        let mut frame = Frame::from_bytes(&raw);
+        frame.apply_offsets(&self.offsets);
+        Ok(frame)
+    }
+}
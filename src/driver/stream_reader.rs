@@ -1,185 +1,240 @@
-use byteorder::{BigEndian, ByteOrder};
+use core::iter::Take;
+use core::time::Duration;
+
+use embedded_hal::digital::InputPin;
 use embedded_hal::spi::SpiDevice;
-use ux::u24;
 
-use super::{
-    registers::{
-        self,
-        data::{DataStatus1, DataStatus2, DataStatus3},
-        DataRegister,
-    },
-    StreamError, ADS1298,
-};
+use super::frame::{Frame, FrameBuffers, FrameRef, FRAME_SIZE};
+use super::measurement::wait_data_ready;
+use super::timestamp::{SampleClock, Timestamper, Timestamps};
+use super::{registers, StreamError, ADS1298};
 
 /// `StreamReader` is used to continuously read data from the ADS1298 by using streaming mode.
 ///
+/// Each read busy waits for `DRDY` becoming `low`, so every frame is a new conversion.
+/// As an `Iterator` it yields `read_frame` forever, errors included.
+///
 /// todo: It uses `RDATA` command for now.
-pub struct StreamReader<'a, Spi: SpiDevice> {
+pub struct StreamReader<'a, Spi: SpiDevice, DRDY> {
     pub driver: &'a mut ADS1298<Spi>,
-    buffer: Vec<u8>,
+    /// The `DRDY` pin
+    pub drdy: DRDY,
 }
 
-struct FieldConfig {
-    register: registers::DataRegister,
-    width: usize,
+/// Produces frames, from the ADS1298 or from a recorded capture
+///
+/// As an `Iterator` it yields `read_frame` until the source is exhausted, errors included.
+pub trait FrameSource:
+    Iterator<Item = Result<Frame, <Self as FrameSource>::Error>> + Sized
+{
+    type Error;
+
+    /// The next decoded frame
+    fn read_frame(&mut self) -> Result<Frame, Self::Error>;
+
+    /// Read into `buffer` without allocating, the frame is decoded lazily from it
+    fn read_into<'b>(
+        &mut self,
+        buffer: &'b mut [u8; FRAME_SIZE],
+    ) -> Result<FrameRef<'b>, Self::Error>;
+
+    /// Output data rate, in SPS
+    fn data_rate_hz(&mut self) -> Result<f32, Self::Error>;
+
+    /// Read into the next buffer of `buffers`, same as `read_into`
+    fn read_into_ring<'b, const N: usize>(
+        &mut self,
+        buffers: &'b mut FrameBuffers<N>,
+    ) -> Result<FrameRef<'b>, Self::Error> {
+        buffers.fill(|buffer| self.read_into(buffer).map(|_| ()))
+    }
+
+    /// Yield `n` frames
+    fn samples(self, n: usize) -> Take<Self> {
+        self.take(n)
+    }
+
+    /// Yield the frames converted in `duration` at the data rate
+    fn for_duration(mut self, duration: Duration) -> Result<Take<Self>, Self::Error> {
+        let hz = self.data_rate_hz()?;
+        Ok(self.take(samples_in(duration, hz)))
+    }
+
+    /// A `Timestamper` at the data rate
+    fn timestamper<C: SampleClock>(&mut self, clock: C) -> Result<Timestamper<C>, Self::Error> {
+        Ok(Timestamper::with_clock(self.data_rate_hz()?, clock))
+    }
+
+    /// Yield frames tagged by a `Timestamper` reading `clock`, pass `NoClock` for none
+    fn timestamped<C: SampleClock>(mut self, clock: C) -> Result<Timestamps<Self, C>, Self::Error> {
+        let timestamper = self.timestamper(clock)?;
+        Ok(Timestamps::new(self, timestamper))
+    }
+
+    /// Yield frames until one reports an electrode off, see `UntilLeadOff`
+    fn until_lead_off(self) -> UntilLeadOff<Self> {
+        UntilLeadOff::new(self)
+    }
 }
 
-const LOOP_READ_BACK_CONFIG_FIELDS: &'static [FieldConfig] = &[
-    FieldConfig {
-        register: DataRegister::DATA_STATUS_1(DataStatus1(0)),
-        width: 1,
-    },
-    FieldConfig {
-        register: DataRegister::DATA_STATUS_2(DataStatus2(0)),
-        width: 1,
-    },
-    FieldConfig {
-        register: DataRegister::DATA_STATUS_3(DataStatus3(0)),
-        width: 1,
-    },
-    FieldConfig {
-        register: DataRegister::DATA_CH1(u24::new(0)),
-        width: 3,
-    },
-    FieldConfig {
-        register: DataRegister::DATA_CH2(u24::new(0)),
-        width: 3,
-    },
-    FieldConfig {
-        register: DataRegister::DATA_CH3(u24::new(0)),
-        width: 3,
-    },
-    FieldConfig {
-        register: DataRegister::DATA_CH4(u24::new(0)),
-        width: 3,
-    },
-    FieldConfig {
-        register: DataRegister::DATA_CH5(u24::new(0)),
-        width: 3,
-    },
-    FieldConfig {
-        register: DataRegister::DATA_CH6(u24::new(0)),
-        width: 3,
-    },
-    FieldConfig {
-        register: DataRegister::DATA_CH7(u24::new(0)),
-        width: 3,
-    },
-    FieldConfig {
-        register: DataRegister::DATA_CH8(u24::new(0)),
-        width: 3,
-    },
-];
-
-impl<'a, Spi: SpiDevice> StreamReader<'a, Spi> {
-    pub fn new(driver: &'a mut ADS1298<Spi>) -> Result<Self, StreamError<Spi::Error>> {
-        let buffer_len = LOOP_READ_BACK_CONFIG_FIELDS
-            .iter()
-            .map(|field| field.width)
-            .sum::<usize>();
-        let buffer: Vec<u8> = vec![0xff; buffer_len];
-        Ok(Self { driver, buffer })
-    }
-
-    /// before read, please set `START` = `high`, and wait for `DRDY` become `high`
+impl<'a, Spi: SpiDevice, DRDY: InputPin> StreamReader<'a, Spi, DRDY> {
+    pub fn new(driver: &'a mut ADS1298<Spi>, drdy: DRDY) -> Result<Self, StreamError<Spi::Error>> {
+        Ok(Self { driver, drdy })
+    }
+
+    /// before read, please set `START` = `high`
     pub fn read(&mut self) -> Result<Vec<registers::DataRegister>, StreamError<Spi::Error>> {
-        let buffer = self
-            .driver
-            .operator
-            .read_single_data()
-            .map_err(StreamError::StreamingAbort)?;
-
-        let mut i = buffer.iter();
-        let mut r = vec![];
-        r.push(DataRegister::DATA_STATUS_1(DataStatus1(*i.next().unwrap())));
-        r.push(DataRegister::DATA_STATUS_2(DataStatus2(*i.next().unwrap())));
-        r.push(DataRegister::DATA_STATUS_3(DataStatus3(*i.next().unwrap())));
-        r.push(DataRegister::DATA_CH1(u24::new(BigEndian::read_u24(&[
-            *i.next().unwrap(),
-            *i.next().unwrap(),
-            *i.next().unwrap(),
-        ]))));
-        r.push(DataRegister::DATA_CH2(u24::new(BigEndian::read_u24(&[
-            *i.next().unwrap(),
-            *i.next().unwrap(),
-            *i.next().unwrap(),
-        ]))));
-        r.push(DataRegister::DATA_CH3(u24::new(BigEndian::read_u24(&[
-            *i.next().unwrap(),
-            *i.next().unwrap(),
-            *i.next().unwrap(),
-        ]))));
-        r.push(DataRegister::DATA_CH4(u24::new(BigEndian::read_u24(&[
-            *i.next().unwrap(),
-            *i.next().unwrap(),
-            *i.next().unwrap(),
-        ]))));
-        r.push(DataRegister::DATA_CH5(u24::new(BigEndian::read_u24(&[
-            *i.next().unwrap(),
-            *i.next().unwrap(),
-            *i.next().unwrap(),
-        ]))));
-        r.push(DataRegister::DATA_CH6(u24::new(BigEndian::read_u24(&[
-            *i.next().unwrap(),
-            *i.next().unwrap(),
-            *i.next().unwrap(),
-        ]))));
-        r.push(DataRegister::DATA_CH7(u24::new(BigEndian::read_u24(&[
-            *i.next().unwrap(),
-            *i.next().unwrap(),
-            *i.next().unwrap(),
-        ]))));
-        r.push(DataRegister::DATA_CH8(u24::new(BigEndian::read_u24(&[
-            *i.next().unwrap(),
-            *i.next().unwrap(),
-            *i.next().unwrap(),
-        ]))));
-
-        // let mut cursor = (0, 0);
-
-        // let result: Vec<DataRegister> = LOOP_READ_BACK_CONFIG_FIELDS
-        //     .iter()
-        //     .map(|enabled_field| {
-        //         let &FieldConfig { register, width } = enabled_field;
-
-        //         let mut register = register.clone();
-        //         cursor = (cursor.1, cursor.1 + width);
-
-        //         macro_rules! ecg {
-        //             ($data: ident, $raw: ident) => {{
-        //                 debug_assert!($raw.len() == 3);
-        //                 *$data = u24::new(BigEndian::read_u24(&$raw))
-        //             }};
-        //         }
-
-        //         let raw = &buffer[cursor.0..cursor.1];
-        //         match &mut register {
-        //             DataRegister::DATA_STATUS_1(data) => {
-        //                 debug_assert!(raw.len() == 1);
-        //                 *data = DataStatus1(raw[0]);
-        //             }
-        //             DataRegister::DATA_STATUS_2(data) => {
-        //                 debug_assert!(raw.len() == 1);
-        //                 *data = DataStatus2(raw[0]);
-        //             }
-        //             DataRegister::DATA_STATUS_3(data) => {
-        //                 debug_assert!(raw.len() == 1);
-        //                 *data = DataStatus3(raw[0]);
-        //             }
-        //             DataRegister::DATA_CH1(data) => ecg!(data, raw),
-        //             DataRegister::DATA_CH2(data) => ecg!(data, raw),
-        //             DataRegister::DATA_CH3(data) => ecg!(data, raw),
-        //             DataRegister::DATA_CH4(data) => ecg!(data, raw),
-        //             DataRegister::DATA_CH5(data) => ecg!(data, raw),
-        //             DataRegister::DATA_CH6(data) => ecg!(data, raw),
-        //             DataRegister::DATA_CH7(data) => ecg!(data, raw),
-        //             DataRegister::DATA_CH8(data) => ecg!(data, raw),
-        //         }
-        //         register
-        //     })
-        //     .collect::<Vec<_>>();
-
-        // Ok(result)
-        Ok(r)
+        Ok(self.read_frame()?.to_registers())
+    }
+}
+
+// The `FrameSource` methods stay callable without importing the trait
+impl<Spi: SpiDevice, DRDY: InputPin> StreamReader<'_, Spi, DRDY> {
+    /// Same as `read`, but returns the decoded frame
+    ///
+    /// Waits for `DRDY` becoming `low` first.
+    /// Offsets set by `ADS1298::calibrate_offsets` are subtracted.
+    pub fn read_frame(&mut self) -> Result<Frame, StreamError<Spi::Error>> {
+        FrameSource::read_frame(self)
+    }
+
+    /// Wait for `DRDY` and read into `buffer` without allocating, the frame is decoded lazily
+    /// from it
+    ///
+    /// Offsets set by `ADS1298::calibrate_offsets` are not applied.
+    pub fn read_into<'b>(
+        &mut self,
+        buffer: &'b mut [u8; FRAME_SIZE],
+    ) -> Result<FrameRef<'b>, StreamError<Spi::Error>> {
+        FrameSource::read_into(self, buffer)
+    }
+
+    /// Read into the next buffer of `buffers`, same as `read_into`
+    pub fn read_into_ring<'b, const N: usize>(
+        &mut self,
+        buffers: &'b mut FrameBuffers<N>,
+    ) -> Result<FrameRef<'b>, StreamError<Spi::Error>> {
+        FrameSource::read_into_ring(self, buffers)
+    }
+
+    /// Yield `n` frames
+    pub fn samples(self, n: usize) -> Take<Self> {
+        FrameSource::samples(self, n)
+    }
+
+    /// Yield the frames converted in `duration` at the data rate of the current `CONFIG1`
+    pub fn for_duration(self, duration: Duration) -> Result<Take<Self>, StreamError<Spi::Error>> {
+        FrameSource::for_duration(self, duration)
+    }
+
+    /// A `Timestamper` at the data rate of the current `CONFIG1`
+    pub fn timestamper<C: SampleClock>(
+        &mut self,
+        clock: C,
+    ) -> Result<Timestamper<C>, StreamError<Spi::Error>> {
+        FrameSource::timestamper(self, clock)
+    }
+
+    /// Yield frames tagged by a `Timestamper` reading `clock`, pass `NoClock` for none
+    pub fn timestamped<C: SampleClock>(
+        self,
+        clock: C,
+    ) -> Result<Timestamps<Self, C>, StreamError<Spi::Error>> {
+        FrameSource::timestamped(self, clock)
+    }
+
+    /// Yield frames until one reports an electrode off, see `UntilLeadOff`
+    pub fn until_lead_off(self) -> UntilLeadOff<Self> {
+        FrameSource::until_lead_off(self)
+    }
+}
+
+impl<Spi: SpiDevice, DRDY: InputPin> FrameSource for StreamReader<'_, Spi, DRDY> {
+    type Error = StreamError<Spi::Error>;
+
+    /// Offsets set by `ADS1298::calibrate_offsets` are subtracted.
+    fn read_frame(&mut self) -> Result<Frame, StreamError<Spi::Error>> {
+        let mut buffer = [0; FRAME_SIZE];
+        let mut frame = self.read_into(&mut buffer)?.to_frame();
+        frame.apply_offsets(&self.driver.offsets());
+        Ok(frame)
+    }
+
+    /// Offsets set by `ADS1298::calibrate_offsets` are not applied.
+    fn read_into<'b>(
+        &mut self,
+        buffer: &'b mut [u8; FRAME_SIZE],
+    ) -> Result<FrameRef<'b>, StreamError<Spi::Error>> {
+        wait_data_ready(&mut self.drdy).map_err(StreamError::DataReadyError)?;
+        self.driver.operator.read_data_into(buffer).map_err(|e| {
+            error!("Streaming from ADS1298 aborted");
+            StreamError::StreamingAbort(e)
+        })?;
+        Ok(FrameRef::new(buffer))
+    }
+
+    /// Output data rate of the current `CONFIG1`, in SPS
+    fn data_rate_hz(&mut self) -> Result<f32, StreamError<Spi::Error>> {
+        self.driver
+            .data_rate_hz()
+            .map_err(StreamError::ReadConfigError)?
+            .ok_or(StreamError::ReservedDataRate)
+    }
+}
+
+impl<Spi: SpiDevice, DRDY: InputPin> Iterator for StreamReader<'_, Spi, DRDY> {
+    type Item = Result<Frame, StreamError<Spi::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.read_frame())
+    }
+}
+
+/// Number of frames converted in `duration` at `hz` SPS
+pub(crate) fn samples_in(duration: Duration, hz: f32) -> usize {
+    (duration.as_secs_f64() * f64::from(hz)).round() as usize
+}
+
+/// Yields frames until one reports an electrode off in its status word
+///
+/// The frame reporting the lead-off is not yielded, it is kept in `lead_off`. Errors are
+/// yielded and do not end the iteration.
+pub struct UntilLeadOff<I> {
+    frames: I,
+    lead_off: Option<Frame>,
+}
+
+impl<I> UntilLeadOff<I> {
+    pub fn new(frames: I) -> Self {
+        UntilLeadOff {
+            frames,
+            lead_off: None,
+        }
+    }
+
+    /// The frame which ended the iteration
+    pub fn lead_off(&self) -> Option<&Frame> {
+        self.lead_off.as_ref()
+    }
+
+    pub fn into_inner(self) -> I {
+        self.frames
+    }
+}
+
+impl<I: Iterator<Item = Result<Frame, E>>, E> Iterator for UntilLeadOff<I> {
+    type Item = Result<Frame, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.lead_off.is_some() {
+            return None;
+        }
+        match self.frames.next()? {
+            Ok(frame) if frame.status.is_lead_off() => {
+                self.lead_off = Some(frame);
+                None
+            }
+            item => Some(item),
+        }
     }
 }
@@ -0,0 +1,155 @@
+use core::ops::RangeInclusive;
+
+use embedded_hal::digital::InputPin;
+use embedded_hal::spi::SpiDevice;
+
+use super::frame::code_to_volts;
+use super::measurement::MeasureError;
+use super::registers::access::ReadFromRegister;
+use super::registers::data::ChSetReg;
+use super::registers::CONFIG3;
+use super::ADS1298;
+
+/// Channels whose MVDD input is `0.5 × (AVDD + AVSS)`
+const AVDD_CHANNELS: [u8; 6] = [1, 2, 5, 6, 7, 8];
+
+/// Channels whose MVDD input is `DVDD / 4`
+const DVDD_CHANNELS: [u8; 2] = [3, 4];
+
+/// Settings of `ADS1298::measure_supplies`
+///
+/// The default uses CH1 for AVDD, CH3 for DVDD and the recommended operating ranges of the
+/// datasheet for a unipolar analog supply.
+#[derive(Clone, Debug)]
+pub struct SupplyMonitor {
+    /// Channel measuring AVDD, one of CH1, CH2, CH5 ~ CH8
+    pub avdd_channel: u8,
+    /// Channel measuring DVDD, CH3 or CH4
+    pub dvdd_channel: u8,
+    /// AVSS, in V
+    pub avss: f32,
+    /// Accepted AVDD, in V
+    pub avdd: RangeInclusive<f32>,
+    /// Accepted DVDD, in V
+    pub dvdd: RangeInclusive<f32>,
+    /// Number of samples averaged
+    pub samples: usize,
+}
+
+impl Default for SupplyMonitor {
+    fn default() -> Self {
+        SupplyMonitor {
+            avdd_channel: 1,
+            dvdd_channel: 3,
+            avss: 0.0,
+            avdd: 2.7..=5.25,
+            dvdd: 1.65..=3.6,
+            samples: 4,
+        }
+    }
+}
+
+impl SupplyMonitor {
+    /// Accept AVDD and DVDD within `tolerance` (e.g. `0.05` for ±5%) of their nominal values
+    pub fn with_nominal(avdd: f32, dvdd: f32, tolerance: f32) -> Self {
+        SupplyMonitor {
+            avdd: avdd * (1.0 - tolerance)..=avdd * (1.0 + tolerance),
+            dvdd: dvdd * (1.0 - tolerance)..=dvdd * (1.0 + tolerance),
+            ..Default::default()
+        }
+    }
+}
+
+/// Measured supplies, in V
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SupplyReport {
+    pub avdd: f32,
+    pub dvdd: f32,
+    pub avdd_in_range: bool,
+    pub dvdd_in_range: bool,
+}
+
+impl SupplyReport {
+    pub fn is_ok(&self) -> bool {
+        self.avdd_in_range && self.dvdd_in_range
+    }
+}
+
+impl<SPI: SpiDevice> ADS1298<SPI> {
+    /// Measure AVDD and DVDD through the MVDD input of two channels
+    ///
+    /// Both channels are switched to MVDD with gain `1` to avoid saturating the PGA, their
+    /// previous `CHnSET` are restored afterwards, and conversion is restarted if it was running.
+    /// With VREF = 2.4 V AVDD is limited to 4.8 V. Supplies out of range are logged as warnings.
+    /// The device must be in `SDATAC` mode, `drdy` is the `DRDY#` pin.
+    pub fn measure_supplies<DRDY: InputPin>(
+        &mut self,
+        drdy: &mut DRDY,
+        monitor: &SupplyMonitor,
+    ) -> Result<SupplyReport, MeasureError<SPI::Error>> {
+        if !AVDD_CHANNELS.contains(&monitor.avdd_channel) {
+            return Err(MeasureError::InvalidChannel(monitor.avdd_channel));
+        }
+        if !DVDD_CHANNELS.contains(&monitor.dvdd_channel) {
+            return Err(MeasureError::InvalidChannel(monitor.dvdd_channel));
+        }
+        let channels = [monitor.avdd_channel, monitor.dvdd_channel];
+        let settings = [
+            self.read_channel_setting(channels[0])?,
+            self.read_channel_setting(channels[1])?,
+        ];
+        let vref = self.read(CONFIG3)?.vref();
+        let started = self.operator.is_started();
+        for channel in channels {
+            self.write_channel_setting(channel, {
+                let mut x = ChSetReg(0);
+                x.set_mux(ChSetReg::MUX_MVDD);
+                x.set_gain(ChSetReg::GAIN_1);
+                x
+            })?;
+        }
+
+        let index = channels.map(|channel| usize::from(channel - 1));
+        let mut sum = [0i64; 2];
+        let captured = self.capture(drdy, monitor.samples, |frame| {
+            for (sum, index) in sum.iter_mut().zip(index) {
+                *sum += i64::from(frame.channels[index]);
+            }
+        });
+
+        for (channel, setting) in channels.into_iter().zip(settings) {
+            self.write_channel_setting(channel, setting)?;
+        }
+        self.restore_conversion(started)?;
+        captured?;
+
+        let volts =
+            sum.map(|sum| code_to_volts(1, vref, 1) * (sum as f64 / monitor.samples as f64) as f32);
+        let avdd = 2.0 * volts[0] - monitor.avss;
+        let dvdd = 4.0 * volts[1];
+        let report = SupplyReport {
+            avdd,
+            dvdd,
+            avdd_in_range: monitor.avdd.contains(&avdd),
+            dvdd_in_range: monitor.dvdd.contains(&dvdd),
+        };
+        if !report.avdd_in_range {
+            warn!(
+                "AVDD of ADS1298 out of range: {} V, expected {} ~ {} V",
+                avdd,
+                monitor.avdd.start(),
+                monitor.avdd.end()
+            );
+        }
+        if !report.dvdd_in_range {
+            warn!(
+                "DVDD of ADS1298 out of range: {} V, expected {} ~ {} V",
+                dvdd,
+                monitor.dvdd.start(),
+                monitor.dvdd.end()
+            );
+        }
+        Ok(report)
+    }
+}
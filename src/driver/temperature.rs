@@ -0,0 +1,62 @@
+use embedded_hal::digital::InputPin;
+use embedded_hal::spi::SpiDevice;
+
+use super::frame::code_to_volts;
+use super::measurement::MeasureError;
+use super::registers::access::ReadFromRegister;
+use super::registers::data::ChSetReg;
+use super::registers::CONFIG3;
+use super::ADS1298;
+
+/// Sensor output at 25 °C, in µV
+const TEMPERATURE_OFFSET_UV: f32 = 145_300.0;
+
+/// Sensor coefficient, in µV/°C
+const TEMPERATURE_COEFFICIENT_UV: f32 = 490.0;
+
+/// 温度传感器读数转换
+///
+/// 温度 (°C) = (温度读数 (µV) – 145,300µV) / (490µV/°C) + 25°C
+pub fn temperature_from_microvolts(uv: f32) -> f32 {
+    (uv - TEMPERATURE_OFFSET_UV) / TEMPERATURE_COEFFICIENT_UV + 25.0
+}
+
+impl<SPI: SpiDevice> ADS1298<SPI> {
+    /// Read the on-chip temperature sensor through `channel`, in °C
+    ///
+    /// The channel is switched to the temperature sensor with gain `1` and `samples` samples are
+    /// averaged. Its previous `CHnSET` is restored afterwards, and conversion is restarted if it
+    /// was running.
+    /// The device must be in `SDATAC` mode, `drdy` is the `DRDY#` pin.
+    pub fn read_temperature<DRDY: InputPin>(
+        &mut self,
+        drdy: &mut DRDY,
+        channel: u8,
+        samples: usize,
+    ) -> Result<f32, MeasureError<SPI::Error>> {
+        let setting = self.read_channel_setting(channel)?;
+        let vref = self.read(CONFIG3)?.vref();
+        let started = self.operator.is_started();
+        self.write_channel_setting(channel, {
+            let mut x = ChSetReg(0);
+            x.set_mux(ChSetReg::MUX_TEMPERATURE);
+            x.set_gain(ChSetReg::GAIN_1);
+            x
+        })?;
+
+        let index = usize::from(channel - 1);
+        let mut sum = 0i64;
+        let captured = self.capture(drdy, samples, |frame| {
+            sum += i64::from(frame.channels[index]);
+        });
+
+        self.write_channel_setting(channel, setting)?;
+        self.restore_conversion(started)?;
+        captured?;
+
+        let uv = code_to_volts(1, vref, 1) * 1e6 * (sum as f64 / samples as f64) as f32;
+        let temperature = temperature_from_microvolts(uv);
+        debug!("Temperature of ADS1298: {} °C ({} µV)", temperature, uv);
+        Ok(temperature)
+    }
+}
@@ -0,0 +1,187 @@
+//! Sample indexes and dropped-sample detection
+//!
+//! Each frame is tagged with the index of its conversion, counted from the first frame at the
+//! configured data rate. Missed `DRDY` periods are detected from a caller-supplied clock or
+//! `DRDY` edge count and skip the index forward, so gaps are reported instead of hidden.
+
+use core::time::Duration;
+
+use super::frame::Frame;
+
+/// Source of the time a frame is read at, in µs
+///
+/// Implemented for `FnMut() -> u64` closures, such as a free-running timer read.
+pub trait SampleClock {
+    fn now_us(&mut self) -> Option<u64>;
+}
+
+impl<F: FnMut() -> u64> SampleClock for F {
+    fn now_us(&mut self) -> Option<u64> {
+        Some(self())
+    }
+}
+
+/// No clock, gaps are only detected from `DRDY` edge counts
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoClock;
+
+impl SampleClock for NoClock {
+    fn now_us(&mut self) -> Option<u64> {
+        None
+    }
+}
+
+/// A frame with its place in the sample sequence
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TimestampedFrame {
+    pub frame: Frame,
+    /// Index of the conversion, `0` is the first frame tagged
+    pub index: u64,
+    /// Time of the conversion since the first frame, `index` periods of the data rate
+    pub elapsed: Duration,
+    /// Time of the read from the `SampleClock`, in µs
+    pub clock_us: Option<u64>,
+    /// Conversions missed between the previous frame and this one
+    pub missed: u64,
+}
+
+impl TimestampedFrame {
+    /// Whether conversions were missed before this frame
+    pub fn is_after_gap(&self) -> bool {
+        self.missed > 0
+    }
+}
+
+/// Tags frames with sample indexes and detects missed `DRDY` periods
+pub struct Timestamper<C = NoClock> {
+    data_rate_hz: f32,
+    clock: C,
+    /// Index of the next frame, `None` before the first frame
+    next: Option<u64>,
+    last_clock_us: Option<u64>,
+    last_edges: Option<u64>,
+    missed: u64,
+}
+
+impl Timestamper {
+    /// `data_rate_hz` is the output data rate, see `ADS1298::data_rate_hz`
+    pub fn new(data_rate_hz: f32) -> Self {
+        Self::with_clock(data_rate_hz, NoClock)
+    }
+}
+
+impl<C: SampleClock> Timestamper<C> {
+    /// Read `clock` for every frame, a frame later than half a period after the expected time
+    /// counts the periods in between as missed
+    pub fn with_clock(data_rate_hz: f32, clock: C) -> Self {
+        Timestamper {
+            data_rate_hz,
+            clock,
+            next: None,
+            last_clock_us: None,
+            last_edges: None,
+            missed: 0,
+        }
+    }
+
+    pub fn data_rate_hz(&self) -> f32 {
+        self.data_rate_hz
+    }
+
+    /// Conversions missed since the first frame
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+
+    /// Time of the conversion at `index` since the first frame
+    pub fn elapsed(&self, index: u64) -> Duration {
+        Duration::from_secs_f64(index as f64 / f64::from(self.data_rate_hz))
+    }
+
+    /// Tag `frame`, detecting gaps from the clock only
+    pub fn tag(&mut self, frame: Frame) -> TimestampedFrame {
+        let clock_us = self.clock.now_us();
+        let missed = self.missed_by_clock(clock_us);
+        self.advance(frame, clock_us, missed)
+    }
+
+    /// Tag `frame`, `drdy_edges` is the running count of `DRDY` falling edges, for instance
+    /// counted in the `DRDY` interrupt
+    ///
+    /// Edges between two frames beyond the first one count as missed conversions. The clock is
+    /// still read but only used for `clock_us`.
+    pub fn tag_counted(&mut self, frame: Frame, drdy_edges: u64) -> TimestampedFrame {
+        let clock_us = self.clock.now_us();
+        let missed = match self.last_edges {
+            Some(last) => drdy_edges.wrapping_sub(last).saturating_sub(1),
+            None => 0,
+        };
+        self.last_edges = Some(drdy_edges);
+        self.advance(frame, clock_us, missed)
+    }
+
+    /// Start again from index `0`, for instance after restarting conversions
+    pub fn restart(&mut self) {
+        self.next = None;
+        self.last_clock_us = None;
+        self.last_edges = None;
+        self.missed = 0;
+    }
+
+    fn missed_by_clock(&self, clock_us: Option<u64>) -> u64 {
+        let (Some(now), Some(last)) = (clock_us, self.last_clock_us) else {
+            return 0;
+        };
+        let periods = now.saturating_sub(last) as f64 * f64::from(self.data_rate_hz) / 1e6;
+        (periods.round() as u64).saturating_sub(1)
+    }
+
+    fn advance(&mut self, frame: Frame, clock_us: Option<u64>, missed: u64) -> TimestampedFrame {
+        let index = match self.next {
+            Some(next) => next + missed,
+            None => 0,
+        };
+        if missed > 0 {
+            warn!("Missed {} conversions before sample {}", missed, index);
+        }
+        self.next = Some(index + 1);
+        self.last_clock_us = clock_us.or(self.last_clock_us);
+        self.missed += missed;
+        TimestampedFrame {
+            frame,
+            index,
+            elapsed: self.elapsed(index),
+            clock_us,
+            missed,
+        }
+    }
+}
+
+/// Tags the frames of an iterator, see `StreamReader::timestamped`
+pub struct Timestamps<I, C = NoClock> {
+    frames: I,
+    timestamper: Timestamper<C>,
+}
+
+impl<I, C> Timestamps<I, C> {
+    pub fn new(frames: I, timestamper: Timestamper<C>) -> Self {
+        Timestamps {
+            frames,
+            timestamper,
+        }
+    }
+
+    pub fn timestamper(&self) -> &Timestamper<C> {
+        &self.timestamper
+    }
+}
+
+impl<I: Iterator<Item = Result<Frame, E>>, C: SampleClock, E> Iterator for Timestamps<I, C> {
+    type Item = Result<TimestampedFrame, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let frame = self.frames.next()?;
+        Some(frame.map(|frame| self.timestamper.tag(frame)))
+    }
+}
@@ -0,0 +1,294 @@
+//! Second-order IIR sections and cascades of them
+//!
+//! Designs follow the Audio EQ Cookbook by R. Bristow-Johnson. Cutoffs far below the data
+//! rate lose precision, such as 0.05 Hz above 4 kSPS, decimate before filtering instead.
+
+use core::f64::consts::PI;
+
+use super::{check_frequency, Filter, FilterError};
+
+/// Fraction bits of the `FixedBiquad` coefficients, Q2.30
+pub const FIXED_FRACTION_BITS: u32 = 30;
+
+/// Q of a second-order Butterworth section
+pub const BUTTERWORTH_Q: f32 = core::f32::consts::FRAC_1_SQRT_2;
+
+/// `H(z) = (b0 + b1 z⁻¹ + b2 z⁻²) / (1 + a1 z⁻¹ + a2 z⁻²)`
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BiquadCoefficients {
+    pub b0: f32,
+    pub b1: f32,
+    pub b2: f32,
+    pub a1: f32,
+    pub a2: f32,
+}
+
+impl BiquadCoefficients {
+    /// Passes everything unchanged
+    pub const IDENTITY: BiquadCoefficients = BiquadCoefficients {
+        b0: 1.0,
+        b1: 0.0,
+        b2: 0.0,
+        a1: 0.0,
+        a2: 0.0,
+    };
+
+    /// Second-order low-pass at `hz`, `BUTTERWORTH_Q` for a Butterworth response
+    pub fn low_pass(data_rate_hz: f32, hz: f32, q: f32) -> Result<Self, FilterError> {
+        let (cos, alpha) = prewarp(data_rate_hz, hz, q)?;
+        let b = (1.0 - cos) / 2.0;
+        Ok(Self::normalized([b, 2.0 * b, b], alpha, cos))
+    }
+
+    /// Second-order high-pass at `hz`, `BUTTERWORTH_Q` for a Butterworth response
+    pub fn high_pass(data_rate_hz: f32, hz: f32, q: f32) -> Result<Self, FilterError> {
+        let (cos, alpha) = prewarp(data_rate_hz, hz, q)?;
+        let b = (1.0 + cos) / 2.0;
+        Ok(Self::normalized([b, -2.0 * b, b], alpha, cos))
+    }
+
+    /// Notch at `hz`, the -3 dB bandwidth is `hz / q`
+    pub fn notch(data_rate_hz: f32, hz: f32, q: f32) -> Result<Self, FilterError> {
+        let (cos, alpha) = prewarp(data_rate_hz, hz, q)?;
+        Ok(Self::normalized([1.0, -2.0 * cos, 1.0], alpha, cos))
+    }
+
+    fn normalized(b: [f64; 3], alpha: f64, cos: f64) -> Self {
+        let a0 = 1.0 + alpha;
+        BiquadCoefficients {
+            b0: (b[0] / a0) as f32,
+            b1: (b[1] / a0) as f32,
+            b2: (b[2] / a0) as f32,
+            a1: (-2.0 * cos / a0) as f32,
+            a2: ((1.0 - alpha) / a0) as f32,
+        }
+    }
+
+    /// Magnitude of the response at `hz`
+    pub fn gain(&self, data_rate_hz: f32, hz: f32) -> f32 {
+        let w = 2.0 * PI * f64::from(hz) / f64::from(data_rate_hz);
+        let response = |c: [f32; 3]| {
+            let [c0, c1, c2] = c.map(f64::from);
+            let re = c0 + c1 * w.cos() + c2 * (2.0 * w).cos();
+            let im = -c1 * w.sin() - c2 * (2.0 * w).sin();
+            re.hypot(im)
+        };
+        let numerator = response([self.b0, self.b1, self.b2]);
+        let denominator = response([1.0, self.a1, self.a2]);
+        (numerator / denominator) as f32
+    }
+}
+
+/// `cos(w0)` and `alpha` of the cookbook
+fn prewarp(data_rate_hz: f32, hz: f32, q: f32) -> Result<(f64, f64), FilterError> {
+    check_frequency(hz, data_rate_hz)?;
+    let w0 = 2.0 * PI * f64::from(hz) / f64::from(data_rate_hz);
+    Ok((w0.cos(), w0.sin() / (2.0 * f64::from(q))))
+}
+
+/// `f32` section in transposed direct form II
+#[derive(Clone, Copy, Debug)]
+pub struct Biquad {
+    coefficients: BiquadCoefficients,
+    s1: f32,
+    s2: f32,
+}
+
+impl Biquad {
+    pub const fn new(coefficients: BiquadCoefficients) -> Self {
+        Biquad {
+            coefficients,
+            s1: 0.0,
+            s2: 0.0,
+        }
+    }
+
+    pub fn coefficients(&self) -> &BiquadCoefficients {
+        &self.coefficients
+    }
+
+    /// The same section on 24-bit codes
+    pub fn to_fixed(&self) -> FixedBiquad {
+        FixedBiquad::new(&self.coefficients)
+    }
+}
+
+impl Default for Biquad {
+    fn default() -> Self {
+        Biquad::new(BiquadCoefficients::IDENTITY)
+    }
+}
+
+impl Filter for Biquad {
+    type Sample = f32;
+
+    fn process(&mut self, x: f32) -> f32 {
+        let c = &self.coefficients;
+        let y = c.b0 * x + self.s1;
+        self.s1 = c.b1 * x - c.a1 * y + self.s2;
+        self.s2 = c.b2 * x - c.a2 * y;
+        y
+    }
+
+    fn reset(&mut self) {
+        self.s1 = 0.0;
+        self.s2 = 0.0;
+    }
+}
+
+/// Fixed-point section on 24-bit codes in direct form I
+///
+/// Coefficients are Q2.30, products are accumulated in `i64` and the rounding error is fed
+/// back into the next sample, which keeps low cutoffs free of limit cycles.
+#[derive(Clone, Copy, Debug)]
+pub struct FixedBiquad {
+    /// `b0`, `b1`, `b2`, `a1`, `a2`
+    coefficients: [i32; 5],
+    x: [i32; 2],
+    y: [i32; 2],
+    error: i64,
+}
+
+impl FixedBiquad {
+    /// Coefficients are rounded to Q2.30 and saturated to `[-2, 2)`
+    pub fn new(coefficients: &BiquadCoefficients) -> Self {
+        let c = coefficients;
+        FixedBiquad {
+            coefficients: [c.b0, c.b1, c.b2, c.a1, c.a2].map(to_q30),
+            x: [0; 2],
+            y: [0; 2],
+            error: 0,
+        }
+    }
+}
+
+impl Default for FixedBiquad {
+    fn default() -> Self {
+        FixedBiquad::new(&BiquadCoefficients::IDENTITY)
+    }
+}
+
+impl Filter for FixedBiquad {
+    type Sample = i32;
+
+    fn process(&mut self, x: i32) -> i32 {
+        let [b0, b1, b2, a1, a2] = self.coefficients.map(i64::from);
+        let acc = b0 * i64::from(x) + b1 * i64::from(self.x[0]) + b2 * i64::from(self.x[1])
+            - a1 * i64::from(self.y[0])
+            - a2 * i64::from(self.y[1])
+            + self.error;
+        let y = acc >> FIXED_FRACTION_BITS;
+        self.error = acc - (y << FIXED_FRACTION_BITS);
+        let y = y.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32;
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+
+    fn reset(&mut self) {
+        self.x = [0; 2];
+        self.y = [0; 2];
+        self.error = 0;
+    }
+}
+
+fn to_q30(c: f32) -> i32 {
+    let scaled = (f64::from(c) * f64::from(1u32 << FIXED_FRACTION_BITS)).round();
+    scaled.clamp(f64::from(i32::MIN), f64::from(i32::MAX)) as i32
+}
+
+/// Up to `N` `f32` sections applied in order
+#[derive(Clone, Copy, Debug)]
+pub struct Cascade<const N: usize> {
+    sections: [Biquad; N],
+    len: usize,
+}
+
+impl<const N: usize> Cascade<N> {
+    /// No sections, passes everything unchanged
+    pub fn new() -> Self {
+        Cascade {
+            sections: [Biquad::default(); N],
+            len: 0,
+        }
+    }
+
+    /// Append a section after the existing ones
+    pub fn push(&mut self, coefficients: BiquadCoefficients) -> Result<(), FilterError> {
+        let section = self
+            .sections
+            .get_mut(self.len)
+            .ok_or(FilterError::TooManySections(self.len + 1))?;
+        *section = Biquad::new(coefficients);
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn sections(&self) -> &[Biquad] {
+        &self.sections[..self.len]
+    }
+
+    /// Magnitude of the response at `hz`
+    pub fn gain(&self, data_rate_hz: f32, hz: f32) -> f32 {
+        self.sections()
+            .iter()
+            .map(|s| s.coefficients.gain(data_rate_hz, hz))
+            .product()
+    }
+
+    /// The same cascade on 24-bit codes
+    pub fn to_fixed(&self) -> FixedCascade<N> {
+        FixedCascade {
+            sections: self.sections.map(|s| s.to_fixed()),
+            len: self.len,
+        }
+    }
+}
+
+impl<const N: usize> Default for Cascade<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Filter for Cascade<N> {
+    type Sample = f32;
+
+    fn process(&mut self, x: f32) -> f32 {
+        self.sections[..self.len]
+            .iter_mut()
+            .fold(x, |x, section| section.process(x))
+    }
+
+    fn reset(&mut self) {
+        self.sections.iter_mut().for_each(Filter::reset);
+    }
+}
+
+/// Up to `N` fixed-point sections applied in order, see `Cascade::to_fixed`
+#[derive(Clone, Copy, Debug)]
+pub struct FixedCascade<const N: usize> {
+    sections: [FixedBiquad; N],
+    len: usize,
+}
+
+impl<const N: usize> FixedCascade<N> {
+    pub fn sections(&self) -> &[FixedBiquad] {
+        &self.sections[..self.len]
+    }
+}
+
+impl<const N: usize> Filter for FixedCascade<N> {
+    type Sample = i32;
+
+    fn process(&mut self, x: i32) -> i32 {
+        self.sections[..self.len]
+            .iter_mut()
+            .fold(x, |x, section| section.process(x))
+    }
+
+    fn reset(&mut self) {
+        self.sections.iter_mut().for_each(Filter::reset);
+    }
+}
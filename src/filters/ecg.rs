@@ -0,0 +1,150 @@
+//! Baseline wander, powerline and low-pass filtering of ECG channels
+
+use super::biquad::{BiquadCoefficients, Cascade, FixedCascade, BUTTERWORTH_Q};
+use super::FilterError;
+
+/// Powerline harmonics notched at most, the fundamental included
+pub const MAX_HARMONICS: usize = 3;
+
+/// Sections of an `EcgFilterConfig` cascade: high-pass, notches and a 4th-order low-pass
+pub const MAX_SECTIONS: usize = 1 + MAX_HARMONICS + 2;
+
+/// Q of the powerline notches, about 2 Hz wide at 50 Hz
+const NOTCH_Q: f32 = 25.0;
+
+/// Q of the two sections of a 4th-order Butterworth low-pass
+const BUTTERWORTH_4_Q: [f32; 2] = [0.541_196_1, 1.306_563];
+
+/// Baseline wander removal, 2nd-order Butterworth
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HighPass {
+    /// 0.05 Hz, keeps the ST segment for diagnostic bandwidth
+    Diagnostic,
+    /// 0.5 Hz, for monitoring
+    Monitoring,
+}
+
+impl HighPass {
+    pub fn hz(self) -> f32 {
+        match self {
+            HighPass::Diagnostic => 0.05,
+            HighPass::Monitoring => 0.5,
+        }
+    }
+}
+
+/// Powerline frequency
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mains {
+    Hz50,
+    Hz60,
+}
+
+impl Mains {
+    pub fn hz(self) -> f32 {
+        match self {
+            Mains::Hz50 => 50.0,
+            Mains::Hz60 => 60.0,
+        }
+    }
+}
+
+/// Anti-aliasing and noise low-pass, 4th-order Butterworth
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LowPass {
+    /// 40 Hz, for monitoring
+    Monitoring,
+    /// 150 Hz, for diagnostic bandwidth
+    Diagnostic,
+}
+
+impl LowPass {
+    pub fn hz(self) -> f32 {
+        match self {
+            LowPass::Monitoring => 40.0,
+            LowPass::Diagnostic => 150.0,
+        }
+    }
+}
+
+/// Filters applied to every ECG channel, `None` skips the stage
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EcgFilterConfig {
+    pub high_pass: Option<HighPass>,
+    pub notch: Option<Mains>,
+    /// Harmonics notched, the fundamental included, up to `MAX_HARMONICS`
+    ///
+    /// Harmonics not below half the data rate are skipped.
+    pub notch_harmonics: usize,
+    pub low_pass: Option<LowPass>,
+}
+
+impl EcgFilterConfig {
+    /// 0.5 Hz ~ 40 Hz with the fundamental of `mains` notched
+    pub fn monitoring(mains: Mains) -> Self {
+        EcgFilterConfig {
+            high_pass: Some(HighPass::Monitoring),
+            notch: Some(mains),
+            notch_harmonics: 1,
+            low_pass: Some(LowPass::Monitoring),
+        }
+    }
+
+    /// 0.05 Hz ~ 150 Hz with `mains` and its harmonics notched
+    pub fn diagnostic(mains: Mains) -> Self {
+        EcgFilterConfig {
+            high_pass: Some(HighPass::Diagnostic),
+            notch: Some(mains),
+            notch_harmonics: MAX_HARMONICS,
+            low_pass: Some(LowPass::Diagnostic),
+        }
+    }
+
+    /// `f32` cascade for `data_rate_hz`, see `DataRate::hz`
+    ///
+    /// Fails when the low-pass cutoff is not below half the data rate, or when more than
+    /// `MAX_HARMONICS` harmonics are requested.
+    pub fn design(&self, data_rate_hz: f32) -> Result<Cascade<MAX_SECTIONS>, FilterError> {
+        let mut cascade = Cascade::new();
+        if let Some(high_pass) = self.high_pass {
+            cascade.push(BiquadCoefficients::high_pass(
+                data_rate_hz,
+                high_pass.hz(),
+                BUTTERWORTH_Q,
+            )?)?;
+        }
+        if let Some(mains) = self.notch {
+            if self.notch_harmonics > MAX_HARMONICS {
+                return Err(FilterError::TooManySections(self.notch_harmonics));
+            }
+            let harmonics = (1..=self.notch_harmonics)
+                .map(|n| mains.hz() * n as f32)
+                .filter(|hz| *hz < data_rate_hz / 2.0);
+            for hz in harmonics {
+                cascade.push(BiquadCoefficients::notch(data_rate_hz, hz, NOTCH_Q)?)?;
+            }
+        }
+        if let Some(low_pass) = self.low_pass {
+            for q in BUTTERWORTH_4_Q {
+                cascade.push(BiquadCoefficients::low_pass(
+                    data_rate_hz,
+                    low_pass.hz(),
+                    q,
+                )?)?;
+            }
+        }
+        Ok(cascade)
+    }
+
+    /// Same as `design`, on 24-bit codes
+    pub fn design_fixed(
+        &self,
+        data_rate_hz: f32,
+    ) -> Result<FixedCascade<MAX_SECTIONS>, FilterError> {
+        Ok(self.design(data_rate_hz)?.to_fixed())
+    }
+}
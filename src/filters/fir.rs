@@ -0,0 +1,146 @@
+//! FIR filters with a fixed number of taps
+
+use core::f64::consts::PI;
+
+use super::{check_frequency, Filter, FilterError};
+
+/// Fraction bits of the `FixedFir` coefficients, Q1.31
+pub const FIXED_FRACTION_BITS: u32 = 31;
+
+/// `f32` FIR filter of `TAPS` taps
+#[derive(Clone, Copy, Debug)]
+pub struct Fir<const TAPS: usize> {
+    coefficients: [f32; TAPS],
+    /// Past inputs, `delay[position]` is the oldest
+    delay: [f32; TAPS],
+    position: usize,
+}
+
+impl<const TAPS: usize> Fir<TAPS> {
+    /// `coefficients[0]` weights the newest input
+    pub const fn new(coefficients: [f32; TAPS]) -> Self {
+        assert!(TAPS > 0, "Fir needs at least one tap");
+        Fir {
+            coefficients,
+            delay: [0.0; TAPS],
+            position: 0,
+        }
+    }
+
+    /// Linear-phase low-pass at `hz`, windowed sinc with a Hamming window and unity DC gain
+    ///
+    /// The delay is `(TAPS - 1) / 2` samples, the transition band is about
+    /// `3.3 × data_rate_hz / TAPS` wide.
+    pub fn low_pass(data_rate_hz: f32, hz: f32) -> Result<Self, FilterError> {
+        check_frequency(hz, data_rate_hz)?;
+        let fc = f64::from(hz) / f64::from(data_rate_hz);
+        let middle = (TAPS - 1) as f64 / 2.0;
+        let mut coefficients = [0.0f64; TAPS];
+        for (i, c) in coefficients.iter_mut().enumerate() {
+            let t = i as f64 - middle;
+            let sinc = if t == 0.0 {
+                2.0 * fc
+            } else {
+                (2.0 * PI * fc * t).sin() / (PI * t)
+            };
+            let window = if TAPS == 1 {
+                1.0
+            } else {
+                0.54 - 0.46 * (2.0 * PI * i as f64 / (TAPS - 1) as f64).cos()
+            };
+            *c = sinc * window;
+        }
+        let sum: f64 = coefficients.iter().sum();
+        Ok(Self::new(coefficients.map(|c| (c / sum) as f32)))
+    }
+
+    pub fn coefficients(&self) -> &[f32; TAPS] {
+        &self.coefficients
+    }
+
+    /// The same filter on 24-bit codes
+    pub fn to_fixed(&self) -> FixedFir<TAPS> {
+        FixedFir::new(&self.coefficients)
+    }
+}
+
+impl<const TAPS: usize> Filter for Fir<TAPS> {
+    type Sample = f32;
+
+    fn process(&mut self, x: f32) -> f32 {
+        self.delay[self.position] = x;
+        let y = convolve(
+            &self.coefficients,
+            &self.delay,
+            self.position,
+            0.0,
+            |c, x| c * x,
+        );
+        self.position = (self.position + 1) % TAPS;
+        y
+    }
+
+    fn reset(&mut self) {
+        self.delay = [0.0; TAPS];
+        self.position = 0;
+    }
+}
+
+/// Fixed-point FIR filter on 24-bit codes, with Q1.31 coefficients
+#[derive(Clone, Copy, Debug)]
+pub struct FixedFir<const TAPS: usize> {
+    coefficients: [i32; TAPS],
+    delay: [i32; TAPS],
+    position: usize,
+}
+
+impl<const TAPS: usize> FixedFir<TAPS> {
+    /// Coefficients are rounded to Q1.31 and saturated to `[-1, 1)`
+    pub fn new(coefficients: &[f32; TAPS]) -> Self {
+        assert!(TAPS > 0, "FixedFir needs at least one tap");
+        FixedFir {
+            coefficients: coefficients.map(|c| {
+                let scaled = (f64::from(c) * f64::from(1u32 << FIXED_FRACTION_BITS)).round();
+                scaled.clamp(f64::from(i32::MIN), f64::from(i32::MAX)) as i32
+            }),
+            delay: [0; TAPS],
+            position: 0,
+        }
+    }
+}
+
+impl<const TAPS: usize> Filter for FixedFir<TAPS> {
+    type Sample = i32;
+
+    fn process(&mut self, x: i32) -> i32 {
+        self.delay[self.position] = x;
+        let acc = convolve(&self.coefficients, &self.delay, self.position, 0, |c, x| {
+            i64::from(c) * i64::from(x)
+        });
+        self.position = (self.position + 1) % TAPS;
+        let rounded = (acc + (1 << (FIXED_FRACTION_BITS - 1))) >> FIXED_FRACTION_BITS;
+        rounded.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32
+    }
+
+    fn reset(&mut self) {
+        self.delay = [0; TAPS];
+        self.position = 0;
+    }
+}
+
+/// `Σ coefficients[k] × delay[newest - k]`, `newest` indexes the newest input
+pub(crate) fn convolve<C: Copy, X: Copy, A: core::ops::Add<Output = A>>(
+    coefficients: &[C],
+    delay: &[X],
+    newest: usize,
+    zero: A,
+    multiply: impl Fn(C, X) -> A,
+) -> A {
+    let (older, newer) = delay.split_at(newest + 1);
+    newer
+        .iter()
+        .chain(older)
+        .rev()
+        .zip(coefficients)
+        .fold(zero, |acc, (x, c)| acc + multiply(*c, *x))
+}
@@ -0,0 +1,125 @@
+//! Digital filters for decoded ECG channels
+//!
+//! Every filter keeps its state in fixed-size arrays, so filtering never allocates. `f32`
+//! filters work on channel codes converted to `f32`, fixed-point filters work on the 24-bit
+//! codes directly, for cores without an FPU.
+
+use core::fmt;
+
+use crate::driver::frame::{Frame, CHANNELS, CODE_MAX, CODE_MIN};
+
+pub mod biquad;
+pub mod ecg;
+pub mod fir;
+
+pub use biquad::{Biquad, BiquadCoefficients, Cascade, FixedBiquad, FixedCascade};
+pub use ecg::{EcgFilterConfig, HighPass, LowPass, Mains};
+pub use fir::{Fir, FixedFir};
+
+/// A filter processing one sample at a time
+pub trait Filter {
+    /// `f32` or 24-bit codes in `i32`
+    type Sample: Copy;
+
+    fn process(&mut self, x: Self::Sample) -> Self::Sample;
+
+    /// Clear the state, as if only zeros had been processed
+    fn reset(&mut self);
+}
+
+/// `A` followed by `B`
+impl<A: Filter, B: Filter<Sample = A::Sample>> Filter for (A, B) {
+    type Sample = A::Sample;
+
+    fn process(&mut self, x: A::Sample) -> A::Sample {
+        self.1.process(self.0.process(x))
+    }
+
+    fn reset(&mut self) {
+        self.0.reset();
+        self.1.reset();
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FilterError {
+    /// The frequency is not below half the data rate, in Hz
+    AboveNyquist { hz: f32, data_rate_hz: f32 },
+    /// The frequency is not positive, in Hz
+    InvalidFrequency(f32),
+    /// More sections than the cascade holds
+    TooManySections(usize),
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterError::AboveNyquist { hz, data_rate_hz } => write!(
+                f,
+                "{hz} Hz is not below the Nyquist frequency of {data_rate_hz} SPS"
+            ),
+            FilterError::InvalidFrequency(hz) => write!(f, "invalid frequency {hz} Hz"),
+            FilterError::TooManySections(n) => write!(f, "{n} sections do not fit the cascade"),
+        }
+    }
+}
+
+impl core::error::Error for FilterError {}
+
+/// Check `hz` is in `(0, data_rate_hz / 2)`
+pub(crate) fn check_frequency(hz: f32, data_rate_hz: f32) -> Result<(), FilterError> {
+    if hz.is_nan() || hz <= 0.0 {
+        return Err(FilterError::InvalidFrequency(hz));
+    }
+    if hz >= data_rate_hz / 2.0 {
+        return Err(FilterError::AboveNyquist { hz, data_rate_hz });
+    }
+    Ok(())
+}
+
+/// The same filter applied to every channel of a frame, `[0]` is CH1
+#[derive(Clone, Debug)]
+pub struct ChannelFilters<F> {
+    channels: [F; CHANNELS],
+}
+
+impl<F: Clone> ChannelFilters<F> {
+    /// A copy of `filter` for every channel
+    pub fn new(filter: F) -> Self {
+        ChannelFilters {
+            channels: core::array::from_fn(|_| filter.clone()),
+        }
+    }
+}
+
+impl<F: Filter> ChannelFilters<F> {
+    /// Filter of the channel at `index`, `0` is CH1
+    pub fn channel_mut(&mut self, index: usize) -> &mut F {
+        &mut self.channels[index]
+    }
+
+    pub fn reset(&mut self) {
+        self.channels.iter_mut().for_each(Filter::reset);
+    }
+}
+
+impl<F: Filter<Sample = f32>> ChannelFilters<F> {
+    /// Filter every channel of `frame`, in codes
+    pub fn process(&mut self, frame: &Frame) -> [f32; CHANNELS] {
+        let mut out = [0.0; CHANNELS];
+        for ((y, filter), code) in out.iter_mut().zip(&mut self.channels).zip(frame.channels) {
+            *y = filter.process(code as f32);
+        }
+        out
+    }
+}
+
+impl<F: Filter<Sample = i32>> ChannelFilters<F> {
+    /// Replace every channel of `frame` with its filtered code, saturating at the 24-bit range
+    pub fn process_in_place(&mut self, frame: &mut Frame) {
+        for (code, filter) in frame.channels.iter_mut().zip(&mut self.channels) {
+            *code = filter.process(*code).clamp(CODE_MIN, CODE_MAX);
+        }
+    }
+}
@@ -1 +1,13 @@
+#[macro_use]
+mod fmt;
+
+pub mod decimation;
 pub mod driver;
+pub mod filters;
+pub mod qrs;
+#[cfg(feature = "std")]
+pub mod recording;
+#[cfg(feature = "simulator")]
+pub mod simulator;
+#[cfg(feature = "timing-checker")]
+pub mod timing_checker;
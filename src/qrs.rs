@@ -0,0 +1,361 @@
+//! Pan–Tompkins QRS detection and heart rate
+//!
+//! The chosen lead is averaged down to at most 1 kSPS, band-passed to 5 ~ 15 Hz,
+//! differentiated, squared and integrated over 150 ms. Peaks of the integrated signal are
+//! classified against adaptive signal and noise thresholds, with a 200 ms refractory period and
+//! a search back at half the threshold when no beat follows within 166% of the average RR
+//! interval.
+//!
+//! Reference: J. Pan and W. J. Tompkins, "A Real-Time QRS Detection Algorithm",
+//! IEEE Trans. Biomed. Eng., 1985.
+
+use core::fmt;
+use core::time::Duration;
+
+use crate::driver::frame::{Frame, CHANNELS};
+use crate::filters::biquad::{BiquadCoefficients, Cascade, BUTTERWORTH_Q};
+use crate::filters::{Filter, FilterError};
+
+/// Highest rate of the detection stages, in SPS
+const MAX_PROCESSING_HZ: f32 = 1000.0;
+
+/// Integration window, in s
+const WINDOW_S: f32 = 0.15;
+
+/// Samples of the integration window at `MAX_PROCESSING_HZ`
+const MAX_WINDOW: usize = 150;
+
+/// No beat within this time after a beat, in s
+const REFRACTORY_S: f32 = 0.2;
+
+/// Thresholds are learned over this time before detecting, in s
+const LEARNING_S: f32 = 2.0;
+
+/// RR intervals in the average heart rate
+pub const RR_AVERAGE: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum QrsError {
+    /// Channel number is not in `1..=8`
+    InvalidChannel(u8),
+    /// The data rate is too low for the 5 ~ 15 Hz band-pass
+    Filter(FilterError),
+}
+
+impl From<FilterError> for QrsError {
+    fn from(e: FilterError) -> Self {
+        QrsError::Filter(e)
+    }
+}
+
+impl fmt::Display for QrsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QrsError::InvalidChannel(channel) => write!(f, "invalid channel {channel}"),
+            QrsError::Filter(e) => write!(f, "unsupported data rate: {e}"),
+        }
+    }
+}
+
+impl core::error::Error for QrsError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            QrsError::Filter(e) => Some(e),
+            QrsError::InvalidChannel(_) => None,
+        }
+    }
+}
+
+/// A detected heart beat
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Beat {
+    /// Index of the frame holding the R peak, counted from the first frame processed
+    pub r_peak: u64,
+    /// Time since the previous beat, `None` for the first beat after start or lead-off
+    pub rr: Option<Duration>,
+    /// `60 / rr`, in BPM
+    pub heart_rate_bpm: Option<f32>,
+    /// Over the last `RR_AVERAGE` RR intervals, in BPM
+    pub average_bpm: Option<f32>,
+    /// Found by the search back at half the threshold
+    pub searched_back: bool,
+}
+
+/// A peak of the integrated signal
+#[derive(Clone, Copy, Debug)]
+struct Peak {
+    value: f32,
+    r_peak: u64,
+}
+
+/// Detects QRS complexes on one channel of decoded frames
+pub struct QrsDetector {
+    /// `0` is CH1
+    channel: usize,
+    data_rate_hz: f32,
+    /// Frames averaged into one processed sample
+    decimation: usize,
+    sum: i64,
+    summed: usize,
+    /// Frames processed, the index of the next frame
+    frames: u64,
+
+    band_pass: Cascade<2>,
+    /// Last 4 band-passed samples, `[0]` is the newest
+    history: [f32; 4],
+    /// Squared derivative and input sample with its frame index, over the window
+    window: [(f32, f32, u64); MAX_WINDOW],
+    window_len: usize,
+    position: usize,
+    integrated: f32,
+    previous: f32,
+    rising: bool,
+
+    learning: u64,
+    learned_max: f32,
+    learned_sum: f32,
+    spki: f32,
+    npki: f32,
+    refractory: u64,
+    last_beat: Option<u64>,
+    /// Largest rejected peak since the last beat, for the search back
+    search_back: Option<Peak>,
+    rr: [u64; RR_AVERAGE],
+    rr_len: usize,
+    rr_next: usize,
+    lead_off: bool,
+}
+
+impl QrsDetector {
+    /// Detect on `channel` in `1..=8`, `data_rate_hz` is the output data rate, see
+    /// `DataRate::hz`
+    pub fn new(channel: u8, data_rate_hz: f32) -> Result<Self, QrsError> {
+        if channel == 0 || channel as usize > CHANNELS {
+            return Err(QrsError::InvalidChannel(channel));
+        }
+        let decimation = (data_rate_hz / MAX_PROCESSING_HZ).ceil().max(1.0) as usize;
+        let processing_hz = data_rate_hz / decimation as f32;
+        let mut band_pass = Cascade::new();
+        band_pass.push(BiquadCoefficients::high_pass(
+            processing_hz,
+            5.0,
+            BUTTERWORTH_Q,
+        )?)?;
+        band_pass.push(BiquadCoefficients::low_pass(
+            processing_hz,
+            15.0,
+            BUTTERWORTH_Q,
+        )?)?;
+        let window_len = ((WINDOW_S * processing_hz).round() as usize).clamp(1, MAX_WINDOW);
+        Ok(QrsDetector {
+            channel: usize::from(channel - 1),
+            data_rate_hz,
+            decimation,
+            sum: 0,
+            summed: 0,
+            frames: 0,
+            band_pass,
+            history: [0.0; 4],
+            window: [(0.0, 0.0, 0); MAX_WINDOW],
+            window_len,
+            position: 0,
+            integrated: 0.0,
+            previous: 0.0,
+            rising: false,
+            learning: (LEARNING_S * data_rate_hz) as u64,
+            learned_max: 0.0,
+            learned_sum: 0.0,
+            spki: 0.0,
+            npki: 0.0,
+            refractory: (REFRACTORY_S * data_rate_hz) as u64,
+            last_beat: None,
+            search_back: None,
+            rr: [0; RR_AVERAGE],
+            rr_len: 0,
+            rr_next: 0,
+            lead_off: false,
+        })
+    }
+
+    /// Process the next frame, returns a beat once its QRS complex is complete
+    ///
+    /// Frames reporting an electrode of the channel off suppress detection. The detector
+    /// restarts, learning its thresholds again, once the electrodes are back on.
+    pub fn process(&mut self, frame: &Frame) -> Option<Beat> {
+        let bit = 1 << self.channel;
+        let lead_off = (frame.status.loff_statp() | frame.status.loff_statn()) & bit != 0;
+        self.process_code(frame.channels[self.channel], lead_off)
+    }
+
+    /// Process the next code of the channel, see `process`
+    pub fn process_code(&mut self, code: i32, lead_off: bool) -> Option<Beat> {
+        let index = self.frames;
+        self.frames += 1;
+        if lead_off {
+            if !self.lead_off {
+                warn!("Lead off, QRS detection suppressed");
+                self.restart();
+                self.lead_off = true;
+            }
+            return None;
+        }
+        self.lead_off = false;
+
+        self.sum += i64::from(code);
+        self.summed += 1;
+        if self.summed < self.decimation {
+            return None;
+        }
+        let x = self.sum as f32 / self.summed as f32;
+        self.sum = 0;
+        self.summed = 0;
+        self.detect(x, index)
+    }
+
+    /// Whether the last frame reported an electrode of the channel off
+    pub fn is_lead_off(&self) -> bool {
+        self.lead_off
+    }
+
+    /// Average of the last `RR_AVERAGE` RR intervals, in BPM
+    pub fn average_bpm(&self) -> Option<f32> {
+        if self.rr_len == 0 {
+            return None;
+        }
+        let mean = self.rr[..self.rr_len].iter().sum::<u64>() as f32 / self.rr_len as f32;
+        Some(60.0 * self.data_rate_hz / mean)
+    }
+
+    /// Forget the signal and the thresholds, frame indexes keep counting
+    pub fn restart(&mut self) {
+        self.sum = 0;
+        self.summed = 0;
+        self.band_pass.reset();
+        self.history = [0.0; 4];
+        self.window = [(0.0, 0.0, 0); MAX_WINDOW];
+        self.position = 0;
+        self.integrated = 0.0;
+        self.previous = 0.0;
+        self.rising = false;
+        self.learning = (LEARNING_S * self.data_rate_hz) as u64;
+        self.learned_max = 0.0;
+        self.learned_sum = 0.0;
+        self.spki = 0.0;
+        self.npki = 0.0;
+        self.last_beat = None;
+        self.search_back = None;
+        self.rr_len = 0;
+        self.rr_next = 0;
+    }
+
+    /// `x` is the averaged code of the frames up to `index`
+    fn detect(&mut self, x: f32, index: u64) -> Option<Beat> {
+        let band = self.band_pass.process(x);
+        let [x1, _, x3, x4] = self.history;
+        let derivative = (2.0 * band + x1 - x3 - 2.0 * x4) / 8.0;
+        self.history = [band, x1, self.history[1], x3];
+
+        // Integrate the squared derivative over the window
+        let squared = derivative * derivative;
+        self.integrated += (squared - self.window[self.position].0) / self.window_len as f32;
+        self.window[self.position] = (squared, x, index);
+        self.position = (self.position + 1) % self.window_len;
+        if self.position == 0 {
+            // Drop the rounding errors of the running sum
+            let sum: f32 = self.window[..self.window_len].iter().map(|w| w.0).sum();
+            self.integrated = sum / self.window_len as f32;
+        }
+
+        let integrated = self.integrated.max(0.0);
+        let falling = integrated < self.previous;
+        let peak = (self.rising && falling).then(|| Peak {
+            value: self.previous,
+            r_peak: self.r_peak(),
+        });
+        self.rising = integrated > self.previous;
+        self.previous = integrated;
+
+        if self.learning > 0 {
+            self.learning = self.learning.saturating_sub(self.decimation as u64);
+            self.learned_max = self.learned_max.max(integrated);
+            self.learned_sum += integrated;
+            if self.learning == 0 {
+                let samples = LEARNING_S * self.data_rate_hz / self.decimation as f32;
+                self.spki = self.learned_max / 3.0;
+                self.npki = self.learned_sum / samples / 2.0;
+            }
+            return None;
+        }
+
+        if let Some(beat) = peak.and_then(|peak| self.classify(peak)) {
+            return Some(beat);
+        }
+        self.search_back(index)
+    }
+
+    /// Frame index of the input sample furthest from the mean of the window
+    fn r_peak(&self) -> u64 {
+        let window = &self.window[..self.window_len];
+        let mean = window.iter().map(|w| w.1).sum::<f32>() / self.window_len as f32;
+        window
+            .iter()
+            .max_by(|a, b| (a.1 - mean).abs().total_cmp(&(b.1 - mean).abs()))
+            .map_or(0, |(_, _, index)| *index)
+    }
+
+    fn threshold(&self) -> f32 {
+        self.npki + 0.25 * (self.spki - self.npki)
+    }
+
+    fn classify(&mut self, peak: Peak) -> Option<Beat> {
+        let refractory = self
+            .last_beat
+            .is_some_and(|last| peak.r_peak < last + self.refractory);
+        if !refractory && peak.value > self.threshold() {
+            self.spki = 0.125 * peak.value + 0.875 * self.spki;
+            return Some(self.beat(peak, false));
+        }
+        self.npki = 0.125 * peak.value + 0.875 * self.npki;
+        if !refractory && self.search_back.is_none_or(|best| peak.value > best.value) {
+            self.search_back = Some(peak);
+        }
+        None
+    }
+
+    /// Take the largest rejected peak above half the threshold as a beat, when no beat
+    /// followed within 166% of the average RR interval
+    fn search_back(&mut self, index: u64) -> Option<Beat> {
+        let last = self.last_beat?;
+        let mean = self.rr[..self.rr_len].iter().sum::<u64>() / self.rr_len.max(1) as u64;
+        if self.rr_len == 0 || index < last + mean * 166 / 100 {
+            return None;
+        }
+        let peak = self.search_back.take()?;
+        if peak.value <= self.threshold() / 2.0 {
+            return None;
+        }
+        self.spki = 0.25 * peak.value + 0.75 * self.spki;
+        Some(self.beat(peak, true))
+    }
+
+    fn beat(&mut self, peak: Peak, searched_back: bool) -> Beat {
+        self.search_back = None;
+        let rr = self.last_beat.map(|last| peak.r_peak - last);
+        self.last_beat = Some(peak.r_peak);
+        if let Some(rr) = rr {
+            self.rr[self.rr_next] = rr;
+            self.rr_next = (self.rr_next + 1) % RR_AVERAGE;
+            self.rr_len = (self.rr_len + 1).min(RR_AVERAGE);
+        }
+        let rr = rr.map(|rr| Duration::from_secs_f64(rr as f64 / f64::from(self.data_rate_hz)));
+        Beat {
+            r_peak: peak.r_peak,
+            rr,
+            heart_rate_bpm: rr.map(|rr| 60.0 / rr.as_secs_f32()),
+            average_bpm: self.average_bpm(),
+            searched_back,
+        }
+    }
+}
@@ -0,0 +1,275 @@
+//! Raw capture of a session, for bug reports and offline processing
+//!
+//! A capture is a 72 byte header followed by every frame as read from the chip:
+//!
+//! | Offset | Size   | Content                                                       |
+//! |--------|--------|---------------------------------------------------------------|
+//! | 0      | 8      | `MAGIC`                                                       |
+//! | 8      | 1      | `VERSION`                                                     |
+//! | 9      | 1      | `FRAME_SIZE`                                                  |
+//! | 10     | 4      | fCLK of an external clock in Hz, `0` for the internal one, LE |
+//! | 14     | 26     | Registers `00h` ~ `19h`                                       |
+//! | 40     | 32     | Offsets of CH1 ~ CH8, `i32` LE                                |
+//! | 72     | 27 × n | Raw frames                                                    |
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+use embedded_hal::spi::SpiDevice;
+
+use crate::driver::clock::ClockSource;
+use crate::driver::frame::{Frame, FrameRef, CHANNELS, FRAME_SIZE};
+use crate::driver::registers::map::{RegisterMap, REGISTER_COUNT};
+use crate::driver::stream_reader::FrameSource;
+use crate::driver::{ConfigError, ADS1298};
+
+pub const MAGIC: [u8; 8] = *b"ADS1298C";
+
+pub const VERSION: u8 = 1;
+
+/// Bytes before the first frame
+pub const HEADER_SIZE: usize = 14 + REGISTER_COUNT + 4 * CHANNELS;
+
+/// Device state needed to decode the frames of a capture
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CaptureHeader {
+    pub clock: ClockSource,
+    pub registers: RegisterMap,
+    /// Subtracted by `Replay::read_frame`, see `ADS1298::calibrate_offsets`
+    pub offsets: [i32; CHANNELS],
+}
+
+impl CaptureHeader {
+    /// Clock, registers and offsets of `driver`
+    pub fn read<SPI: SpiDevice>(
+        driver: &mut ADS1298<SPI>,
+    ) -> Result<Self, ConfigError<SPI::Error>> {
+        Ok(CaptureHeader {
+            clock: driver.clock(),
+            registers: driver.read_register_map()?,
+            offsets: driver.offsets(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0; HEADER_SIZE];
+        bytes[..8].copy_from_slice(&MAGIC);
+        bytes[8] = VERSION;
+        bytes[9] = FRAME_SIZE as u8;
+        let clock_hz = match self.clock {
+            ClockSource::Internal => 0,
+            ClockSource::External(hz) => hz,
+        };
+        bytes[10..14].copy_from_slice(&clock_hz.to_le_bytes());
+        bytes[14..40].copy_from_slice(&self.registers.to_bytes());
+        for (chunk, offset) in bytes[40..].chunks_exact_mut(4).zip(self.offsets) {
+            chunk.copy_from_slice(&offset.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; HEADER_SIZE]) -> Result<Self, CaptureError> {
+        if bytes[..8] != MAGIC {
+            return Err(CaptureError::NotACapture);
+        }
+        if bytes[8] != VERSION || usize::from(bytes[9]) != FRAME_SIZE {
+            return Err(CaptureError::UnsupportedVersion(bytes[8]));
+        }
+        let clock = match u32::from_le_bytes(bytes[10..14].try_into().unwrap()) {
+            0 => ClockSource::Internal,
+            hz => ClockSource::External(hz),
+        };
+        let registers = RegisterMap::from_bytes(bytes[14..40].try_into().unwrap());
+        let mut offsets = [0; CHANNELS];
+        for (offset, chunk) in offsets.iter_mut().zip(bytes[40..].chunks_exact(4)) {
+            *offset = i32::from_le_bytes(chunk.try_into().unwrap());
+        }
+        Ok(CaptureHeader {
+            clock,
+            registers,
+            offsets,
+        })
+    }
+
+    /// Output data rate of the captured `CONFIG1`, in SPS
+    pub fn data_rate_hz(&self) -> Option<f32> {
+        self.clock.data_rate_hz(self.registers.config1)
+    }
+}
+
+/// Writes raw frames after a `CaptureHeader`
+pub struct CaptureWriter<W: Write> {
+    writer: W,
+    frames: u64,
+}
+
+impl CaptureWriter<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>, header: &CaptureHeader) -> Result<Self, CaptureError> {
+        Self::new(BufWriter::new(File::create(path)?), header)
+    }
+}
+
+impl<W: Write> CaptureWriter<W> {
+    pub fn new(mut writer: W, header: &CaptureHeader) -> Result<Self, CaptureError> {
+        writer.write_all(&header.to_bytes())?;
+        Ok(CaptureWriter { writer, frames: 0 })
+    }
+
+    /// Append a frame as read by `FrameSource::read_into`
+    pub fn write_frame(&mut self, raw: &[u8; FRAME_SIZE]) -> Result<(), CaptureError> {
+        self.writer.write_all(raw)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Frames written
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn finish(mut self) -> Result<W, CaptureError> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Frames of a capture, as produced by the `StreamReader` which recorded them
+///
+/// As an `Iterator` it ends with the capture.
+pub struct Replay<R: Read> {
+    reader: R,
+    header: CaptureHeader,
+    frames: u64,
+}
+
+impl Replay<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, CaptureError> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> Replay<R> {
+    /// Read the header of the capture
+    pub fn new(mut reader: R) -> Result<Self, CaptureError> {
+        let mut bytes = [0; HEADER_SIZE];
+        reader.read_exact(&mut bytes).map_err(|e| match e.kind() {
+            ErrorKind::UnexpectedEof => CaptureError::NotACapture,
+            _ => CaptureError::Io(e),
+        })?;
+        Ok(Replay {
+            reader,
+            header: CaptureHeader::from_bytes(&bytes)?,
+            frames: 0,
+        })
+    }
+
+    pub fn header(&self) -> &CaptureHeader {
+        &self.header
+    }
+
+    /// Frames read
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> FrameSource for Replay<R> {
+    type Error = CaptureError;
+
+    /// Offsets of the header are subtracted.
+    fn read_frame(&mut self) -> Result<Frame, CaptureError> {
+        let mut buffer = [0; FRAME_SIZE];
+        let mut frame = self.read_into(&mut buffer)?.to_frame();
+        frame.apply_offsets(&self.header.offsets);
+        Ok(frame)
+    }
+
+    /// Returns `CaptureError::End` after the last frame.
+    fn read_into<'b>(
+        &mut self,
+        buffer: &'b mut [u8; FRAME_SIZE],
+    ) -> Result<FrameRef<'b>, CaptureError> {
+        let mut filled = 0;
+        while filled < FRAME_SIZE {
+            match self.reader.read(&mut buffer[filled..]) {
+                Ok(0) if filled == 0 => return Err(CaptureError::End),
+                Ok(0) => return Err(CaptureError::TruncatedFrame),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        self.frames += 1;
+        Ok(FrameRef::new(buffer))
+    }
+
+    fn data_rate_hz(&mut self) -> Result<f32, CaptureError> {
+        self.header
+            .data_rate_hz()
+            .ok_or(CaptureError::ReservedDataRate)
+    }
+}
+
+impl<R: Read> Iterator for Replay<R> {
+    type Item = Result<Frame, CaptureError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_frame() {
+            Err(CaptureError::End) => None,
+            item => Some(item),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CaptureError {
+    Io(io::Error),
+    /// The header does not start with `MAGIC`
+    NotACapture,
+    /// Written by another version of the format
+    UnsupportedVersion(u8),
+    /// The captured `CONFIG1::dr` holds the reserved `111`
+    ReservedDataRate,
+    /// The capture ends in the middle of a frame
+    TruncatedFrame,
+    /// No frame left
+    End,
+}
+
+impl From<io::Error> for CaptureError {
+    fn from(e: io::Error) -> Self {
+        CaptureError::Io(e)
+    }
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureError::Io(e) => write!(f, "failed to access the capture: {e}"),
+            CaptureError::NotACapture => f.write_str("not an ADS1298 capture"),
+            CaptureError::UnsupportedVersion(version) => {
+                write!(f, "unsupported capture version {version}")
+            }
+            CaptureError::ReservedDataRate => {
+                f.write_str("captured CONFIG1 holds the reserved data rate")
+            }
+            CaptureError::TruncatedFrame => f.write_str("capture ends in the middle of a frame"),
+            CaptureError::End => f.write_str("end of the capture"),
+        }
+    }
+}
+
+impl std::error::Error for CaptureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CaptureError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! CSV export, one row per frame
+//!
+//! Columns are the frame index, the time in s, each signal in µV, then `LOFF_STATP`,
+//! `LOFF_STATN` and the `GPIO` inputs of the status word in hex:
+//!
+//! ```text
+//! index,time_s,ECG I,ECG II,loff_statp,loff_statn,gpio
+//! 0,0.000000,-12.345,6.789,0x00,0x00,0x0
+//! ```
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::{RecordingError, RecordingInfo};
+use crate::driver::frame::{Frame, CHANNELS};
+
+/// Writes frames as CSV rows
+pub struct CsvWriter<W: Write> {
+    writer: W,
+    /// Index in `Frame::channels` and µV per code of each signal
+    signals: Vec<(usize, f64)>,
+    data_rate_hz: f64,
+    frames: u64,
+}
+
+impl CsvWriter<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>, info: &RecordingInfo) -> Result<Self, RecordingError> {
+        Self::new(BufWriter::new(File::create(path)?), info)
+    }
+}
+
+impl<W: Write> CsvWriter<W> {
+    /// Write the header row of `info`
+    ///
+    /// Labels holding `,` or `"` are quoted.
+    pub fn new(mut writer: W, info: &RecordingInfo) -> Result<Self, RecordingError> {
+        if info.signals.is_empty() {
+            return Err(RecordingError::NoSignals);
+        }
+        let signals = info
+            .signals
+            .iter()
+            .map(|signal| match usize::from(signal.channel) {
+                channel @ 1..=CHANNELS => Ok((channel - 1, signal.lsb_uv())),
+                _ => Err(RecordingError::InvalidChannel(signal.channel)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        write!(writer, "index,time_s")?;
+        for signal in &info.signals {
+            write!(writer, ",{}", quote(&signal.label))?;
+        }
+        writeln!(writer, ",loff_statp,loff_statn,gpio")?;
+        Ok(CsvWriter {
+            writer,
+            signals,
+            data_rate_hz: info.data_rate_hz.into(),
+            frames: 0,
+        })
+    }
+
+    /// Frames written
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn write_frame(&mut self, frame: &Frame) -> Result<(), RecordingError> {
+        let time = self.frames as f64 / self.data_rate_hz;
+        write!(self.writer, "{},{time:.6}", self.frames)?;
+        for (channel, lsb_uv) in &self.signals {
+            write!(
+                self.writer,
+                ",{:.3}",
+                f64::from(frame.channels[*channel]) * lsb_uv
+            )?;
+        }
+        let status = &frame.status;
+        writeln!(
+            self.writer,
+            ",{:#04x},{:#04x},{:#03x}",
+            status.loff_statp(),
+            status.loff_statn(),
+            status.ds3.0 & 0x0f
+        )?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<W, RecordingError> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// `field` quoted when it holds a separator, a quote or a line break
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
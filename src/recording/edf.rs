@@ -0,0 +1,393 @@
+//! EDF+ and BDF+ writer
+//!
+//! Data records are 1 s long. Lead-off changes reported by the status word are stored as
+//! annotations in the `EDF Annotations` / `BDF Annotations` signal.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use super::{trim_decimals, RecordingError, RecordingInfo, Signal};
+use crate::driver::frame::{Frame, CHANNELS};
+
+/// Offset of the number of data records in the header
+const RECORD_COUNT_OFFSET: u64 = 236;
+
+/// Bytes of the annotation signal in each data record
+const ANNOTATION_BYTES: usize = 120;
+
+/// Duration of a data record, in s
+const RECORD_SECONDS: u32 = 1;
+
+const MONTHS: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EdfFormat {
+    /// 16-bit samples, the 8 LSBs of the codes are dropped
+    Edf,
+    /// 24-bit samples, the codes are stored unchanged
+    Bdf,
+}
+
+impl EdfFormat {
+    pub fn bytes_per_sample(self) -> usize {
+        match self {
+            EdfFormat::Edf => 2,
+            EdfFormat::Bdf => 3,
+        }
+    }
+
+    /// Digital minimum and maximum
+    pub fn digital_range(self) -> (i32, i32) {
+        match self {
+            EdfFormat::Edf => (i16::MIN.into(), i16::MAX.into()),
+            EdfFormat::Bdf => (-0x80_0000, 0x7f_ffff),
+        }
+    }
+
+    /// Bits dropped from a 24-bit code
+    fn shift(self) -> u32 {
+        match self {
+            EdfFormat::Edf => 8,
+            EdfFormat::Bdf => 0,
+        }
+    }
+
+    fn version(self) -> &'static [u8; 8] {
+        match self {
+            EdfFormat::Edf => b"0       ",
+            EdfFormat::Bdf => b"\xffBIOSEMI",
+        }
+    }
+
+    fn reserved(self) -> &'static str {
+        match self {
+            EdfFormat::Edf => "EDF+C",
+            EdfFormat::Bdf => "BDF+C",
+        }
+    }
+
+    fn annotations_label(self) -> &'static str {
+        match self {
+            EdfFormat::Edf => "EDF Annotations",
+            EdfFormat::Bdf => "BDF Annotations",
+        }
+    }
+
+    /// Physical minimum and maximum of `signal`, in µV
+    pub fn physical_range(self, signal: &Signal) -> (f64, f64) {
+        let step = signal.lsb_uv() * f64::from(1u32 << self.shift());
+        let (min, max) = self.digital_range();
+        (f64::from(min) * step, f64::from(max) * step)
+    }
+}
+
+/// Writes frames to an EDF+ or BDF+ file
+///
+/// The number of data records is written by `finish`, a file left unfinished keeps `-1`.
+pub struct EdfWriter<W: Write + Seek> {
+    writer: W,
+    format: EdfFormat,
+    /// Index in `Frame::channels` of each signal
+    channels: Vec<usize>,
+    samples_per_record: usize,
+    data_rate_hz: f32,
+    /// Data record being filled
+    record: Vec<u8>,
+    /// Frames in `record`
+    filled: usize,
+    records: u64,
+    /// Frames written, `record` included
+    frames: u64,
+    /// Onset in s and text of annotations not yet written
+    annotations: VecDeque<(f64, String)>,
+    /// `LOFF_STATP` and `LOFF_STATN` of the last frame
+    lead_off: (u8, u8),
+}
+
+impl EdfWriter<BufWriter<File>> {
+    pub fn create(
+        path: impl AsRef<Path>,
+        format: EdfFormat,
+        info: &RecordingInfo,
+    ) -> Result<Self, RecordingError> {
+        Self::new(BufWriter::new(File::create(path)?), format, info)
+    }
+}
+
+impl<W: Write + Seek> EdfWriter<W> {
+    /// Write the header of `info`
+    pub fn new(
+        mut writer: W,
+        format: EdfFormat,
+        info: &RecordingInfo,
+    ) -> Result<Self, RecordingError> {
+        if info.signals.is_empty() {
+            return Err(RecordingError::NoSignals);
+        }
+        let rate = info.data_rate_hz;
+        if rate < 1.0 || rate.fract() != 0.0 {
+            return Err(RecordingError::UnsupportedDataRate(rate));
+        }
+        let channels = info
+            .signals
+            .iter()
+            .map(|signal| match usize::from(signal.channel) {
+                channel @ 1..=CHANNELS => Ok(channel - 1),
+                _ => Err(RecordingError::InvalidChannel(signal.channel)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let samples_per_record = rate as usize * RECORD_SECONDS as usize;
+
+        writer.write_all(&header(format, info, samples_per_record)?)?;
+        let record_bytes =
+            channels.len() * samples_per_record * format.bytes_per_sample() + ANNOTATION_BYTES;
+        Ok(EdfWriter {
+            writer,
+            format,
+            channels,
+            samples_per_record,
+            data_rate_hz: rate,
+            record: vec![0; record_bytes],
+            filled: 0,
+            records: 0,
+            frames: 0,
+            annotations: VecDeque::new(),
+            lead_off: (0, 0),
+        })
+    }
+
+    pub fn format(&self) -> EdfFormat {
+        self.format
+    }
+
+    /// Data records written
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    /// Add the next frame, annotating lead-off changes
+    pub fn write_frame(&mut self, frame: &Frame) -> Result<(), RecordingError> {
+        let lead_off = (frame.status.loff_statp(), frame.status.loff_statn());
+        let changes = [
+            (self.lead_off.0, lead_off.0, 'P'),
+            (self.lead_off.1, lead_off.1, 'N'),
+        ];
+        for (from, to, polarity) in changes {
+            let off = electrodes(to & !from, polarity);
+            if !off.is_empty() {
+                self.queue(format!("Lead off {off}"));
+            }
+            let on = electrodes(from & !to, polarity);
+            if !on.is_empty() {
+                self.queue(format!("Lead on {on}"));
+            }
+        }
+        self.lead_off = lead_off;
+
+        let bytes = self.format.bytes_per_sample();
+        let signal_bytes = self.samples_per_record * bytes;
+        for (signal, channel) in self.channels.iter().enumerate() {
+            let sample = frame.channels[*channel] >> self.format.shift();
+            let offset = signal * signal_bytes + self.filled * bytes;
+            self.record[offset..offset + bytes].copy_from_slice(&sample.to_le_bytes()[..bytes]);
+        }
+        self.filled += 1;
+        self.frames += 1;
+        if self.filled == self.samples_per_record {
+            self.write_record()?;
+        }
+        Ok(())
+    }
+
+    /// Annotate the next frame with `text`
+    ///
+    /// `text` can not hold the `\0`, `\x14` and `\x15` separators of the annotation signal.
+    pub fn annotate(&mut self, text: &str) -> Result<(), RecordingError> {
+        // Room for the time-keeping TAL and the onset
+        if text.len() > ANNOTATION_BYTES / 2 || text.contains(['\0', '\x14', '\x15']) {
+            return Err(RecordingError::InvalidField("annotation"));
+        }
+        self.queue(text.to_owned());
+        Ok(())
+    }
+
+    /// Pad the last data record with zeros, write the number of data records and return the
+    /// writer
+    pub fn finish(mut self) -> Result<W, RecordingError> {
+        if self.filled > 0 {
+            let bytes = self.format.bytes_per_sample();
+            let signal_bytes = self.samples_per_record * bytes;
+            for signal in 0..self.channels.len() {
+                let start = signal * signal_bytes;
+                self.record[start + self.filled * bytes..start + signal_bytes].fill(0);
+            }
+            self.write_record()?;
+        }
+        while !self.annotations.is_empty() {
+            self.record.fill(0);
+            self.write_record()?;
+        }
+        self.writer.seek(SeekFrom::Start(RECORD_COUNT_OFFSET))?;
+        self.writer
+            .write_all(pad(&self.records.to_string(), 8, "number of records")?.as_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn queue(&mut self, text: String) {
+        let onset = self.frames as f64 / f64::from(self.data_rate_hz);
+        self.annotations.push_back((onset, text));
+    }
+
+    fn write_record(&mut self) -> Result<(), RecordingError> {
+        let start = self.record.len() - ANNOTATION_BYTES;
+        let area = &mut self.record[start..];
+        let onset = self.records as f64 * f64::from(RECORD_SECONDS);
+        let mut tals = format!("+{}\x14\x14\0", seconds(onset)).into_bytes();
+        while let Some((onset, text)) = self.annotations.front() {
+            let tal = format!("+{}\x14{text}\x14\0", seconds(*onset));
+            if tals.len() + tal.len() > ANNOTATION_BYTES {
+                break;
+            }
+            tals.extend_from_slice(tal.as_bytes());
+            self.annotations.pop_front();
+        }
+        area.fill(0);
+        area[..tals.len()].copy_from_slice(&tals);
+
+        self.writer.write_all(&self.record)?;
+        self.records += 1;
+        self.filled = 0;
+        Ok(())
+    }
+}
+
+/// Electrodes set in a `LOFF_STATP` or `LOFF_STATN` value, such as `"IN2P IN3P"`
+fn electrodes(bits: u8, polarity: char) -> String {
+    (0..CHANNELS)
+        .filter(|bit| bits & (1 << bit) != 0)
+        .map(|bit| format!("IN{}{polarity}", bit + 1))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn header(
+    format: EdfFormat,
+    info: &RecordingInfo,
+    samples_per_record: usize,
+) -> Result<Vec<u8>, RecordingError> {
+    let start = &info.start;
+    if !(1..=12).contains(&start.month) {
+        return Err(RecordingError::InvalidField("start date"));
+    }
+    let signals = info.signals.len() + 1;
+    let (digital_min, digital_max) = format.digital_range();
+    let annotation_samples = ANNOTATION_BYTES / format.bytes_per_sample();
+
+    let mut header = format.version().to_vec();
+    let recording = format!(
+        "Startdate {:02}-{}-{:04} {}",
+        start.day,
+        MONTHS[usize::from(start.month - 1)],
+        start.year,
+        info.recording
+    );
+    let fields = [
+        pad(&info.patient, 80, "patient identification")?,
+        pad(&recording, 80, "recording identification")?,
+        pad(
+            &format!(
+                "{:02}.{:02}.{:02}",
+                start.day,
+                start.month,
+                start.year % 100
+            ),
+            8,
+            "start date",
+        )?,
+        pad(
+            &format!("{:02}.{:02}.{:02}", start.hour, start.minute, start.second),
+            8,
+            "start time",
+        )?,
+        pad(&(256 * (signals + 1)).to_string(), 8, "header size")?,
+        pad(format.reserved(), 44, "reserved")?,
+        pad("-1", 8, "number of records")?,
+        pad(&RECORD_SECONDS.to_string(), 8, "record duration")?,
+        pad(&signals.to_string(), 4, "number of signals")?,
+    ];
+    fields
+        .iter()
+        .for_each(|f| header.extend_from_slice(f.as_bytes()));
+
+    let ranges: Vec<_> = info
+        .signals
+        .iter()
+        .map(|s| format.physical_range(s))
+        .collect();
+    let mut columns: Vec<Vec<String>> = vec![vec![]; 10];
+    for (signal, (physical_min, physical_max)) in info.signals.iter().zip(ranges) {
+        let column = [
+            pad(&signal.label, 16, "label")?,
+            pad("AgAgCl electrode", 80, "transducer")?,
+            pad("uV", 8, "physical dimension")?,
+            number(physical_min, "physical minimum")?,
+            number(physical_max, "physical maximum")?,
+            number(digital_min.into(), "digital minimum")?,
+            number(digital_max.into(), "digital maximum")?,
+            pad(&signal.prefiltering, 80, "prefiltering")?,
+            number(samples_per_record as f64, "samples per record")?,
+            pad("", 32, "reserved")?,
+        ];
+        columns.iter_mut().zip(column).for_each(|(c, f)| c.push(f));
+    }
+    let annotations = [
+        pad(format.annotations_label(), 16, "label")?,
+        pad("", 80, "transducer")?,
+        pad("", 8, "physical dimension")?,
+        number(-1.0, "physical minimum")?,
+        number(1.0, "physical maximum")?,
+        number(digital_min.into(), "digital minimum")?,
+        number(digital_max.into(), "digital maximum")?,
+        pad("", 80, "prefiltering")?,
+        number(annotation_samples as f64, "samples per record")?,
+        pad("", 32, "reserved")?,
+    ];
+    columns
+        .iter_mut()
+        .zip(annotations)
+        .for_each(|(c, f)| c.push(f));
+    columns
+        .iter()
+        .flatten()
+        .for_each(|f| header.extend_from_slice(f.as_bytes()));
+    Ok(header)
+}
+
+/// `value` padded with spaces to `width`, which must be printable ASCII
+fn pad(value: &str, width: usize, field: &'static str) -> Result<String, RecordingError> {
+    if value.len() > width || !value.bytes().all(|b| (b' '..=b'~').contains(&b)) {
+        return Err(RecordingError::InvalidField(field));
+    }
+    Ok(format!("{value:width$}"))
+}
+
+/// `value` in an 8 character field, with as many decimals as fit
+fn number(value: f64, field: &'static str) -> Result<String, RecordingError> {
+    let text = (0..=6)
+        .rev()
+        .map(|decimals| trim_decimals(format!("{value:.decimals$}")))
+        .find(|text| text.len() <= 8)
+        .ok_or(RecordingError::InvalidField(field))?;
+    pad(&text, 8, field)
+}
+
+/// Onset of an annotation, in s
+fn seconds(value: f64) -> String {
+    trim_decimals(format!("{value:.6}"))
+}
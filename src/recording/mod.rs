@@ -0,0 +1,202 @@
+//! Archiving frames to files
+//!
+//! Available with the `std` feature.
+
+use std::fmt;
+use std::io;
+
+use crate::driver::clock::ClockSource;
+use crate::driver::frame::{CHANNELS, CODE_MAX};
+use crate::driver::registers::map::RegisterMap;
+
+pub mod capture;
+pub mod csv;
+pub mod edf;
+pub mod wfdb;
+
+/// Label of each channel, `[0]` is CH1, `None` leaves the channel out of recordings
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LeadMapping(pub [Option<String>; CHANNELS]);
+
+impl LeadMapping {
+    /// `CH1` ~ `CH8`
+    pub fn numbered() -> Self {
+        LeadMapping(core::array::from_fn(|i| Some(format!("CH{}", i + 1))))
+    }
+
+    /// 12-lead wiring of the ADS1298ECGFE-PDK: V6, I, II, V2, V3, V4, V5, V1
+    ///
+    /// Leads III, aVR, aVL and aVF are derived from I and II and not recorded.
+    pub fn twelve_lead() -> Self {
+        let leads = ["V6", "I", "II", "V2", "V3", "V4", "V5", "V1"];
+        LeadMapping(leads.map(|lead| Some(format!("ECG {lead}"))))
+    }
+
+    pub fn label(&self, channel: u8) -> Option<&str> {
+        let index = usize::from(channel).checked_sub(1)?;
+        self.0.get(index)?.as_deref()
+    }
+}
+
+impl Default for LeadMapping {
+    fn default() -> Self {
+        Self::numbered()
+    }
+}
+
+/// A recorded channel and the scale of its codes
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signal {
+    /// In `1..=8`
+    pub channel: u8,
+    pub label: String,
+    /// PGA gain
+    pub gain: u8,
+    /// VREFP, in V
+    pub vref: f32,
+    /// Filters applied before recording, such as `"HP:0.5Hz LP:40Hz N:50Hz"`
+    pub prefiltering: String,
+}
+
+impl Signal {
+    /// Input referred voltage of one code, in µV
+    pub fn lsb_uv(&self) -> f64 {
+        // Scaled in f32 first, so 2.4 V is exactly 2 400 000 µV. `f64::from(2.4f32)` is 40 ppb
+        // high, enough to move the EDF physical maximum at gain 6 from 399987.8 to 399987.9.
+        f64::from(self.vref * 1e6) / f64::from(CODE_MAX) / f64::from(self.gain)
+    }
+}
+
+/// Start of a recording, in local time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StartTime {
+    pub year: u16,
+    /// In `1..=12`
+    pub month: u8,
+    /// In `1..=31`
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+/// What the recorded signals are and where they come from
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordingInfo {
+    /// EDF+ patient identification: code, sex, birthdate and name, `"X"` for unknown fields
+    pub patient: String,
+    /// EDF+ recording identification after the start date: admin code, technician and
+    /// equipment, `"X"` for unknown fields
+    pub recording: String,
+    pub start: StartTime,
+    /// In SPS
+    pub data_rate_hz: f32,
+    pub signals: Vec<Signal>,
+}
+
+impl RecordingInfo {
+    /// Signals of the channels labelled in `leads` and not powered down in `map`, with the
+    /// gains, VREF and data rate configured in `map`
+    pub fn from_register_map(
+        map: &RegisterMap,
+        clock: ClockSource,
+        leads: &LeadMapping,
+        start: StartTime,
+    ) -> Result<Self, RecordingError> {
+        let data_rate_hz = clock
+            .data_rate_hz(map.config1)
+            .ok_or(RecordingError::ReservedDataRate)?;
+        let settings = [
+            map.ch1set, map.ch2set, map.ch3set, map.ch4set, map.ch5set, map.ch6set, map.ch7set,
+            map.ch8set,
+        ];
+        let mut signals = vec![];
+        for (channel, setting) in (1..).zip(settings) {
+            let Some(label) = leads.label(channel) else {
+                continue;
+            };
+            if setting.pd() {
+                continue;
+            }
+            let gain = setting
+                .pga_gain()
+                .ok_or(RecordingError::InvalidGain(channel))?;
+            signals.push(Signal {
+                channel,
+                label: label.to_owned(),
+                gain,
+                vref: map.config3.vref(),
+                prefiltering: String::new(),
+            });
+        }
+        Ok(RecordingInfo {
+            patient: "X X X X".to_owned(),
+            recording: "X X X".to_owned(),
+            start,
+            data_rate_hz,
+            signals,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum RecordingError {
+    Io(io::Error),
+    /// Channel number is not in `1..=8`
+    InvalidChannel(u8),
+    /// `CHnSET` of the channel holds the reserved gain `111`
+    InvalidGain(u8),
+    /// `CONFIG1::dr` holds the reserved `111`
+    ReservedDataRate,
+    /// The data rate is not a whole number of samples per second, in SPS
+    UnsupportedDataRate(f32),
+    /// A header field or annotation is too long, or holds characters the format forbids
+    InvalidField(&'static str),
+    /// Nothing to record
+    NoSignals,
+}
+
+impl From<io::Error> for RecordingError {
+    fn from(e: io::Error) -> Self {
+        RecordingError::Io(e)
+    }
+}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordingError::Io(e) => write!(f, "failed to write the recording: {e}"),
+            RecordingError::InvalidChannel(channel) => write!(f, "invalid channel {channel}"),
+            RecordingError::InvalidGain(channel) => {
+                write!(f, "reserved PGA gain on channel {channel}")
+            }
+            RecordingError::ReservedDataRate => f.write_str("CONFIG1 holds the reserved data rate"),
+            RecordingError::UnsupportedDataRate(hz) => {
+                write!(f, "{hz} SPS is not a whole number of samples per second")
+            }
+            RecordingError::InvalidField(field) => write!(f, "invalid {field}"),
+            RecordingError::NoSignals => f.write_str("no signals to record"),
+        }
+    }
+}
+
+impl std::error::Error for RecordingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RecordingError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// `text` without the trailing zeros of its decimals
+pub(crate) fn trim_decimals(mut text: String) -> String {
+    if text.contains('.') {
+        let len = text.trim_end_matches('0').trim_end_matches('.').len();
+        text.truncate(len);
+    }
+    if text == "-0" {
+        text.remove(0);
+    }
+    text
+}
@@ -0,0 +1,144 @@
+//! WFDB (PhysioNet) export, a format 24 `.dat` signal file and its `.hea` header
+//!
+//! Samples are the 24-bit codes, interleaved in signal order. The gain of each signal is the
+//! number of codes per mV at its PGA gain and VREF, with a baseline of 0.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::{trim_decimals, RecordingError, RecordingInfo};
+use crate::driver::frame::{Frame, CHANNELS};
+
+/// 24-bit two's complement, little endian
+pub const FORMAT: u8 = 24;
+
+/// Writes frames to a WFDB record
+///
+/// The header is written by `finish`, once the number of samples and the checksums are known.
+pub struct WfdbWriter<D: Write, H: Write> {
+    dat: D,
+    hea: H,
+    record: String,
+    info: RecordingInfo,
+    /// Index in `Frame::channels` of each signal
+    channels: Vec<usize>,
+    samples: u64,
+    /// First sample of each signal
+    initial: Vec<i32>,
+    /// Sum of the samples of each signal, modulo 2¹⁶
+    checksums: Vec<i16>,
+}
+
+impl WfdbWriter<BufWriter<File>, File> {
+    /// Write `<directory>/<record>.dat` and `<directory>/<record>.hea`
+    pub fn create(
+        directory: impl AsRef<Path>,
+        record: &str,
+        info: &RecordingInfo,
+    ) -> Result<Self, RecordingError> {
+        check_record_name(record)?;
+        let directory = directory.as_ref();
+        let dat = BufWriter::new(File::create(directory.join(format!("{record}.dat")))?);
+        let hea = File::create(directory.join(format!("{record}.hea")))?;
+        Self::new(dat, hea, record, info)
+    }
+}
+
+impl<D: Write, H: Write> WfdbWriter<D, H> {
+    /// `record` is the record name, made of letters, digits, `_` and `-`
+    pub fn new(dat: D, hea: H, record: &str, info: &RecordingInfo) -> Result<Self, RecordingError> {
+        check_record_name(record)?;
+        if info.signals.is_empty() {
+            return Err(RecordingError::NoSignals);
+        }
+        let channels = info
+            .signals
+            .iter()
+            .map(|signal| match usize::from(signal.channel) {
+                channel @ 1..=CHANNELS => Ok(channel - 1),
+                _ => Err(RecordingError::InvalidChannel(signal.channel)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if info.signals.iter().any(|s| s.label.contains(['\n', '\r'])) {
+            return Err(RecordingError::InvalidField("label"));
+        }
+        Ok(WfdbWriter {
+            dat,
+            hea,
+            record: record.to_owned(),
+            info: info.clone(),
+            checksums: vec![0; channels.len()],
+            channels,
+            samples: 0,
+            initial: vec![],
+        })
+    }
+
+    /// Samples written per signal
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    pub fn write_frame(&mut self, frame: &Frame) -> Result<(), RecordingError> {
+        let mut bytes = Vec::with_capacity(3 * self.channels.len());
+        for (channel, checksum) in self.channels.iter().zip(&mut self.checksums) {
+            let code = frame.channels[*channel];
+            bytes.extend_from_slice(&code.to_le_bytes()[..3]);
+            *checksum = checksum.wrapping_add(code as i16);
+        }
+        self.dat.write_all(&bytes)?;
+        if self.initial.is_empty() {
+            self.initial = self.channels.iter().map(|c| frame.channels[*c]).collect();
+        }
+        self.samples += 1;
+        Ok(())
+    }
+
+    /// Write the header and return the signal and header writers
+    pub fn finish(mut self) -> Result<(D, H), RecordingError> {
+        self.dat.flush()?;
+        let header = self.header();
+        self.hea.write_all(header.as_bytes())?;
+        self.hea.flush()?;
+        Ok((self.dat, self.hea))
+    }
+
+    /// Contents of the `.hea` file for the samples written so far
+    pub fn header(&self) -> String {
+        let start = &self.info.start;
+        let mut header = format!(
+            "{} {} {} {} {:02}:{:02}:{:02} {:02}/{:02}/{:04}\n",
+            self.record,
+            self.channels.len(),
+            self.info.data_rate_hz,
+            self.samples,
+            start.hour,
+            start.minute,
+            start.second,
+            start.day,
+            start.month,
+            start.year,
+        );
+        for (i, signal) in self.info.signals.iter().enumerate() {
+            let codes_per_mv = 1000.0 / signal.lsb_uv();
+            header.push_str(&format!(
+                "{}.dat {FORMAT} {}(0)/mV 24 0 {} {} 0 {}\n",
+                self.record,
+                trim_decimals(format!("{codes_per_mv:.6}")),
+                self.initial.get(i).copied().unwrap_or(0),
+                self.checksums[i],
+                signal.label,
+            ));
+        }
+        header
+    }
+}
+
+fn check_record_name(record: &str) -> Result<(), RecordingError> {
+    let valid = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    if record.is_empty() || !record.chars().all(valid) {
+        return Err(RecordingError::InvalidField("record name"));
+    }
+    Ok(())
+}
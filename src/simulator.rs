@@ -0,0 +1,512 @@
+//! A simulated ADS1298 for host-side tests
+//!
+//! `Simulator` models the register file, the opcode state machine and synthetic conversion
+//! results. `Simulator::spi` implements `SpiDevice` and `Simulator::drdy` implements `InputPin`
+//! for `DRDY#`, both share the state with the `Simulator` they come from. With the `async`
+//! feature they also implement the `embedded-hal-async` traits, SPI transactions completing
+//! immediately and `DRDY#` waits yielding between polls.
+//!
+//! Every `DRDY#` poll while converting completes a new conversion instantly, so routines
+//! waiting for `DRDY#` never block.
+
+use core::convert::Infallible;
+use core::f32::consts::PI;
+#[cfg(feature = "async")]
+use core::task::Poll;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use embedded_hal::digital::{self, InputPin};
+use embedded_hal::spi::{self, Operation, SpiDevice};
+
+use crate::driver::clock::ClockSource;
+use crate::driver::frame::{CHANNELS, CODE_MAX, CODE_MIN, FRAME_SIZE};
+use crate::driver::registers::data::{ChSetReg, Config1Reg, Config2Reg, Config3Reg};
+pub use crate::driver::registers::map::REGISTER_COUNT;
+
+/// ID of an ADS1298
+pub const DEVICE_ID: u8 = 0x92;
+
+/// Reset values of the register file, see `Register`
+pub const RESET_VALUES: [u8; REGISTER_COUNT] = [
+    DEVICE_ID, 0x06, 0x40, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x0f, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+const ID: usize = 0x00;
+const CONFIG1: usize = 0x01;
+const CONFIG2: usize = 0x02;
+const CONFIG3: usize = 0x03;
+const CH1SET: usize = 0x05;
+const LOFF_STATP: usize = 0x12;
+const LOFF_STATN: usize = 0x13;
+const GPIO: usize = 0x14;
+const CONFIG4: usize = 0x17;
+
+/// Error returned by a transaction after `Simulator::inject_fault`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimulatorError;
+
+impl spi::Error for SimulatorError {
+    fn kind(&self) -> spi::ErrorKind {
+        spi::ErrorKind::Other
+    }
+}
+
+/// Command waiting for the `Read` operations following it
+#[derive(Clone, Copy)]
+enum Pending {
+    None,
+    RReg { address: usize, remaining: usize },
+    RData,
+}
+
+struct State {
+    clock: ClockSource,
+    registers: [u8; REGISTER_COUNT],
+    continuous_read: bool,
+    converting: bool,
+    standby: bool,
+    data_ready: bool,
+    sample: u64,
+    pending: Pending,
+    commands: Vec<u8>,
+    /// Transactions left before the injected fault
+    fault: Option<usize>,
+    input_short_offsets: [i32; CHANNELS],
+    normal_input_amplitude: f32,
+    temperature: f32,
+    avdd: f32,
+    avss: f32,
+    dvdd: f32,
+}
+
+impl State {
+    fn new(clock: ClockSource) -> Self {
+        State {
+            clock,
+            registers: RESET_VALUES,
+            continuous_read: true,
+            converting: false,
+            standby: false,
+            data_ready: false,
+            sample: 0,
+            pending: Pending::None,
+            commands: vec![],
+            fault: None,
+            input_short_offsets: [0; CHANNELS],
+            normal_input_amplitude: 1e-3,
+            temperature: 25.0,
+            avdd: 3.0,
+            avss: 0.0,
+            dvdd: 1.8,
+        }
+    }
+
+    fn command(&mut self, bytes: &[u8]) {
+        let mut i = bytes.iter().copied();
+        while let Some(opcode) = i.next() {
+            self.commands.push(opcode);
+            match opcode {
+                0x02 => self.standby = false,
+                0x04 => self.standby = true,
+                0x06 => {
+                    self.registers = RESET_VALUES;
+                    self.continuous_read = true;
+                    self.converting = false;
+                    self.data_ready = false;
+                }
+                // An unread frame keeps `DRDY#` low
+                0x08 => self.converting = true,
+                0x0a => self.converting = false,
+                0x10 => self.continuous_read = true,
+                0x11 => self.continuous_read = false,
+                0x12 => self.pending = Pending::RData,
+                0x20..=0x3f => {
+                    let n = usize::from(i.next().unwrap_or(0) & 0x1f);
+                    if !self.continuous_read {
+                        self.pending = Pending::RReg {
+                            address: usize::from(opcode & 0x1f),
+                            remaining: n + 1,
+                        };
+                    }
+                }
+                0x40..=0x5f => {
+                    let n = usize::from(i.next().unwrap_or(0) & 0x1f);
+                    let start = usize::from(opcode & 0x1f);
+                    for address in start..=start + n {
+                        let data = i.next();
+                        if let (Some(data), false) = (data, self.continuous_read) {
+                            self.write_register(address, data);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn write_register(&mut self, address: usize, data: u8) {
+        match address {
+            ID | LOFF_STATP | LOFF_STATN => {}
+            GPIO => {
+                // 输入引脚的 GPIOD 由外部决定
+                let inputs = data & 0x0f;
+                let outputs = data & !(inputs << 4) & 0xf0;
+                self.registers[GPIO] =
+                    (data & 0x0f) | outputs | (self.registers[GPIO] & (inputs << 4));
+            }
+            address if address < REGISTER_COUNT => self.registers[address] = data,
+            _ => {}
+        }
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) {
+        match self.pending {
+            Pending::RReg { address, remaining } => {
+                let n = remaining.min(buffer.len());
+                for (i, byte) in buffer[..n].iter_mut().enumerate() {
+                    *byte = self.registers.get(address + i).copied().unwrap_or(0);
+                }
+                buffer[n..].fill(0);
+                self.pending = match remaining - n {
+                    0 => Pending::None,
+                    remaining => Pending::RReg {
+                        address: address + n,
+                        remaining,
+                    },
+                };
+            }
+            Pending::RData => {
+                self.read_frame(buffer);
+                self.pending = Pending::None;
+            }
+            Pending::None if self.continuous_read => self.read_frame(buffer),
+            Pending::None => buffer.fill(0),
+        }
+    }
+
+    fn read_frame(&mut self, buffer: &mut [u8]) {
+        let frame = self.frame();
+        let n = buffer.len().min(FRAME_SIZE);
+        buffer[..n].copy_from_slice(&frame[..n]);
+        buffer[n..].fill(0);
+        self.data_ready = false;
+    }
+
+    /// Complete a conversion if converting, returns the level of `DRDY#`
+    fn poll_data_ready(&mut self) -> bool {
+        if !self.data_ready && self.converting && !self.standby {
+            self.sample += 1;
+            self.data_ready = true;
+            if self.registers[CONFIG4] & 0b1000 != 0 {
+                self.converting = false;
+            }
+        }
+        !self.data_ready
+    }
+
+    /// Differential input of `channel` in `0..8`, in V
+    fn input(&self, channel: usize, setting: ChSetReg) -> f32 {
+        let vref = Config3Reg(self.registers[CONFIG3]).vref();
+        let lsb = vref / CODE_MAX as f32;
+        match setting.mux() {
+            0b000 => {
+                let phase = 2.0 * PI * (self.sample % 50) as f32 / 50.0;
+                self.normal_input_amplitude * phase.sin()
+            }
+            0b001 => {
+                let noise = if self.sample.is_multiple_of(2) { 1 } else { -1 };
+                (self.input_short_offsets[channel] + noise) as f32 * lsb
+            }
+            0b011 if channel == 2 || channel == 3 => self.dvdd / 4.0,
+            0b011 => (self.avdd + self.avss) / 2.0,
+            0b100 => 145_300e-6 + 490e-6 * (self.temperature - 25.0),
+            0b101 => {
+                let config2 = Config2Reg(self.registers[CONFIG2]);
+                let amplitude = vref / 2400.0 * if config2.test_amp() { 2.0 } else { 1.0 };
+                let data_rate = self.clock.data_rate_hz(Config1Reg(self.registers[CONFIG1]));
+                let (Some(data_rate), Some(frequency @ 0.1..)) =
+                    (data_rate, self.clock.test_signal_hz(config2))
+                else {
+                    return amplitude;
+                };
+                let half_period = (data_rate / frequency / 2.0).max(1.0) as u64;
+                if (self.sample / half_period).is_multiple_of(2) {
+                    amplitude
+                } else {
+                    -amplitude
+                }
+            }
+            _ => 0.0,
+        }
+    }
+
+    fn frame(&self) -> [u8; FRAME_SIZE] {
+        let statp = self.registers[LOFF_STATP];
+        let statn = self.registers[LOFF_STATN];
+        let gpio = self.registers[GPIO];
+        let mut frame = [0u8; FRAME_SIZE];
+        frame[0] = 0xc0 | statp >> 4;
+        frame[1] = statp << 4 | statn >> 4;
+        frame[2] = statn << 4 | gpio >> 4;
+        let vref = Config3Reg(self.registers[CONFIG3]).vref();
+        for (channel, data) in frame[3..].chunks_exact_mut(3).enumerate() {
+            let setting = ChSetReg(self.registers[CH1SET + channel]);
+            let code = match (setting.pd(), setting.pga_gain()) {
+                (false, Some(gain)) => {
+                    let code = self.input(channel, setting) * f32::from(gain) / vref;
+                    (code * CODE_MAX as f32).round() as i32
+                }
+                _ => 0,
+            };
+            let code = code.clamp(CODE_MIN, CODE_MAX) as u32;
+            data.copy_from_slice(&code.to_be_bytes()[1..]);
+        }
+        frame
+    }
+}
+
+/// A simulated ADS1298
+#[derive(Clone)]
+pub struct Simulator {
+    state: Rc<RefCell<State>>,
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Simulator {
+    /// A powered-up device in `RDATAC` mode with reset register values
+    pub fn new() -> Self {
+        Self::with_clock(ClockSource::Internal)
+    }
+
+    /// Like `new`, running from `clock`
+    pub fn with_clock(clock: ClockSource) -> Self {
+        Simulator {
+            state: Rc::new(RefCell::new(State::new(clock))),
+        }
+    }
+
+    pub fn spi(&self) -> SimulatedSpi {
+        SimulatedSpi {
+            state: self.state.clone(),
+        }
+    }
+
+    pub fn drdy(&self) -> SimulatedDrdy {
+        SimulatedDrdy {
+            state: self.state.clone(),
+        }
+    }
+
+    pub fn register(&self, address: u8) -> u8 {
+        self.state.borrow().registers[usize::from(address)]
+    }
+
+    /// Set a register bypassing SPI, including read-only ones
+    pub fn set_register(&self, address: u8, data: u8) {
+        self.state.borrow_mut().registers[usize::from(address)] = data;
+    }
+
+    pub fn registers(&self) -> [u8; REGISTER_COUNT] {
+        self.state.borrow().registers
+    }
+
+    /// First byte of every opcode received
+    pub fn commands(&self) -> Vec<u8> {
+        self.state.borrow().commands.clone()
+    }
+
+    pub fn is_continuous_read(&self) -> bool {
+        self.state.borrow().continuous_read
+    }
+
+    pub fn is_converting(&self) -> bool {
+        self.state.borrow().converting
+    }
+
+    pub fn is_standby(&self) -> bool {
+        self.state.borrow().standby
+    }
+
+    /// Number of conversions completed
+    pub fn samples(&self) -> u64 {
+        self.state.borrow().sample
+    }
+
+    /// Make the next transaction fail with `SimulatorError`
+    pub fn inject_fault(&self) {
+        self.inject_fault_after(0);
+    }
+
+    /// Make the transaction after the next `transactions` ones fail with `SimulatorError`
+    pub fn inject_fault_after(&self, transactions: usize) {
+        self.state.borrow_mut().fault = Some(transactions);
+    }
+
+    /// Offset of each channel with shorted inputs, in codes at gain `1`
+    pub fn set_input_short_offsets(&self, offsets: [i32; CHANNELS]) {
+        self.state.borrow_mut().input_short_offsets = offsets;
+    }
+
+    /// Amplitude of the sine on the electrode inputs, in V
+    pub fn set_normal_input_amplitude(&self, amplitude: f32) {
+        self.state.borrow_mut().normal_input_amplitude = amplitude;
+    }
+
+    /// Die temperature, in °C
+    pub fn set_temperature(&self, temperature: f32) {
+        self.state.borrow_mut().temperature = temperature;
+    }
+
+    /// Supplies, in V
+    pub fn set_supplies(&self, avdd: f32, avss: f32, dvdd: f32) {
+        let mut state = self.state.borrow_mut();
+        state.avdd = avdd;
+        state.avss = avss;
+        state.dvdd = dvdd;
+    }
+
+    /// Set `LOFF_STATP` and `LOFF_STATN`, reported in the status word
+    pub fn set_lead_off(&self, statp: u8, statn: u8) {
+        let mut state = self.state.borrow_mut();
+        state.registers[LOFF_STATP] = statp;
+        state.registers[LOFF_STATN] = statn;
+    }
+
+    /// Drive the GPIO pins configured as input, `levels` bit 0 is GPIO1
+    pub fn set_gpio_inputs(&self, levels: u8) {
+        let mut state = self.state.borrow_mut();
+        let inputs = state.registers[GPIO] & 0x0f;
+        state.registers[GPIO] = (state.registers[GPIO] & !(inputs << 4)) | (levels & inputs) << 4;
+    }
+}
+
+/// `SpiDevice` of a `Simulator`
+pub struct SimulatedSpi {
+    state: Rc<RefCell<State>>,
+}
+
+impl spi::ErrorType for SimulatedSpi {
+    type Error = SimulatorError;
+}
+
+impl SpiDevice for SimulatedSpi {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        let mut state = self.state.borrow_mut();
+        match state.fault {
+            Some(0) => {
+                state.fault = None;
+                return Err(SimulatorError);
+            }
+            Some(n) => state.fault = Some(n - 1),
+            None => {}
+        }
+        for operation in operations {
+            match operation {
+                Operation::Write(bytes) => state.command(bytes),
+                Operation::Read(buffer) => state.read(buffer),
+                Operation::Transfer(read, write) => {
+                    state.command(write);
+                    state.read(read);
+                }
+                Operation::TransferInPlace(buffer) => {
+                    let bytes = buffer.to_vec();
+                    state.command(&bytes);
+                    state.read(buffer);
+                }
+                Operation::DelayNs(_) => {}
+            }
+        }
+        state.pending = Pending::None;
+        Ok(())
+    }
+}
+
+/// `DRDY#` of a `Simulator`
+pub struct SimulatedDrdy {
+    state: Rc<RefCell<State>>,
+}
+
+impl digital::ErrorType for SimulatedDrdy {
+    type Error = Infallible;
+}
+
+impl InputPin for SimulatedDrdy {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.state.borrow_mut().poll_data_ready())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.is_high()?)
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::spi::SpiDevice for SimulatedSpi {
+    async fn transaction(
+        &mut self,
+        operations: &mut [Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        SpiDevice::transaction(self, operations)
+    }
+}
+
+/// Each poll samples `DRDY#` once and returns `Pending` otherwise, waking the task right away
+///
+/// A frame left unread keeps `DRDY#` low. While converting, waiting for `high` sees the pulse
+/// before the next conversion, which overwrites that frame. Waiting for `high` never completes
+/// while a frame is unread and conversion is stopped, nor `low` while conversion is stopped.
+#[cfg(feature = "async")]
+impl embedded_hal_async::digital::Wait for SimulatedDrdy {
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        core::future::poll_fn(|cx| {
+            let mut state = self.state.borrow_mut();
+            if state.data_ready && state.converting && !state.standby {
+                state.data_ready = false;
+            }
+            if state.data_ready {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            } else {
+                Poll::Ready(Ok(()))
+            }
+        })
+        .await
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        core::future::poll_fn(|cx| {
+            if self.state.borrow_mut().poll_data_ready() {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            } else {
+                Poll::Ready(Ok(()))
+            }
+        })
+        .await
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_low().await?;
+        self.wait_for_high().await
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        self.wait_for_high().await?;
+        self.wait_for_low().await
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        if self.is_high()? {
+            self.wait_for_low().await
+        } else {
+            self.wait_for_high().await
+        }
+    }
+}
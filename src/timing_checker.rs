@@ -0,0 +1,369 @@
+//! Transaction-level SPI compliance checker for tests
+//!
+//! `TimingChecker` wraps a `SpiDevice`, records every `Operation` the driver issues and checks
+//! it against the ADS1298 command timing and sequence rules.
+
+use core::fmt;
+
+use embedded_hal::spi::{ErrorType, Operation, SpiDevice};
+
+use crate::driver::clock::ClockSource;
+use crate::driver::frame::FRAME_SIZE;
+
+/// Minimum `CS#` low to first `SCLK`, in ns
+pub const MIN_CS_TO_SCLK_NS: u32 = 6;
+
+/// SPI bus settings the driver runs with, taken from the `SpiDevice` configuration
+#[derive(Clone, Copy, Debug)]
+pub struct BusTiming {
+    /// `CS#` low to first `SCLK`, in ns
+    pub cs_to_sclk_ns: u32,
+    /// Last `SCLK` to `CS#` high, in ns, at least 4 tCLK
+    pub sclk_to_cs_ns: u32,
+    /// `SCLK` frequency, in Hz
+    pub sclk_hz: u32,
+}
+
+/// An `Operation` as recorded by `TimingChecker`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RecordedOperation {
+    Write(Vec<u8>),
+    /// Number of bytes read
+    Read(usize),
+    Transfer {
+        write: Vec<u8>,
+        read: usize,
+    },
+    TransferInPlace(Vec<u8>),
+    DelayNs(u32),
+}
+
+/// A broken rule
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Rule {
+    /// `CS#` low to first `SCLK` too short
+    CsToSclk { actual_ns: u32, required_ns: u32 },
+    /// Last `SCLK` to `CS#` high too short
+    SclkToCs { actual_ns: u32, required_ns: u32 },
+    /// A byte of a multi-byte command is shorter than the 4 tCLK decode time
+    CommandDecode { sclk_hz: u32, max_sclk_hz: u32 },
+    /// Opcode not followed by its tCLK wait in the same transaction
+    CommandDelay {
+        opcode: u8,
+        delay_ns: u32,
+        required_ns: u32,
+    },
+    /// `RREG`, `WREG` or `RDATA` while in `RDATAC` mode, issue `SDATAC` first
+    CommandInContinuousRead { opcode: u8 },
+    /// Any command but `WAKEUP` while in standby
+    CommandInStandby { opcode: u8 },
+    /// `WREG` with fewer data bytes than its `n + 1`
+    IncompleteCommand { opcode: u8 },
+    /// Bytes read after `RREG` or `RDATA` do not match the command
+    ReadLength {
+        opcode: u8,
+        expected: usize,
+        actual: usize,
+    },
+    /// Byte not decoded as an opcode
+    UnknownOpcode { opcode: u8 },
+}
+
+/// A broken rule and the index of the transaction breaking it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    /// Index in `TimingChecker::transactions`, `None` for bus settings
+    pub transaction: Option<usize>,
+    pub rule: Rule,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.transaction {
+            Some(i) => write!(f, "transaction {i}: {:?}", self.rule),
+            None => write!(f, "bus: {:?}", self.rule),
+        }
+    }
+}
+
+/// A recording `SpiDevice` checking the ADS1298 rules
+pub struct TimingChecker<SPI> {
+    spi: SPI,
+    tclk_ns: u32,
+    bus_timing: Option<BusTiming>,
+    continuous_read: bool,
+    standby: bool,
+    transactions: Vec<Vec<RecordedOperation>>,
+    violations: Vec<Violation>,
+}
+
+impl<SPI> TimingChecker<SPI> {
+    /// Wrap `spi` of a device powered up in `RDATAC` mode, running at 2.048 MHz
+    pub fn new(spi: SPI) -> Self {
+        TimingChecker {
+            spi,
+            tclk_ns: ClockSource::Internal.tclk_ns(),
+            bus_timing: None,
+            continuous_read: true,
+            standby: false,
+            transactions: vec![],
+            violations: vec![],
+        }
+    }
+
+    /// Use the tCLK of another master clock, in ns
+    pub fn with_tclk_ns(mut self, tclk_ns: u32) -> Self {
+        self.tclk_ns = tclk_ns;
+        self
+    }
+
+    /// Use the tCLK of `clock`
+    pub fn with_clock(self, clock: ClockSource) -> Self {
+        self.with_tclk_ns(clock.tclk_ns())
+    }
+
+    /// Check the bus settings of `spi`, against the tCLK in use when checking
+    pub fn with_bus_timing(mut self, timing: BusTiming) -> Self {
+        self.bus_timing = Some(timing);
+        self
+    }
+
+    pub fn transactions(&self) -> &[Vec<RecordedOperation>] {
+        &self.transactions
+    }
+
+    /// Violations of the bus settings, then of the transactions in order
+    pub fn violations(&self) -> Vec<Violation> {
+        let mut violations = self.bus_violations();
+        violations.extend_from_slice(&self.violations);
+        violations
+    }
+
+    pub fn is_compliant(&self) -> bool {
+        self.violations().is_empty()
+    }
+
+    /// Panic listing every violation
+    pub fn assert_compliant(&self) {
+        if !self.is_compliant() {
+            let violations: Vec<_> = self.violations().iter().map(|v| v.to_string()).collect();
+            panic!("SPI rules broken:\n{}", violations.join("\n"));
+        }
+    }
+
+    /// Forget the transactions and their violations, the bus settings are kept
+    pub fn clear(&mut self) {
+        self.transactions.clear();
+        self.violations.clear();
+    }
+
+    pub fn into_inner(self) -> SPI {
+        self.spi
+    }
+
+    fn bus_violations(&self) -> Vec<Violation> {
+        let Some(timing) = self.bus_timing else {
+            return vec![];
+        };
+        let mut rules = vec![];
+        if timing.cs_to_sclk_ns < MIN_CS_TO_SCLK_NS {
+            rules.push(Rule::CsToSclk {
+                actual_ns: timing.cs_to_sclk_ns,
+                required_ns: MIN_CS_TO_SCLK_NS,
+            });
+        }
+        if timing.sclk_to_cs_ns < 4 * self.tclk_ns {
+            rules.push(Rule::SclkToCs {
+                actual_ns: timing.sclk_to_cs_ns,
+                required_ns: 4 * self.tclk_ns,
+            });
+        }
+        // 8 SCLK of a byte must last 4 tCLK
+        let max_sclk_hz = (2_000_000_000u64 / u64::from(self.tclk_ns)) as u32;
+        if timing.sclk_hz > max_sclk_hz {
+            rules.push(Rule::CommandDecode {
+                sclk_hz: timing.sclk_hz,
+                max_sclk_hz,
+            });
+        }
+        rules
+            .into_iter()
+            .map(|rule| Violation {
+                transaction: None,
+                rule,
+            })
+            .collect()
+    }
+
+    fn check(&mut self, index: usize) {
+        let mut checker = TransactionChecker {
+            index,
+            tclk_ns: self.tclk_ns,
+            continuous_read: &mut self.continuous_read,
+            standby: &mut self.standby,
+            violations: &mut self.violations,
+            delay: None,
+            read: None,
+        };
+        for operation in &self.transactions[index] {
+            checker.operation(operation);
+        }
+        checker.finish();
+    }
+}
+
+/// Checks the operations of one transaction in order
+struct TransactionChecker<'a> {
+    index: usize,
+    tclk_ns: u32,
+    continuous_read: &'a mut bool,
+    standby: &'a mut bool,
+    violations: &'a mut Vec<Violation>,
+    /// Opcode waiting for its delay and the required delay
+    delay: Option<(u8, u32)>,
+    /// Opcode waiting for `Read`, expected and read bytes
+    read: Option<(u8, usize, usize)>,
+}
+
+impl TransactionChecker<'_> {
+    fn violation(&mut self, rule: Rule) {
+        self.violations.push(Violation {
+            transaction: Some(self.index),
+            rule,
+        });
+    }
+
+    fn operation(&mut self, operation: &RecordedOperation) {
+        if let RecordedOperation::DelayNs(ns) = operation {
+            if let Some((opcode, required_ns)) = self.delay.take() {
+                if *ns < required_ns {
+                    self.violation(Rule::CommandDelay {
+                        opcode,
+                        delay_ns: *ns,
+                        required_ns,
+                    });
+                }
+            }
+            return;
+        }
+        self.missing_delay();
+        match operation {
+            RecordedOperation::Write(bytes) => self.commands(bytes),
+            RecordedOperation::Read(n) => self.bytes_read(*n),
+            RecordedOperation::Transfer { write, read } => {
+                self.commands(write);
+                self.bytes_read(*read);
+            }
+            RecordedOperation::TransferInPlace(bytes) => self.commands(bytes),
+            RecordedOperation::DelayNs(_) => {}
+        }
+    }
+
+    fn finish(&mut self) {
+        self.missing_delay();
+        self.read_finished();
+    }
+
+    fn missing_delay(&mut self) {
+        if let Some((opcode, required_ns)) = self.delay.take() {
+            self.violation(Rule::CommandDelay {
+                opcode,
+                delay_ns: 0,
+                required_ns,
+            });
+        }
+    }
+
+    fn bytes_read(&mut self, n: usize) {
+        if let Some((_, _, read)) = &mut self.read {
+            *read += n;
+        }
+    }
+
+    fn read_finished(&mut self) {
+        if let Some((opcode, expected, actual)) = self.read.take() {
+            if expected != actual {
+                self.violation(Rule::ReadLength {
+                    opcode,
+                    expected,
+                    actual,
+                });
+            }
+        }
+    }
+
+    fn commands(&mut self, bytes: &[u8]) {
+        let mut i = bytes.iter().copied();
+        while let Some(opcode) = i.next() {
+            self.read_finished();
+            if *self.standby && opcode != 0x02 {
+                self.violation(Rule::CommandInStandby { opcode });
+            }
+            let register_access = matches!(opcode, 0x12 | 0x20..=0x5f);
+            if *self.continuous_read && register_access {
+                self.violation(Rule::CommandInContinuousRead { opcode });
+            }
+            match opcode {
+                0x02 => {
+                    *self.standby = false;
+                    self.delay = Some((opcode, 4 * self.tclk_ns));
+                }
+                0x04 => *self.standby = true,
+                0x06 => {
+                    *self.continuous_read = true;
+                    self.delay = Some((opcode, 18 * self.tclk_ns));
+                }
+                0x08 => self.delay = Some((opcode, 4 * self.tclk_ns)),
+                0x0a => {}
+                0x10 => *self.continuous_read = true,
+                0x11 => {
+                    *self.continuous_read = false;
+                    self.delay = Some((opcode, 4 * self.tclk_ns));
+                }
+                0x12 => self.read = Some((opcode, FRAME_SIZE, 0)),
+                0x20..=0x3f => {
+                    let n = i.next().map(|n| usize::from(n & 0x1f) + 1);
+                    match n {
+                        Some(n) => self.read = Some((opcode, n, 0)),
+                        None => self.violation(Rule::IncompleteCommand { opcode }),
+                    }
+                }
+                0x40..=0x5f => {
+                    let n = i.next().map(|n| usize::from(n & 0x1f) + 1);
+                    let data = n.map(|n| i.by_ref().take(n).count() == n);
+                    if data != Some(true) {
+                        self.violation(Rule::IncompleteCommand { opcode });
+                    }
+                }
+                _ => self.violation(Rule::UnknownOpcode { opcode }),
+            }
+        }
+    }
+}
+
+impl<SPI: ErrorType> ErrorType for TimingChecker<SPI> {
+    type Error = SPI::Error;
+}
+
+impl<SPI: SpiDevice> SpiDevice for TimingChecker<SPI> {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        let recorded = operations
+            .iter()
+            .map(|operation| match operation {
+                Operation::Write(bytes) => RecordedOperation::Write(bytes.to_vec()),
+                Operation::Read(buffer) => RecordedOperation::Read(buffer.len()),
+                Operation::Transfer(read, write) => RecordedOperation::Transfer {
+                    write: write.to_vec(),
+                    read: read.len(),
+                },
+                Operation::TransferInPlace(buffer) => {
+                    RecordedOperation::TransferInPlace(buffer.to_vec())
+                }
+                Operation::DelayNs(ns) => RecordedOperation::DelayNs(*ns),
+            })
+            .collect();
+        self.transactions.push(recorded);
+        self.check(self.transactions.len() - 1);
+        self.spi.transaction(operations)
+    }
+}
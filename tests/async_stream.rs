@@ -0,0 +1,102 @@
+use core::pin::pin;
+use std::future::Future;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use ads1298_rs::driver::initialization::{Default8Lead1x500, Initializer};
+use ads1298_rs::driver::{StreamError, ADS1298};
+use ads1298_rs::simulator::Simulator;
+use embedded_hal_async::digital::Wait;
+use futures_util::StreamExt;
+
+/// Polls until ready, the simulator wakes the task whenever it returns `Pending`
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut context = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
+
+#[test]
+fn frames_are_streamed() {
+    let simulator = Simulator::new();
+    let mut driver = ADS1298::new(simulator.spi());
+    driver.init(Default8Lead1x500).unwrap();
+
+    let before = simulator.samples();
+    let frames: Vec<_> = block_on(
+        driver
+            .async_stream_reader(simulator.drdy())
+            .samples(4)
+            .collect(),
+    );
+    assert_eq!(frames.len(), 4);
+    assert!(frames.iter().all(Result::is_ok));
+    assert_eq!(simulator.samples(), before + 4);
+
+    // 500 SPS
+    let frames = driver
+        .async_stream_reader(simulator.drdy())
+        .for_duration(Duration::from_millis(20))
+        .unwrap();
+    assert_eq!(block_on(frames.count()), 10);
+
+    simulator.set_lead_off(0b0000_0001, 0);
+    let reader = driver.async_stream_reader(simulator.drdy());
+    assert_eq!(block_on(reader.until_lead_off().count()), 0);
+
+    simulator.inject_fault();
+    let mut frames = pin!(driver.async_stream_reader(simulator.drdy()).into_stream());
+    assert!(matches!(
+        block_on(frames.next()),
+        Some(Err(StreamError::StreamingAbort(_)))
+    ));
+    assert!(matches!(block_on(frames.next()), Some(Ok(_))));
+}
+
+#[test]
+fn driver_offsets_are_subtracted() {
+    let frame = |offsets| {
+        let simulator = Simulator::new();
+        let mut driver = ADS1298::new(simulator.spi());
+        driver.init(Default8Lead1x500).unwrap();
+        driver.set_offsets(offsets);
+        let mut reader = driver.async_stream_reader(simulator.drdy());
+        block_on(reader.read_frame()).unwrap()
+    };
+    let raw = frame([0; 8]);
+    let corrected = frame([1, 2, 3, 4, 5, 6, 7, 8]);
+    for (i, (raw, corrected)) in (1..).zip(raw.channels.iter().zip(corrected.channels)) {
+        assert_eq!(raw - corrected, i);
+    }
+}
+
+#[test]
+fn drdy_waits_yield_and_see_the_next_conversion() {
+    let simulator = Simulator::new();
+    let mut drdy = simulator.drdy();
+    let mut context = Context::from_waker(Waker::noop());
+
+    // Idle, `DRDY#` stays high
+    {
+        let mut low = pin!(drdy.wait_for_low());
+        assert!(low.as_mut().poll(&mut context).is_pending());
+        assert!(low.as_mut().poll(&mut context).is_pending());
+    }
+
+    ADS1298::new(simulator.spi())
+        .init(Default8Lead1x500)
+        .unwrap();
+    block_on(drdy.wait_for_low()).unwrap();
+    let before = simulator.samples();
+    // The unread frame is overwritten by the next conversion
+    block_on(drdy.wait_for_falling_edge()).unwrap();
+    assert_eq!(simulator.samples(), before + 1);
+    block_on(drdy.wait_for_rising_edge()).unwrap();
+    assert_eq!(simulator.samples(), before + 1);
+    block_on(drdy.wait_for_low()).unwrap();
+    assert_eq!(simulator.samples(), before + 2);
+}
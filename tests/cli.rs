@@ -0,0 +1,134 @@
+use ads1298_rs::driver::stream_reader::FrameSource;
+use ads1298_rs::recording::capture::Replay;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn cli(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ads1298-cli"))
+        .arg("--simulate")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn dump_decodes_registers() {
+    let output = cli(&["dump"]);
+    assert!(output.status.success());
+    let text = stdout(&output);
+    assert_eq!(text.lines().count(), 26);
+    assert!(text.starts_with("ID         00h = 92h IdReg {"));
+
+    let output = cli(&["dump", "--format", "json"]);
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["config1"], 0x06);
+}
+
+#[test]
+fn write_shows_the_change_and_refuses_read_only_registers() {
+    let output = cli(&["write", "config1", "0x86"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).starts_with("CONFIG1 01h: 06h -> 86h (changed bits 10000000)"));
+
+    let output = cli(&["write", "0x01", "6"]);
+    assert_eq!(stdout(&output), "CONFIG1 01h unchanged\n");
+
+    let output = cli(&["write", "ID", "0"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "error: ID is read-only\n"
+    );
+
+    assert!(!cli(&["write", "CH9SET", "0"]).status.success());
+}
+
+#[test]
+fn self_test_passes() {
+    let output = cli(&["self-test", "--samples", "16"]);
+    assert!(output.status.success());
+    let text = stdout(&output);
+    assert!(text.starts_with("ID   92h ok\n"));
+    assert_eq!(text.lines().filter(|line| line.contains(" ok ")).count(), 8);
+    assert!(text.ends_with("PASS\n"));
+}
+
+#[test]
+fn stream_writes_csv_to_stdout() {
+    let output = cli(&["stream", "--frames", "5", "--leads", "12-lead"]);
+    assert!(output.status.success());
+    let text = stdout(&output);
+    let mut lines = text.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "index,time_s,ECG V6,ECG I,ECG II,ECG V2,ECG V3,ECG V4,ECG V5,ECG V1,loff_statp,loff_statn,gpio"
+    );
+    assert_eq!(lines.count(), 5);
+    assert!(text.lines().nth(2).unwrap().starts_with("1,0.002000,"));
+}
+
+/// A path in the temporary directory unique to this run, removed when dropped
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str) -> Self {
+        let name = format!("ads1298-rs-cli-{}-{name}", std::process::id());
+        TempFile(std::env::temp_dir().join(name))
+    }
+
+    fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn stream_writes_recordings_to_files() {
+    let bdf = TempFile::new("stream.bdf");
+    let output = cli(&["stream", "--seconds", "2", "--format", "bdf", "-o"]);
+    assert!(!output.status.success());
+    let output = cli(&[
+        "stream",
+        "--seconds",
+        "2",
+        "--format",
+        "bdf",
+        "-o",
+        bdf.path(),
+    ]);
+    assert!(output.status.success());
+    let bytes = std::fs::read(bdf.path()).unwrap();
+    assert_eq!(&bytes[..8], b"\xffBIOSEMI");
+    assert_eq!(&bytes[236..244], b"2       ");
+
+    let capture = TempFile::new("stream.capture");
+    let output = cli(&[
+        "stream",
+        "--frames",
+        "7",
+        "--format",
+        "capture",
+        "-o",
+        capture.path(),
+    ]);
+    assert!(output.status.success());
+    let mut replay = Replay::open(capture.path()).unwrap();
+    assert_eq!(replay.data_rate_hz().unwrap(), 500.0);
+    assert_eq!(replay.count(), 7);
+
+    let output = cli(&["stream", "--frames", "7", "--format", "edf"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "error: --output is required for bdf, edf and capture\n"
+    );
+}
@@ -0,0 +1,78 @@
+use ads1298_rs::decimation::{DecimationError, Decimator};
+use ads1298_rs::driver::frame::Frame;
+use ads1298_rs::driver::registers::data::DataStatus;
+
+const INPUT: f32 = 32_000.0;
+const OUTPUT: f32 = 500.0;
+
+fn frame(code: i32, loff_statp: u8) -> Frame {
+    Frame {
+        status: DataStatus::new(loff_statp, 0, 0),
+        channels: [code; 8],
+    }
+}
+
+/// Peak of the output for a sine of `hz` at the input, after one second of settling
+fn output_peak(hz: f32) -> f32 {
+    let mut decimator = Decimator::new(INPUT, OUTPUT).unwrap();
+    let outputs: Vec<_> = (0..2 * INPUT as usize)
+        .filter_map(|n| {
+            let x = 100_000.0 * (2.0 * std::f32::consts::PI * hz * n as f32 / INPUT).sin();
+            decimator.process(&frame(x as i32, 0))
+        })
+        .collect();
+    assert_eq!(outputs.len(), 2 * OUTPUT as usize);
+    outputs[OUTPUT as usize..]
+        .iter()
+        .map(|f| f.channels[0].abs() as f32 / 100_000.0)
+        .fold(0.0, f32::max)
+}
+
+#[test]
+fn decimates_with_anti_aliasing() {
+    let decimator = Decimator::new(INPUT, OUTPUT).unwrap();
+    assert_eq!(decimator.factor(), 64);
+
+    assert!((output_peak(10.0) - 1.0).abs() < 0.01);
+    assert!((output_peak(100.0) - 1.0).abs() < 0.02);
+    // Would alias to 200 Hz and 100 Hz
+    assert!(output_peak(700.0) < 0.005);
+    assert!(output_peak(8_100.0) < 0.005);
+}
+
+#[test]
+fn lead_off_flags_are_ored_across_the_window() {
+    let mut decimator = Decimator::new(INPUT, OUTPUT).unwrap();
+    let outputs: Vec<_> = (0..64 * 3)
+        .filter_map(|n| decimator.process(&frame(1000, if n == 70 { 0b0100_0001 } else { 0 })))
+        .collect();
+    assert_eq!(outputs.len(), 3);
+    assert_eq!(outputs[0].status.loff_statp(), 0);
+    assert_eq!(outputs[1].status.loff_statp(), 0b0100_0001);
+    assert_eq!(outputs[1].status.ds1.0 & 0xf0, 0xc0);
+    assert_eq!(outputs[2].status.loff_statp(), 0);
+}
+
+#[test]
+fn ratios_split_into_stages() {
+    for (input, output, factor) in [
+        (32_000.0, 125.0, 256),
+        (16_000.0, 2_000.0, 8),
+        (4_000.0, 4_000.0, 1),
+    ] {
+        assert_eq!(Decimator::new(input, output).unwrap().factor(), factor);
+    }
+    for (input, output) in [
+        (32_000.0, 3_000.0),
+        (32_000.0, 64_000.0),
+        (22_000.0, 2_000.0),
+    ] {
+        assert_eq!(
+            Decimator::new(input, output).err(),
+            Some(DecimationError::UnsupportedRatio {
+                input_hz: input,
+                output_hz: output
+            })
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use ads1298_rs::driver::frame::Frame;
+use ads1298_rs::driver::registers::data::DataStatus;
+use ads1298_rs::filters::ecg::MAX_SECTIONS;
+use ads1298_rs::filters::{
+    Cascade, ChannelFilters, EcgFilterConfig, Filter, FilterError, Fir, LowPass, Mains,
+};
+
+const RATE: f32 = 500.0;
+
+/// Peak of the output for a sine of `hz` after two seconds of settling
+fn sine_peak<F: Filter>(
+    filter: &mut F,
+    hz: f32,
+    amplitude: f32,
+    convert: fn(f32) -> F::Sample,
+) -> f32
+where
+    F::Sample: Into<f64>,
+{
+    let mut peak = 0.0f64;
+    for n in 0..(4.0 * RATE) as usize {
+        let x = amplitude * (2.0 * std::f32::consts::PI * hz * n as f32 / RATE).sin();
+        let y: f64 = filter.process(convert(x)).into();
+        if n as f32 >= 2.0 * RATE {
+            peak = peak.max(y.abs());
+        }
+    }
+    peak as f32
+}
+
+#[test]
+fn monitoring_cascade_notches_mains_and_keeps_the_band() {
+    let cascade = EcgFilterConfig::monitoring(Mains::Hz50)
+        .design(RATE)
+        .unwrap();
+    assert_eq!(cascade.sections().len(), 4);
+    assert!((cascade.gain(RATE, 10.0) - 1.0).abs() < 0.02);
+    assert!(cascade.gain(RATE, 50.0) < 1e-3);
+    assert!(cascade.gain(RATE, 100.0) < 0.05);
+    assert!(cascade.gain(RATE, 0.05) < 0.02);
+
+    for (hz, min, max) in [(10.0, 0.97, 1.03), (50.0, 0.0, 0.01)] {
+        let peak = sine_peak(&mut cascade.clone(), hz, 1e5, |x| x) / 1e5;
+        assert!((min..max).contains(&peak), "{hz} Hz: {peak}");
+        let peak = sine_peak(&mut cascade.to_fixed(), hz, 1e5, |x| x as i32) / 1e5;
+        assert!((min..max).contains(&peak), "fixed {hz} Hz: {peak}");
+    }
+}
+
+#[test]
+fn designs_follow_the_data_rate() {
+    let diagnostic = EcgFilterConfig::diagnostic(Mains::Hz60);
+    assert_eq!(
+        diagnostic.design(2000.0).unwrap().sections().len(),
+        MAX_SECTIONS
+    );
+    assert!(matches!(
+        diagnostic.design(250.0),
+        Err(FilterError::AboveNyquist { hz: 150.0, .. })
+    ));
+
+    // 150 Hz is above Nyquist and skipped
+    let config = EcgFilterConfig {
+        low_pass: Some(LowPass::Monitoring),
+        ..EcgFilterConfig::diagnostic(Mains::Hz50)
+    };
+    assert_eq!(config.design(250.0).unwrap().sections().len(), 5);
+
+    let config = EcgFilterConfig {
+        notch_harmonics: 4,
+        ..config
+    };
+    assert!(matches!(
+        config.design(250.0),
+        Err(FilterError::TooManySections(4))
+    ));
+}
+
+#[test]
+fn fir_low_pass_in_float_and_fixed_point() {
+    let fir = Fir::<31>::low_pass(RATE, 40.0).unwrap();
+    assert!((fir.coefficients().iter().sum::<f32>() - 1.0).abs() < 1e-5);
+
+    let mut float = fir;
+    let mut fixed = fir.to_fixed();
+    let mut last = (0.0, 0);
+    for n in 0..200 {
+        let x = if n % 100 < 50 { 100_000 } else { -100_000 };
+        last = (float.process(x as f32), fixed.process(x));
+        assert!((last.0 - last.1 as f32).abs() <= 1.0);
+    }
+    assert!((last.0 + 100_000.0).abs() < 1.0);
+
+    let peak = sine_peak(&mut (Cascade::<1>::new(), fir), 100.0, 1e5, |x| x) / 1e5;
+    assert!(peak < 0.02, "{peak}");
+}
+
+#[test]
+fn channel_filters_remove_offsets_in_place() {
+    let config = EcgFilterConfig {
+        high_pass: Some(ads1298_rs::filters::HighPass::Monitoring),
+        notch: None,
+        notch_harmonics: 0,
+        low_pass: None,
+    };
+    let mut filters = ChannelFilters::new(config.design_fixed(RATE).unwrap());
+    let mut floats = ChannelFilters::new(config.design(RATE).unwrap());
+    let status = DataStatus::new(0, 0, 0);
+    let mut out = [0; 8];
+    let mut float_out = [0.0; 8];
+    for _ in 0..(10.0 * RATE) as usize {
+        let mut frame = Frame {
+            status,
+            channels: [10_000, -20_000, 0, 0, 0, 0, 0, 100_000],
+        };
+        float_out = floats.process(&frame);
+        filters.process_in_place(&mut frame);
+        out = frame.channels;
+    }
+    assert!(out.iter().all(|code| code.abs() <= 5), "{out:?}");
+    assert!(float_out.iter().all(|y| y.abs() <= 5.0), "{float_out:?}");
+
+    filters.reset();
+    let mut frame = Frame {
+        status,
+        channels: [1000; 8],
+    };
+    filters.process_in_place(&mut frame);
+    assert!(frame
+        .channels
+        .iter()
+        .all(|code| (990..=1000).contains(code)));
+}
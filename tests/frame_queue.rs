@@ -0,0 +1,77 @@
+use ads1298_rs::driver::frame::FRAME_SIZE;
+use ads1298_rs::driver::frame_queue::FrameQueue;
+use ads1298_rs::driver::initialization::{Default8Lead1x500, Initializer};
+use ads1298_rs::driver::registers::data::DataStatus;
+use ads1298_rs::driver::ADS1298;
+use ads1298_rs::simulator::Simulator;
+use embedded_hal::digital::InputPin;
+
+/// Frame whose CH1 code is `n`
+fn frame(n: u32) -> [u8; FRAME_SIZE] {
+    let status = DataStatus::new(0, 0, 0);
+    let mut raw = [0; FRAME_SIZE];
+    raw[..3].copy_from_slice(&[status.ds1.0, status.ds2.0, status.ds3.0]);
+    raw[3..6].copy_from_slice(&n.to_be_bytes()[1..]);
+    raw
+}
+
+#[test]
+fn reads_from_the_device_and_counts_overflows() {
+    let simulator = Simulator::new();
+    let mut driver = ADS1298::new(simulator.spi());
+    driver.init(Default8Lead1x500).unwrap();
+    let mut drdy = simulator.drdy();
+
+    let mut queue = FrameQueue::<2>::new();
+    let (mut producer, mut consumer) = queue.split();
+    for _ in 0..3 {
+        drdy.is_high().unwrap();
+        producer.read_from(&mut driver.operator).unwrap();
+    }
+    assert!(producer.is_full());
+    assert_eq!(consumer.len(), 2);
+    assert_eq!(consumer.take_overflows(), 1);
+    assert_eq!(consumer.take_overflows(), 0);
+
+    let status = consumer.dequeue_with(|frame| frame.status().ds1.0).unwrap();
+    assert_eq!(status & 0xf0, 0xc0);
+    assert!(consumer.dequeue().is_some());
+    assert!(consumer.dequeue().is_none());
+    assert_eq!(consumer.overflows(), 1);
+}
+
+#[test]
+fn frames_cross_threads_in_order() {
+    const FRAMES: u32 = 1000;
+    let mut queue = FrameQueue::<8>::new();
+    let (mut producer, mut consumer) = queue.split();
+    std::thread::scope(|s| {
+        s.spawn(move || {
+            let mut n = 0;
+            while n < FRAMES {
+                let pushed = producer
+                    .enqueue_with(|buffer| {
+                        *buffer = frame(n);
+                        Ok::<_, ()>(())
+                    })
+                    .unwrap();
+                if pushed {
+                    n += 1;
+                } else {
+                    std::thread::yield_now();
+                }
+            }
+        });
+        let mut expected = 0;
+        while expected < FRAMES {
+            match consumer.dequeue() {
+                Some(frame) => {
+                    assert_eq!(frame.channels[0], expected as i32);
+                    expected += 1;
+                }
+                None => std::thread::yield_now(),
+            }
+        }
+        assert!(consumer.is_empty());
+    });
+}
@@ -0,0 +1,161 @@
+use ads1298_rs::driver::initialization::{InitializeError, Initializer};
+use ads1298_rs::driver::profile::{Profile, ProfileError};
+use ads1298_rs::driver::ADS1298;
+use ads1298_rs::simulator::Simulator;
+use ads1298_rs::timing_checker::TimingChecker;
+
+const THREE_LEAD: &str = r#"
+name = "3-lead"
+data_rate = 500
+
+[[channels]]
+channel = 2
+gain = 6
+rld = { positive = true, negative = true }
+lead_off = { positive = true, negative = true }
+
+[[channels]]
+channel = 3
+gain = 6
+rld = { positive = true, negative = true }
+lead_off = { positive = true, negative = true }
+
+[[channels]]
+channel = 8
+input = "test-signal"
+gain = 1
+
+[test_signal]
+frequency = "fast"
+
+[wct]
+a = "IN2P"
+b = "IN2N"
+c = "IN3N"
+
+[rld]
+enabled = true
+
+[lead_off]
+current_na = 12
+threshold_percent = 87.5
+"#;
+
+#[test]
+fn toml_profile_configures_registers() {
+    let profile = Profile::from_toml(THREE_LEAD).unwrap();
+    let simulator = Simulator::new();
+    let mut driver = ADS1298::new(TimingChecker::new(simulator.spi()));
+    driver.init(&profile).unwrap();
+    driver.operator.spi().assert_compliant();
+
+    assert!(simulator.is_converting());
+    let registers = simulator.registers();
+    assert_eq!(registers[0x01], 0x86);
+    assert_eq!(registers[0x02], 0x11);
+    assert_eq!(registers[0x03], 0xcc);
+    assert_eq!(registers[0x04], 0x67);
+    assert_eq!(
+        &registers[0x05..=0x0c],
+        &[0x81, 0x00, 0x00, 0x81, 0x81, 0x81, 0x81, 0x15]
+    );
+    assert_eq!(&registers[0x0d..=0x10], &[0x06, 0x06, 0x06, 0x06]);
+    assert_eq!(registers[0x17], 0x02);
+    assert_eq!(registers[0x18], 0x0a);
+    assert_eq!(registers[0x19], 0xdd);
+}
+
+#[test]
+fn json_profile_round_trips() {
+    let profile = Profile::from_toml(THREE_LEAD).unwrap();
+    let json = serde_json::to_string(&profile).unwrap();
+    assert_eq!(Profile::from_json(&json).unwrap(), profile);
+
+    let path = std::env::temp_dir().join("ads1298-rs-profile.json");
+    std::fs::write(&path, json).unwrap();
+    assert_eq!(Profile::load(&path).unwrap(), profile);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn augmented_leads_need_their_wct_amplifiers() {
+    let wct = |augmented: &str, without: &str| {
+        let s = THREE_LEAD.replacen(without, "", 1).replacen(
+            "[wct]",
+            &format!("[wct]\n{augmented} = true"),
+            1,
+        );
+        match Profile::from_toml(&s) {
+            Err(ProfileError::WctAmplifierPoweredDown(amplifier)) => Some(amplifier),
+            Ok(_) => None,
+            Err(e) => panic!("{e}"),
+        }
+    };
+    // aVF = (WCTA + WCTB)/2
+    assert_eq!(wct("avf_ch6", "a = \"IN2P\""), Some('A'));
+    assert_eq!(wct("avf_ch6", "b = \"IN2N\""), Some('B'));
+    assert_eq!(wct("avf_ch6", "c = \"IN3N\""), None);
+    // aVL = (WCTA + WCTC)/2
+    assert_eq!(wct("avl_ch5", "a = \"IN2P\""), Some('A'));
+    assert_eq!(wct("avl_ch5", "b = \"IN2N\""), None);
+    assert_eq!(wct("avl_ch5", "c = \"IN3N\""), Some('C'));
+    // aVR = (WCTB + WCTC)/2
+    assert_eq!(wct("avr_ch7", "a = \"IN2P\""), None);
+    assert_eq!(wct("avr_ch7", "b = \"IN2N\""), Some('B'));
+    assert_eq!(wct("avr_ch7", "c = \"IN3N\""), Some('C'));
+}
+
+#[test]
+fn invalid_profiles_are_rejected() {
+    let invalid = |replace: &str, with: &str| {
+        let s = THREE_LEAD.replacen(replace, with, 1);
+        Profile::from_toml(&s).unwrap_err()
+    };
+    assert!(matches!(
+        invalid("gain = 6", "gain = 5"),
+        ProfileError::InvalidGain {
+            channel: 2,
+            gain: 5
+        }
+    ));
+    assert!(matches!(
+        invalid("channel = 3", "channel = 2"),
+        ProfileError::DuplicateChannel(2)
+    ));
+    assert!(matches!(
+        invalid("channel = 8", "channel = 9"),
+        ProfileError::InvalidChannel(9)
+    ));
+    assert!(matches!(
+        invalid("\"IN2P\"", "\"IN5P\""),
+        ProfileError::InvalidWctInput(_)
+    ));
+    assert!(matches!(
+        invalid("current_na = 12", "current_na = 10"),
+        ProfileError::InvalidLeadOffCurrent(10)
+    ));
+    assert!(matches!(
+        invalid("enabled = true", "enabled = false"),
+        ProfileError::RldNotEnabled(2)
+    ));
+    assert!(matches!(
+        invalid("[test_signal]\nfrequency = \"fast\"", ""),
+        ProfileError::TestSignalNotConfigured(8)
+    ));
+    assert!(matches!(
+        invalid("gain = 1", "gain = 1\ncolour = 3"),
+        ProfileError::Toml(_)
+    ));
+
+    let profile =
+        Profile::from_toml(&THREE_LEAD.replace("data_rate = 500", "data_rate = 300")).unwrap();
+    let simulator = Simulator::new();
+    let mut driver = ADS1298::new(simulator.spi());
+    assert!(matches!(
+        driver.init(&profile),
+        Err(InitializeError::ProfileError(
+            ProfileError::UnsupportedDataRate(300)
+        ))
+    ));
+    assert!(simulator.commands().is_empty());
+}
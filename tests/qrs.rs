@@ -0,0 +1,84 @@
+use ads1298_rs::driver::frame::Frame;
+use ads1298_rs::driver::registers::data::DataStatus;
+use ads1298_rs::qrs::{Beat, QrsDetector, QrsError};
+
+/// 72 BPM
+const RR_S: f64 = 60.0 / 72.0;
+
+/// Synthetic lead II code at `t`: R, S and T waves on a 0.3 Hz baseline wander
+fn ecg(t: f64) -> i32 {
+    let phase = t % RR_S;
+    let wave = |center: f64, width: f64, amplitude: f64| {
+        amplitude * (-((phase - center) / width).powi(2) / 2.0).exp()
+    };
+    let x = wave(0.2, 0.01, 200_000.0)
+        + wave(0.225, 0.008, -40_000.0)
+        + wave(0.45, 0.04, 50_000.0)
+        + 30_000.0 * (2.0 * std::f64::consts::PI * 0.3 * t).sin();
+    x as i32
+}
+
+fn frame(code: i32, loff_statn: u8) -> Frame {
+    let mut channels = [0; 8];
+    channels[1] = code;
+    Frame {
+        status: DataStatus::new(0, loff_statn, 0),
+        channels,
+    }
+}
+
+fn detect(detector: &mut QrsDetector, rate: f64, from: f64, to: f64, loff_statn: u8) -> Vec<Beat> {
+    let start = (from * rate) as usize;
+    let end = (to * rate) as usize;
+    (start..end)
+        .filter_map(|n| detector.process(&frame(ecg(n as f64 / rate), loff_statn)))
+        .collect()
+}
+
+#[test]
+fn beats_and_heart_rate_at_every_data_rate() {
+    for rate in [250.0, 500.0, 2000.0, 8000.0] {
+        let mut detector = QrsDetector::new(2, rate as f32).unwrap();
+        let beats = detect(&mut detector, rate, 0.0, 20.0, 0);
+        // Detection starts after 2 s of learning
+        assert!(
+            (20..=22).contains(&beats.len()),
+            "{rate} SPS: {}",
+            beats.len()
+        );
+        for beat in &beats {
+            let t = beat.r_peak as f64 / rate;
+            let error = (t % RR_S - 0.2).abs();
+            assert!(error < 0.01, "{rate} SPS: R peak off by {error} s");
+        }
+        let last = beats.last().unwrap();
+        assert!((last.rr.unwrap().as_secs_f64() - RR_S).abs() < 0.01);
+        assert!((last.heart_rate_bpm.unwrap() - 72.0).abs() < 1.0);
+        assert!((last.average_bpm.unwrap() - 72.0).abs() < 0.5);
+        assert!(beats[0].rr.is_none());
+    }
+}
+
+#[test]
+fn lead_off_suppresses_detection() {
+    let rate = 500.0;
+    let mut detector = QrsDetector::new(2, rate as f32).unwrap();
+    assert!(!detect(&mut detector, rate, 0.0, 10.0, 0).is_empty());
+
+    // IN2N off
+    assert!(detect(&mut detector, rate, 10.0, 15.0, 0b10).is_empty());
+    assert!(detector.is_lead_off());
+    assert!(detector.average_bpm().is_none());
+
+    // Another channel off does not matter, thresholds are learned again
+    let beats = detect(&mut detector, rate, 15.0, 25.0, 0b100);
+    assert!(!detector.is_lead_off());
+    assert!((6..=9).contains(&beats.len()), "{}", beats.len());
+    assert!(beats[0].r_peak as f64 / rate > 17.0);
+    assert!(beats[0].rr.is_none());
+
+    assert_eq!(
+        QrsDetector::new(9, 500.0).err(),
+        Some(QrsError::InvalidChannel(9))
+    );
+}
@@ -0,0 +1,345 @@
+use ads1298_rs::driver::clock::ClockSource;
+use ads1298_rs::driver::frame::Frame;
+use ads1298_rs::driver::frame::FRAME_SIZE;
+use ads1298_rs::driver::initialization::{Default8Lead1x500, Initializer};
+use ads1298_rs::driver::registers::data::DataStatus;
+use ads1298_rs::driver::registers::map::{RegisterMap, REGISTER_COUNT};
+use ads1298_rs::driver::stream_reader::FrameSource;
+use ads1298_rs::driver::ADS1298;
+use ads1298_rs::recording::capture::{CaptureError, CaptureHeader, CaptureWriter, Replay};
+use ads1298_rs::recording::csv::CsvWriter;
+use ads1298_rs::recording::edf::{EdfFormat, EdfWriter};
+use ads1298_rs::recording::wfdb::WfdbWriter;
+use ads1298_rs::recording::{LeadMapping, RecordingError, RecordingInfo, StartTime};
+use ads1298_rs::simulator::Simulator;
+use std::io::Cursor;
+use std::time::Duration;
+
+const START: StartTime = StartTime {
+    year: 2024,
+    month: 3,
+    day: 7,
+    hour: 9,
+    minute: 30,
+    second: 5,
+};
+
+/// Reset values, 250 SPS in LP mode, with CH3 at gain 1 and CH8 powered down
+fn register_map() -> RegisterMap {
+    let mut bytes = [0; REGISTER_COUNT];
+    bytes[0x01] = 0x06;
+    bytes[0x03] = 0x40;
+    bytes[0x07] = 0x10;
+    bytes[0x0c] = 0x80;
+    RegisterMap::from_bytes(&bytes)
+}
+
+fn frame(n: i32, loff_statp: u8) -> Frame {
+    Frame {
+        status: DataStatus::new(loff_statp, 0, 0),
+        channels: core::array::from_fn(|i| {
+            (n * 1000 - 0x40_0000) * if i % 2 == 0 { 1 } else { -1 }
+        }),
+    }
+}
+
+fn info() -> RecordingInfo {
+    RecordingInfo::from_register_map(
+        &register_map(),
+        ClockSource::Internal,
+        &LeadMapping::twelve_lead(),
+        START,
+    )
+    .unwrap()
+}
+
+fn write(format: EdfFormat, frames: i32) -> Vec<u8> {
+    let info = info();
+    let mut writer = EdfWriter::new(Cursor::new(vec![]), format, &info).unwrap();
+    for n in 0..frames {
+        let loff_statp = if (100..280).contains(&n) { 0b10 } else { 0 };
+        writer.write_frame(&frame(n, loff_statp)).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+fn field(bytes: &[u8], offset: usize, width: usize) -> &str {
+    std::str::from_utf8(&bytes[offset..offset + width])
+        .unwrap()
+        .trim_end()
+}
+
+/// Field of every signal in the header, `offset` is the start of the field array
+fn signal_fields(bytes: &[u8], signals: usize, offset: usize, width: usize) -> Vec<&str> {
+    (0..signals)
+        .map(|s| field(bytes, 256 + signals * offset + s * width, width))
+        .collect()
+}
+
+#[test]
+fn signals_follow_the_register_map() {
+    let info = RecordingInfo::from_register_map(
+        &register_map(),
+        ClockSource::Internal,
+        &LeadMapping::twelve_lead(),
+        START,
+    )
+    .unwrap();
+    assert_eq!(info.data_rate_hz, 250.0);
+    let labels: Vec<_> = info.signals.iter().map(|s| s.label.as_str()).collect();
+    assert_eq!(
+        labels,
+        ["ECG V6", "ECG I", "ECG II", "ECG V2", "ECG V3", "ECG V4", "ECG V5"]
+    );
+    assert_eq!(info.signals[2].gain, 1);
+    assert_eq!(info.signals[0].gain, 6);
+    assert_eq!(info.signals[0].vref, 2.4);
+
+    let mut leads = LeadMapping::numbered();
+    leads.0[1] = None;
+    let mut map = register_map();
+    map.ch5set.0 = 0x70;
+    let info = RecordingInfo::from_register_map(&map, ClockSource::Internal, &leads, START);
+    assert!(matches!(info, Err(RecordingError::InvalidGain(5))));
+    map.ch5set.0 = 0;
+    let info =
+        RecordingInfo::from_register_map(&map, ClockSource::Internal, &leads, START).unwrap();
+    let channels: Vec<_> = info.signals.iter().map(|s| s.channel).collect();
+    assert_eq!(channels, [1, 3, 4, 5, 6, 7]);
+}
+
+#[test]
+fn bdf_keeps_codes_and_annotates_lead_off() {
+    let bytes = write(EdfFormat::Bdf, 300);
+    let signals = 8;
+    assert_eq!(&bytes[..8], b"\xffBIOSEMI");
+    assert_eq!(field(&bytes, 88, 80), "Startdate 07-MAR-2024 X X X");
+    assert_eq!(field(&bytes, 168, 8), "07.03.24");
+    assert_eq!(field(&bytes, 176, 8), "09.30.05");
+    assert_eq!(field(&bytes, 184, 8), (256 * 9).to_string());
+    assert_eq!(field(&bytes, 192, 44), "BDF+C");
+    assert_eq!(field(&bytes, 236, 8), "2");
+    assert_eq!(field(&bytes, 244, 8), "1");
+    assert_eq!(field(&bytes, 252, 4), "8");
+
+    let labels = signal_fields(&bytes, signals, 0, 16);
+    assert_eq!(labels[1], "ECG I");
+    assert_eq!(labels[7], "BDF Annotations");
+    assert_eq!(signal_fields(&bytes, signals, 96, 8)[0], "uV");
+    let physical_max = signal_fields(&bytes, signals, 112, 8);
+    assert_eq!(physical_max[0], "400000");
+    assert_eq!(physical_max[2], "2400000");
+    let digital_min = signal_fields(&bytes, signals, 120, 8);
+    assert_eq!(digital_min[0], "-8388608");
+    let samples = signal_fields(&bytes, signals, 216, 8);
+    assert_eq!(samples[0], "250");
+    assert_eq!(samples[7], "40");
+
+    let header = 256 * (signals + 1);
+    let record = 7 * 250 * 3 + 120;
+    assert_eq!(bytes.len(), header + 2 * record);
+    let sample = |record_index: usize, signal: usize, n: usize| {
+        let offset = header + record_index * record + (signal * 250 + n) * 3;
+        let b = &bytes[offset..offset + 3];
+        i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8
+    };
+    assert_eq!(sample(0, 0, 10), frame(10, 0).channels[0]);
+    assert_eq!(sample(1, 1, 20), frame(270, 0).channels[1]);
+    assert_eq!(sample(1, 6, 49), frame(299, 0).channels[6]);
+    // Padding of the last record
+    assert_eq!(sample(1, 0, 50), 0);
+
+    let annotations = |record_index: usize| {
+        let offset = header + record_index * record + 7 * 250 * 3;
+        bytes[offset..offset + 120].to_vec()
+    };
+    assert_eq!(
+        annotations(0),
+        [
+            b"+0\x14\x14\0+0.4\x14Lead off IN2P\x14\0".as_slice(),
+            &[0; 95]
+        ]
+        .concat()
+    );
+    assert!(annotations(1).starts_with(b"+1\x14\x14\0+1.12\x14Lead on IN2P\x14\0"));
+}
+
+#[test]
+fn physical_ranges_use_the_exact_vref() {
+    let signals = 8;
+    // CH1 at gain 6, CH3 at gain 1, VREF = 2.4 V
+    let bytes = write(EdfFormat::Edf, 250);
+    let physical_min = signal_fields(&bytes, signals, 104, 8);
+    let physical_max = signal_fields(&bytes, signals, 112, 8);
+    assert_eq!(physical_min[..3], ["-400000", "-400000", "-2400000"]);
+    assert_eq!(physical_max[..3], ["399987.8", "399987.8", "2399927"]);
+
+    let bytes = write(EdfFormat::Bdf, 250);
+    let physical_min = signal_fields(&bytes, signals, 104, 8);
+    let physical_max = signal_fields(&bytes, signals, 112, 8);
+    assert_eq!(physical_min[..3], ["-400000", "-400000", "-2400000"]);
+    assert_eq!(physical_max[..3], ["400000", "400000", "2400000"]);
+}
+
+#[test]
+fn edf_drops_the_low_byte() {
+    let bytes = write(EdfFormat::Edf, 250);
+    let signals = 8;
+    assert_eq!(&bytes[..8], b"0       ");
+    assert_eq!(field(&bytes, 192, 44), "EDF+C");
+    assert_eq!(field(&bytes, 236, 8), "1");
+    assert_eq!(signal_fields(&bytes, signals, 0, 16)[7], "EDF Annotations");
+    assert_eq!(signal_fields(&bytes, signals, 120, 8)[0], "-32768");
+    assert_eq!(signal_fields(&bytes, signals, 128, 8)[0], "32767");
+    let physical_min: f64 = signal_fields(&bytes, signals, 104, 8)[0].parse().unwrap();
+    assert!((physical_min + 400_000.0).abs() < 0.1);
+    assert_eq!(signal_fields(&bytes, signals, 216, 8)[7], "60");
+
+    let header = 256 * (signals + 1);
+    assert_eq!(bytes.len(), header + 7 * 250 * 2 + 120);
+    let offset = header + (250 + 30) * 2;
+    let sample = i16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+    assert_eq!(i32::from(sample), frame(30, 0).channels[1] >> 8);
+}
+
+/// 10 frames captured from the simulator at 500 SPS with calibration offsets, and the frames
+/// the `StreamReader` decoded from them
+fn capture() -> (Vec<u8>, Vec<Frame>) {
+    let simulator = Simulator::new();
+    let mut driver = ADS1298::new(simulator.spi());
+    driver.init(Default8Lead1x500).unwrap();
+    driver.set_offsets([1, 2, 3, 4, 5, 6, 7, 8]);
+    let header = CaptureHeader::read(&mut driver).unwrap();
+
+    let mut writer = CaptureWriter::new(vec![], &header).unwrap();
+    let mut reader = driver.stream_reader(simulator.drdy()).unwrap();
+    let mut frames = vec![];
+    for n in 0..10 {
+        if n == 4 {
+            simulator.set_lead_off(0b100, 0);
+        }
+        let mut buffer = [0; FRAME_SIZE];
+        let mut frame = reader.read_into(&mut buffer).unwrap().to_frame();
+        frame.apply_offsets(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        frames.push(frame);
+        writer.write_frame(&buffer).unwrap();
+    }
+    assert_eq!(writer.frames(), 10);
+    (writer.finish().unwrap(), frames)
+}
+
+#[test]
+fn replay_yields_the_captured_frames() {
+    let (bytes, expected) = capture();
+    assert_eq!(&bytes[..8], b"ADS1298C");
+    assert_eq!(bytes.len(), 72 + 10 * FRAME_SIZE);
+
+    let replay = Replay::new(Cursor::new(&bytes)).unwrap();
+    assert_eq!(replay.header().offsets, [1, 2, 3, 4, 5, 6, 7, 8]);
+    assert!(replay.header().registers.config1.hr());
+    let frames: Vec<_> = replay.map(Result::unwrap).collect();
+    assert_eq!(frames.len(), 10);
+    for (frame, expected) in frames.iter().zip(&expected) {
+        assert_eq!(frame.channels, expected.channels);
+        assert_eq!(frame.status.loff_statp(), expected.status.loff_statp());
+    }
+
+    // 500 SPS
+    let replay = Replay::new(Cursor::new(&bytes)).unwrap();
+    assert_eq!(
+        replay
+            .for_duration(Duration::from_millis(10))
+            .unwrap()
+            .count(),
+        5
+    );
+    let mut frames = Replay::new(Cursor::new(&bytes)).unwrap().until_lead_off();
+    assert_eq!(frames.by_ref().count(), 4);
+    assert_eq!(frames.lead_off().unwrap().status.loff_statp(), 0b100);
+}
+
+#[test]
+fn replay_rejects_damaged_captures() {
+    let (mut bytes, _) = capture();
+    bytes.truncate(bytes.len() - 1);
+    let mut replay = Replay::new(Cursor::new(&bytes)).unwrap();
+    assert_eq!(replay.by_ref().take(9).filter(Result::is_ok).count(), 9);
+    assert!(matches!(
+        replay.next(),
+        Some(Err(CaptureError::TruncatedFrame))
+    ));
+    assert_eq!(replay.frames(), 9);
+
+    bytes[0] = b'X';
+    assert!(matches!(
+        Replay::new(Cursor::new(&bytes)),
+        Err(CaptureError::NotACapture)
+    ));
+    assert!(matches!(
+        Replay::new(Cursor::new(&bytes[..20])),
+        Err(CaptureError::NotACapture)
+    ));
+}
+
+#[test]
+fn csv_rows_hold_microvolts_and_status() {
+    let mut info = info();
+    info.signals[0].label = "V6, chest".to_owned();
+    let mut writer = CsvWriter::new(vec![], &info).unwrap();
+    writer.write_frame(&frame(0, 0)).unwrap();
+    writer.write_frame(&frame(1, 0b1000_0010)).unwrap();
+    let csv = String::from_utf8(writer.finish().unwrap()).unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(
+        lines[0],
+        "index,time_s,\"V6, chest\",ECG I,ECG II,ECG V2,ECG V3,ECG V4,ECG V5,\
+         loff_statp,loff_statn,gpio"
+    );
+    let row: Vec<_> = lines[2].split(',').collect();
+    assert_eq!(row.len(), 12);
+    assert_eq!(&row[..2], ["1", "0.004000"]);
+    // CH1 at gain 6 and CH3 at gain 1, VREF = 2.4 V
+    let uv = |code: i32, gain: f64| f64::from(code) * 2.4e6 / f64::from(0x7f_ffff) / gain;
+    let ch1: f64 = row[2].parse().unwrap();
+    assert!((ch1 - uv(frame(1, 0).channels[0], 6.0)).abs() < 0.001);
+    let ch3: f64 = row[4].parse().unwrap();
+    assert!((ch3 - uv(frame(1, 0).channels[2], 1.0)).abs() < 0.001);
+    assert_eq!(&row[9..], ["0x82", "0x00", "0x0"]);
+}
+
+#[test]
+fn wfdb_writes_format_24_and_header() {
+    let info = info();
+    let mut writer = WfdbWriter::new(vec![], vec![], "session_1", &info).unwrap();
+    for n in 0..3 {
+        writer.write_frame(&frame(n, 0)).unwrap();
+    }
+    let (dat, hea) = writer.finish().unwrap();
+    assert_eq!(dat.len(), 3 * 7 * 3);
+    let sample = |n: usize, signal: usize| {
+        let offset = (n * 7 + signal) * 3;
+        i32::from_le_bytes([0, dat[offset], dat[offset + 1], dat[offset + 2]]) >> 8
+    };
+    assert_eq!(sample(0, 0), frame(0, 0).channels[0]);
+    assert_eq!(sample(2, 6), frame(2, 0).channels[6]);
+
+    let hea = String::from_utf8(hea).unwrap();
+    let lines: Vec<_> = hea.lines().collect();
+    assert_eq!(lines.len(), 8);
+    assert_eq!(lines[0], "session_1 7 250 3 09:30:05 07/03/2024");
+    let checksum = (0..3).map(|n| frame(n, 0).channels[1]).sum::<i32>() as i16;
+    assert_eq!(
+        lines[2],
+        format!(
+            "session_1.dat 24 20971.5175(0)/mV 24 0 {} {checksum} 0 ECG I",
+            frame(0, 0).channels[1]
+        )
+    );
+    assert!(lines[3].starts_with("session_1.dat 24 3495.252917(0)/mV 24 0 "));
+    assert!(lines[3].ends_with(" 0 ECG II"));
+
+    assert!(matches!(
+        WfdbWriter::new(vec![], vec![], "../x", &info),
+        Err(RecordingError::InvalidField("record name"))
+    ));
+}
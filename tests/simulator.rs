@@ -0,0 +1,620 @@
+use ads1298_rs::driver::clock::ClockSource;
+use ads1298_rs::driver::data_rate::PowerMode;
+use ads1298_rs::driver::frame::{FrameBuffers, FRAME_SIZE};
+use ads1298_rs::driver::gpio::GpioError;
+use ads1298_rs::driver::initialization::{
+    Default8Lead1x500, InitStep, InitializeError, Initializer,
+};
+use ads1298_rs::driver::measurement::MeasureError;
+use ads1298_rs::driver::registers::access::{ReadFromRegister, WriteToRegister};
+use ads1298_rs::driver::registers::data::{ChSetReg, Config1Reg, Config2Reg, GpioReg};
+use ads1298_rs::driver::registers::map::RegisterMap;
+use ads1298_rs::driver::registers::{DataRegister, CH3SET, CONFIG1, CONFIG2, CONFIG4, GPIO, ID};
+use ads1298_rs::driver::supply::SupplyMonitor;
+use ads1298_rs::driver::temperature::temperature_from_microvolts;
+use ads1298_rs::driver::timestamp::{NoClock, Timestamper};
+use ads1298_rs::driver::{ConfigError, StreamError, ADS1298};
+use ads1298_rs::simulator::{SimulatedSpi, Simulator, DEVICE_ID};
+use ads1298_rs::timing_checker::{RecordedOperation, TimingChecker};
+use embedded_hal::digital::{InputPin, OutputPin};
+use std::time::Duration;
+
+fn initialized() -> (Simulator, ADS1298<SimulatedSpi>) {
+    let simulator = Simulator::new();
+    let mut driver = ADS1298::new(simulator.spi());
+    driver.init(Default8Lead1x500).unwrap();
+    (simulator, driver)
+}
+
+#[test]
+fn init_configures_registers() {
+    let (simulator, mut driver) = initialized();
+    assert_eq!(driver.read(ID).unwrap().0, DEVICE_ID);
+    assert_eq!(simulator.register(0x01), 0x86);
+    assert_eq!(simulator.register(0x05), 0x20);
+    assert!(!simulator.is_continuous_read());
+    assert!(simulator.is_converting());
+}
+
+#[test]
+fn init_resets_and_reports_spi_faults() {
+    let simulator = Simulator::new();
+    simulator.set_register(0x00, 0x00);
+    let mut driver = ADS1298::new(simulator.spi());
+    // `RESET` restores the ID
+    assert!(driver.init(Default8Lead1x500).is_ok());
+
+    let simulator = Simulator::new();
+    let mut driver = ADS1298::new(simulator.spi());
+    simulator.inject_fault();
+    let e = driver.init(Default8Lead1x500).unwrap_err();
+    assert!(matches!(
+        e,
+        InitializeError::CommandError {
+            step: InitStep::Reset,
+            ..
+        }
+    ));
+    assert_eq!(
+        e.to_string(),
+        "failed to send RESET: SPI write failed: SimulatorError"
+    );
+
+    // `RESET`, `SDATAC` and `RREG` of `ID` come first
+    simulator.inject_fault_after(3);
+    let e = driver.init(Default8Lead1x500).unwrap_err();
+    assert!(matches!(
+        e,
+        InitializeError::WriteError {
+            address: 0x01,
+            data: 0x86,
+            ..
+        }
+    ));
+    assert!(e
+        .to_string()
+        .starts_with("failed to write 86h to CONFIG1 (01h)"));
+    assert!(core::error::Error::source(&e).is_some());
+}
+
+#[test]
+fn registers_round_trip() {
+    let (simulator, mut driver) = initialized();
+    let mut x = Config2Reg(0);
+    x.set_int_test(true);
+    x.set_test_freq(0b01);
+    driver.write(CONFIG2, x).unwrap();
+    assert_eq!(simulator.register(0x02), 0x11);
+    assert_eq!(driver.read(CONFIG2).unwrap().0, 0x11);
+}
+
+#[test]
+fn registers_ignored_in_continuous_read_mode() {
+    let simulator = Simulator::new();
+    let mut driver = ADS1298::new(simulator.spi());
+    driver.write(CONFIG1, Config1Reg(0x85)).unwrap();
+    assert_eq!(simulator.register(0x01), 0x06);
+}
+
+#[test]
+fn stream_reader_reads_test_signal() {
+    let (simulator, mut driver) = initialized();
+    driver.write(CONFIG2, Config2Reg(0x13)).unwrap();
+    driver.write(CH3SET, ChSetReg(0x15)).unwrap();
+
+    let mut reader = driver.stream_reader(simulator.drdy()).unwrap();
+    let registers = reader.read().unwrap();
+    assert_eq!(registers.len(), 11);
+    assert!(matches!(registers[0], DataRegister::DATA_STATUS_1(x) if x.0 & 0xf0 == 0xc0));
+
+    let frame = reader.read_frame().unwrap();
+    // 1 × VREF / 2400 at gain 1 is 1/2400 of full scale
+    assert_eq!(
+        frame.channels[2],
+        (0x7f_ffff as f32 / 2400.0).round() as i32
+    );
+}
+
+#[test]
+fn calibration_offsets_are_subtracted() {
+    let (simulator, mut driver) = initialized();
+    let mut drdy = simulator.drdy();
+    simulator.set_input_short_offsets([10, -20, 30, -40, 50, -60, 70, -80]);
+
+    let measurement = driver.calibrate_offsets(&mut drdy, 16).unwrap();
+    assert_eq!(measurement.offset, [20, -40, 60, -80, 100, -120, 140, -160]);
+    assert!(measurement.rms_uv.iter().all(|rms| *rms > 0.0));
+    assert_eq!(simulator.register(0x05), 0x20);
+
+    simulator.set_normal_input_amplitude(0.0);
+    let frame = driver
+        .stream_reader(simulator.drdy())
+        .unwrap()
+        .read_frame()
+        .unwrap();
+    assert_eq!(frame.channels, [-20, 40, -60, 80, -100, 120, -140, 160]);
+}
+
+#[test]
+fn input_short_measurement_restores_channels_on_errors() {
+    let (simulator, mut driver) = initialized();
+    let mut drdy = simulator.drdy();
+    let registers = simulator.registers();
+
+    assert!(matches!(
+        driver.measure_input_short(&mut drdy, 0),
+        Err(MeasureError::NoSamples)
+    ));
+    assert_eq!(simulator.registers(), registers);
+    assert!(simulator.is_converting());
+
+    // Reserved gain `111` on CH3
+    simulator.set_register(0x07, 0x70);
+    let registers = simulator.registers();
+    assert!(matches!(
+        driver.measure_input_short(&mut drdy, 16),
+        Err(MeasureError::InvalidGain { channel: 3 })
+    ));
+    assert_eq!(simulator.registers(), registers);
+    assert_eq!(driver.offsets(), [0; 8]);
+}
+
+#[test]
+fn measurements_leave_idle_device_idle() {
+    let (simulator, mut driver) = initialized();
+    let mut drdy = simulator.drdy();
+    driver.operator.stop().unwrap();
+
+    driver.measure_input_short(&mut drdy, 16).unwrap();
+    assert!(!simulator.is_converting());
+    assert!(!driver.operator.is_started());
+
+    driver.read_temperature(&mut drdy, 3, 4).unwrap();
+    assert!(!simulator.is_converting());
+    assert!(!driver.operator.is_started());
+
+    driver
+        .measure_supplies(&mut drdy, &Default::default())
+        .unwrap();
+    assert!(!simulator.is_converting());
+    assert!(!driver.operator.is_started());
+
+    driver.self_test(&mut drdy, 16).unwrap();
+    assert!(!simulator.is_converting());
+    assert!(!driver.operator.is_started());
+}
+
+#[test]
+fn self_test_measures_test_signal_and_restores_registers() {
+    let (simulator, mut driver) = initialized();
+    let mut drdy = simulator.drdy();
+    driver.power_down_channels(0x80).unwrap();
+    let registers = simulator.registers();
+
+    let report = driver.self_test(&mut drdy, 16).unwrap();
+    assert!(report.is_ok(), "{report:?}");
+    assert_eq!(report.expected_uv, 2000.0);
+    assert!(report.test_signal_uv[..7]
+        .iter()
+        .all(|uv| (uv.unwrap() - 2000.0).abs() < 1.0));
+    assert_eq!(report.test_signal_uv[7], None);
+    assert_eq!(report.noise.samples, 16);
+    assert_eq!(simulator.registers(), registers);
+    assert!(simulator.is_converting());
+
+    simulator.set_register(0x00, 0x90);
+    let report = driver.self_test(&mut drdy, 16).unwrap();
+    assert!(!report.id_ok);
+    assert!(!report.is_ok());
+}
+
+#[test]
+fn temperature_conversion_and_channel_checks() {
+    assert_eq!(temperature_from_microvolts(145_300.0), 25.0);
+    assert_eq!(temperature_from_microvolts(145_300.0 + 490.0 * 12.0), 37.0);
+
+    let (simulator, mut driver) = initialized();
+    let mut drdy = simulator.drdy();
+    let registers = simulator.registers();
+    for channel in [0, 9] {
+        assert!(matches!(
+            driver.read_temperature(&mut drdy, channel, 4),
+            Err(MeasureError::InvalidChannel(c)) if c == channel
+        ));
+    }
+    assert!(matches!(
+        driver.read_temperature(&mut drdy, 3, 0),
+        Err(MeasureError::NoSamples)
+    ));
+    assert_eq!(simulator.registers(), registers);
+}
+
+#[test]
+fn temperature_and_supplies() {
+    let (simulator, mut driver) = initialized();
+    let mut drdy = simulator.drdy();
+    simulator.set_temperature(37.0);
+    simulator.set_supplies(3.3, 0.0, 1.8);
+
+    let temperature = driver.read_temperature(&mut drdy, 3, 4).unwrap();
+    assert!((temperature - 37.0).abs() < 0.1);
+    assert_eq!(simulator.register(0x07), 0x20);
+
+    let report = driver
+        .measure_supplies(&mut drdy, &Default::default())
+        .unwrap();
+    assert!((report.avdd - 3.3).abs() < 0.01);
+    assert!((report.dvdd - 1.8).abs() < 0.01);
+    assert!(report.is_ok());
+}
+
+#[test]
+fn supplies_out_of_range_and_invalid_channels() {
+    let (simulator, mut driver) = initialized();
+    let mut drdy = simulator.drdy();
+    simulator.set_supplies(3.0, 0.0, 1.8);
+
+    let monitor = SupplyMonitor::with_nominal(3.3, 1.8, 0.05);
+    let report = driver.measure_supplies(&mut drdy, &monitor).unwrap();
+    assert!(!report.avdd_in_range);
+    assert!(report.dvdd_in_range);
+    assert!(!report.is_ok());
+
+    let registers = simulator.registers();
+    // CH3 and CH4 measure DVDD, the others AVDD
+    let invalid = [
+        SupplyMonitor {
+            avdd_channel: 3,
+            ..Default::default()
+        },
+        SupplyMonitor {
+            dvdd_channel: 1,
+            ..Default::default()
+        },
+    ];
+    for (monitor, channel) in invalid.iter().zip([3, 1]) {
+        assert!(matches!(
+            driver.measure_supplies(&mut drdy, monitor),
+            Err(MeasureError::InvalidChannel(c)) if c == channel
+        ));
+    }
+    assert_eq!(simulator.registers(), registers);
+}
+
+#[test]
+fn gpio_pins() {
+    let (simulator, mut driver) = initialized();
+    let mut pin = driver.gpio(2).unwrap();
+    pin.set_as_output().unwrap();
+    pin.set_high().unwrap();
+    assert!(pin.is_high().unwrap());
+    let x: GpioReg = driver.read(GPIO).unwrap();
+    assert_eq!(x.gpio_c(), 0b1101);
+    assert_eq!(x.gpio_d(), 0b0010);
+
+    simulator.set_gpio_inputs(0b0001);
+    assert!(driver.gpio(1).unwrap().is_high().unwrap());
+
+    let mut x = driver.read(CONFIG4).unwrap();
+    x.set_resp_freq(0b010);
+    driver.write(CONFIG4, x).unwrap();
+    assert!(driver.gpio(3).is_err());
+    assert!(driver.gpio(5).is_err());
+}
+
+#[test]
+fn gpio_writes_fail_once_respiration_claims_the_pin() {
+    let (simulator, mut driver) = initialized();
+    let mut pin = driver.gpio(4).unwrap();
+    pin.set_as_output().unwrap();
+    pin.set_high().unwrap();
+
+    // `resp_freq` = 010, written behind the driver's back
+    simulator.set_register(0x17, simulator.register(0x17) | 0b0100_0000);
+    assert!(matches!(
+        pin.set_low(),
+        Err(GpioError::ClaimedByRespiration(4))
+    ));
+    assert_eq!(simulator.register(0x14) & 0b1000_0000, 0b1000_0000);
+}
+
+#[test]
+fn convert_once_leaves_device_idle() {
+    let (simulator, mut driver) = initialized();
+    let mut drdy = simulator.drdy();
+    driver.operator.stop().unwrap();
+
+    let before = simulator.samples();
+    driver.convert_once(&mut drdy).unwrap();
+    assert_eq!(simulator.samples(), before + 1);
+    assert!(!simulator.is_converting());
+    assert!(driver.read(CONFIG4).unwrap().single_shot());
+}
+
+#[test]
+fn reconfiguring_after_convert_once_starts_no_conversion() {
+    let (simulator, mut driver) = initialized();
+    let mut drdy = simulator.drdy();
+    driver.operator.stop().unwrap();
+    driver.convert_once(&mut drdy).unwrap();
+    assert!(!driver.operator.is_started());
+
+    let starts = simulator.commands().iter().filter(|&&c| c == 0x08).count();
+    driver.set_data_rate(1000).unwrap();
+    assert_eq!(
+        simulator.commands().iter().filter(|&&c| c == 0x08).count(),
+        starts
+    );
+    assert!(!simulator.is_converting());
+}
+
+#[test]
+fn convert_once_drops_a_frame_left_unread() {
+    let (simulator, mut driver) = initialized();
+    let mut drdy = simulator.drdy();
+    assert!(drdy.is_low().unwrap());
+    driver.operator.stop().unwrap();
+
+    let before = simulator.samples();
+    driver.convert_once(&mut drdy).unwrap();
+    assert_eq!(simulator.samples(), before + 1);
+}
+
+#[test]
+fn clock_drives_rates_and_delays() {
+    let clock = ClockSource::External(4_096_000);
+    assert_eq!(clock.tclk_ns(), 245);
+    let simulator = Simulator::with_clock(clock);
+    let checker = TimingChecker::new(simulator.spi()).with_clock(clock);
+    let mut driver = ADS1298::with_clock(checker, clock);
+    driver.init(Default8Lead1x500).unwrap();
+    assert_eq!(driver.data_rate_hz().unwrap(), Some(1000.0));
+    assert_eq!(
+        driver.test_signal_hz().unwrap(),
+        Some(4_096_000.0 / 2097152.0)
+    );
+    assert!(driver.operator.spi().transactions().contains(&vec![
+        RecordedOperation::Write(vec![0x06]),
+        RecordedOperation::DelayNs(18 * 245),
+    ]));
+    let report = driver.self_test(&mut simulator.drdy(), 16).unwrap();
+    assert!(report.is_ok(), "{report:?}");
+    driver.operator.spi().assert_compliant();
+}
+
+#[test]
+fn data_rate_keeps_power_mode_when_possible() {
+    let (simulator, mut driver) = initialized();
+    let rate = driver.set_data_rate(1000).unwrap();
+    assert_eq!(
+        (rate.power_mode, rate.dr),
+        (PowerMode::HighResolution, 0b101)
+    );
+    let rate = driver.set_data_rate(250).unwrap();
+    assert_eq!((rate.power_mode, rate.dr), (PowerMode::LowPower, 0b110));
+    assert_eq!(driver.sample_period().unwrap(), Duration::from_millis(4));
+    assert!(driver.set_data_rate(300).is_err());
+    assert!(simulator.is_converting());
+
+    driver.operator.start_stream().unwrap();
+    driver.set_power_mode(PowerMode::HighResolution).unwrap();
+    assert_eq!(simulator.register(0x01), 0x86);
+    assert!(simulator.is_continuous_read());
+}
+
+#[test]
+fn power_down_and_standby() {
+    let (simulator, mut driver) = initialized();
+    let active = driver.estimate_current().unwrap();
+    let ch2 = simulator.register(0x06);
+
+    driver.set_used_channels(0b0000_0001).unwrap();
+    let x = ChSetReg(simulator.register(0x06));
+    assert!(x.pd());
+    assert_eq!(x.mux(), 0b001);
+    assert!(!ChSetReg(simulator.register(0x05)).pd());
+    let reduced = driver.estimate_current().unwrap();
+    assert!(reduced.avdd_ma < active.avdd_ma);
+
+    driver.power_up_channels(0xff).unwrap();
+    assert_eq!(simulator.register(0x06), ch2);
+    assert_eq!(driver.estimate_current().unwrap(), active);
+
+    driver.enter_standby().unwrap();
+    assert!(simulator.is_standby());
+    assert!(driver.estimate_current().unwrap().avdd_ma < reduced.avdd_ma);
+    driver.wake().unwrap();
+    assert!(!simulator.is_standby());
+    assert!(simulator.is_converting());
+    assert_eq!(simulator.commands().last(), Some(&0x08));
+}
+
+#[test]
+fn register_map_round_trip_and_diff() {
+    let (simulator, mut driver) = initialized();
+    let map = driver.read_register_map().unwrap();
+    assert_eq!(map.to_bytes(), simulator.registers());
+    assert_eq!(map.id.0, DEVICE_ID);
+    assert!(map.config1.hr());
+    assert!(map
+        .to_string()
+        .contains("CONFIG1    01h = 86h Config1Reg {"));
+
+    let mut expected = map;
+    expected.ch2set.set_gain(0b101);
+    expected.config4.set_single_shot(true);
+    let changes = map.diff(&expected);
+    assert_eq!(
+        changes.iter().map(|c| c.name()).collect::<Vec<_>>(),
+        ["CH2SET", "CONFIG4"]
+    );
+    assert_eq!(changes[1].changed_bits(), 0b1000);
+
+    driver.write_register_map(&expected).unwrap();
+    assert_eq!(driver.read_register_map().unwrap(), expected);
+    assert!(simulator.is_converting());
+
+    let json = serde_json::to_string(&expected).unwrap();
+    assert_eq!(
+        serde_json::from_str::<RegisterMap>(&json).unwrap(),
+        expected
+    );
+    let toml = toml::to_string(&expected).unwrap();
+    assert!(toml.contains("config1 = 134"));
+    assert_eq!(toml::from_str::<RegisterMap>(&toml).unwrap(), expected);
+}
+
+#[test]
+fn write_register_writes_one_register() {
+    let simulator = Simulator::new();
+    let mut driver = ADS1298::new(TimingChecker::new(simulator.spi()));
+    driver.init(Default8Lead1x500).unwrap();
+    driver.operator.spi_mut().clear();
+
+    driver.write_register(0x02, 0x11).unwrap();
+    let writes: Vec<_> = driver
+        .operator
+        .spi()
+        .transactions()
+        .iter()
+        .flatten()
+        .filter(|operation| matches!(operation, RecordedOperation::Write(bytes) if bytes[0] & 0xe0 == 0x40))
+        .collect();
+    assert_eq!(writes, [&RecordedOperation::Write(vec![0x42, 0x00, 0x11])]);
+    assert_eq!(simulator.register(0x02), 0x11);
+    assert!(simulator.is_converting());
+    driver.operator.spi().assert_compliant();
+
+    let registers = simulator.registers();
+    for address in [0x00, 0x12, 0x13, 0x1a, 0xff] {
+        assert!(matches!(
+            driver.write_register(address, 0x11),
+            Err(ConfigError::NotWritable(a)) if a == address
+        ));
+    }
+    assert_eq!(simulator.registers(), registers);
+}
+
+#[test]
+fn frames_read_into_borrowed_buffers() {
+    let (simulator, mut driver) = initialized();
+    let mut reader = driver.stream_reader(simulator.drdy()).unwrap();
+
+    let mut buffer = [0; FRAME_SIZE];
+    let frame = reader.read_into(&mut buffer).unwrap();
+    assert_eq!(frame.status().ds1.0 & 0xf0, 0xc0);
+    let raw = frame.raw();
+    let ch1 = i32::from_be_bytes([raw[3], raw[4], raw[5], 0]) >> 8;
+    assert_eq!(frame.channel(0), ch1);
+    assert_eq!(
+        frame.channels().collect::<Vec<_>>(),
+        frame.to_frame().channels
+    );
+
+    let mut buffers = FrameBuffers::<3>::new();
+    assert!(buffers.latest().is_none());
+    for _ in 0..4 {
+        reader.read_into_ring(&mut buffers).unwrap();
+    }
+    assert_eq!(buffers.len(), 3);
+    let latest = buffers.latest().unwrap().raw();
+    assert_eq!(buffers.iter().last().unwrap().raw(), latest);
+    assert_eq!(buffers.iter().count(), 3);
+}
+
+#[test]
+fn stream_reader_iterates_frames() {
+    let (simulator, mut driver) = initialized();
+    let before = simulator.samples();
+    let frames: Vec<_> = driver
+        .stream_reader(simulator.drdy())
+        .unwrap()
+        .samples(5)
+        .map(Result::unwrap)
+        .collect();
+    // Every frame is a new conversion
+    assert_eq!(simulator.samples(), before + 5);
+    assert!(frames
+        .windows(2)
+        .all(|pair| pair[0].channels != pair[1].channels));
+
+    // 500 SPS
+    let before = simulator.samples();
+    let frames = driver
+        .stream_reader(simulator.drdy())
+        .unwrap()
+        .for_duration(Duration::from_millis(100))
+        .unwrap();
+    assert_eq!(frames.count(), 50);
+    assert_eq!(simulator.samples(), before + 50);
+
+    let mut frames = driver
+        .stream_reader(simulator.drdy())
+        .unwrap()
+        .until_lead_off();
+    assert!(frames.by_ref().take(3).all(|frame| frame.is_ok()));
+    simulator.set_lead_off(0b0000_0000, 0b0000_0100);
+    assert!(frames.next().is_none());
+    let lead_off = frames.lead_off().unwrap().status;
+    assert_eq!((lead_off.loff_statp(), lead_off.loff_statn()), (0, 0b100));
+    assert!(frames.next().is_none());
+
+    driver.write(CONFIG1, Config1Reg(0x87)).unwrap();
+    assert!(matches!(
+        driver
+            .stream_reader(simulator.drdy())
+            .unwrap()
+            .for_duration(Duration::from_secs(1)),
+        Err(StreamError::ReservedDataRate)
+    ));
+}
+
+#[test]
+fn frames_are_timestamped_and_gaps_reported() {
+    let (simulator, mut driver) = initialized();
+    let frames: Vec<_> = driver
+        .stream_reader(simulator.drdy())
+        .unwrap()
+        .timestamped(NoClock)
+        .unwrap()
+        .take(3)
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(
+        frames.iter().map(|f| f.index).collect::<Vec<_>>(),
+        [0, 1, 2]
+    );
+    assert_eq!(frames[2].elapsed, Duration::from_millis(4));
+    assert!(frames
+        .iter()
+        .all(|f| !f.is_after_gap() && f.clock_us.is_none()));
+
+    // Reads at 0, 2, 8 and 10.5 ms at 500 SPS
+    let mut reads = [0, 2_000, 8_000, 10_500].into_iter();
+    let mut reader = driver.stream_reader(simulator.drdy()).unwrap();
+    let mut timestamper = reader.timestamper(|| reads.next().unwrap()).unwrap();
+    let tagged: Vec<_> = (0..4)
+        .map(|_| timestamper.tag(reader.read_frame().unwrap()))
+        .collect();
+    assert_eq!(
+        tagged.iter().map(|f| f.index).collect::<Vec<_>>(),
+        [0, 1, 4, 5]
+    );
+    assert_eq!(tagged[2].missed, 2);
+    assert_eq!(tagged[3].clock_us, Some(10_500));
+    assert_eq!(timestamper.missed(), 2);
+
+    let mut timestamper = Timestamper::new(500.0);
+    let frame = reader.read_frame().unwrap();
+    let tagged: Vec<_> = [7, 8, 12]
+        .into_iter()
+        .map(|edges| timestamper.tag_counted(frame, edges))
+        .collect();
+    assert_eq!(
+        tagged.iter().map(|f| f.missed).collect::<Vec<_>>(),
+        [0, 0, 3]
+    );
+    assert_eq!(tagged[2].index, 5);
+    timestamper.restart();
+    assert_eq!(timestamper.tag_counted(frame, 20).index, 0);
+}
@@ -0,0 +1,96 @@
+use ads1298_rs::driver::initialization::{Default8Lead1x500, Initializer};
+use ads1298_rs::driver::registers::access::ReadFromRegister;
+use ads1298_rs::driver::registers::CONFIG1;
+use ads1298_rs::driver::ADS1298;
+use ads1298_rs::driver::clock::ClockSource;
+use ads1298_rs::simulator::Simulator;
+use ads1298_rs::timing_checker::{BusTiming, Rule, TimingChecker};
+use embedded_hal::spi::{Operation, SpiDevice};
+
+#[test]
+fn driver_is_compliant() {
+    let simulator = Simulator::new();
+    let mut driver = ADS1298::new(TimingChecker::new(simulator.spi()));
+    driver.init(Default8Lead1x500).unwrap();
+    driver
+        .stream_reader(simulator.drdy())
+        .unwrap()
+        .read_frame()
+        .unwrap();
+    driver.operator.spi().assert_compliant();
+}
+
+#[test]
+fn register_access_in_continuous_read_mode() {
+    let simulator = Simulator::new();
+    let mut driver = ADS1298::new(TimingChecker::new(simulator.spi()));
+    driver.read(CONFIG1).unwrap();
+    assert_eq!(
+        driver.operator.spi().violations()[0].rule,
+        Rule::CommandInContinuousRead { opcode: 0x21 }
+    );
+}
+
+#[test]
+fn missing_delay_and_bus_timing() {
+    let simulator = Simulator::new();
+    let mut spi = TimingChecker::new(simulator.spi()).with_bus_timing(BusTiming {
+        cs_to_sclk_ns: 10,
+        sclk_to_cs_ns: 1000,
+        sclk_hz: 8_000_000,
+    });
+    spi.transaction(&mut [Operation::Write(&[0x06])]).unwrap();
+    spi.transaction(&mut [Operation::Write(&[0x11]), Operation::DelayNs(100)])
+        .unwrap();
+    let rules: Vec<_> = spi.violations().iter().map(|v| v.rule.clone()).collect();
+    assert!(matches!(rules[0], Rule::SclkToCs { .. }));
+    assert!(matches!(rules[1], Rule::CommandDecode { .. }));
+    assert!(matches!(rules[2], Rule::CommandDelay { opcode: 0x06, .. }));
+    assert!(matches!(
+        rules[3],
+        Rule::CommandDelay {
+            opcode: 0x11,
+            delay_ns: 100,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn bus_timing_uses_final_clock() {
+    let simulator = Simulator::new();
+    let timing = BusTiming {
+        cs_to_sclk_ns: 10,
+        sclk_to_cs_ns: 2000,
+        sclk_hz: 4_000_000,
+    };
+    // Compliant at 2.048 MHz, too fast at 1 MHz
+    let clock = ClockSource::External(1_000_000);
+    let checkers = [
+        TimingChecker::new(simulator.spi())
+            .with_bus_timing(timing)
+            .with_clock(clock),
+        TimingChecker::new(simulator.spi())
+            .with_clock(clock)
+            .with_bus_timing(timing),
+    ];
+    for spi in &checkers {
+        let rules: Vec<_> = spi.violations().iter().map(|v| v.rule.clone()).collect();
+        assert_eq!(
+            rules,
+            [
+                Rule::SclkToCs {
+                    actual_ns: 2000,
+                    required_ns: 4000,
+                },
+                Rule::CommandDecode {
+                    sclk_hz: 4_000_000,
+                    max_sclk_hz: 2_000_000,
+                },
+            ]
+        );
+    }
+    assert!(TimingChecker::new(simulator.spi())
+        .with_bus_timing(timing)
+        .is_compliant());
+}